@@ -1,13 +1,28 @@
-use crate::config::{ConfigManager, ConfigSet, NotificationConfigs};
+use crate::config::{ConfigManager, ConfigSet};
+#[cfg(feature = "editor")]
 use crate::editor::Editor;
 use crate::executor::CommandExecutor;
+use crate::notifications::NotificationSender;
+use crate::report::{RunContext, RunProgress, RunReport};
+use futures_util::future::join_all;
 use log::{error, info};
 use std::error::Error;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// How often senders are offered a progress update while a command runs.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
 
 pub struct App {
     config_manager: ConfigManager,
 }
 
+impl Default for App {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl App {
     pub fn new() -> Self {
         Self {
@@ -38,7 +53,7 @@ impl App {
         config_set_name: &str,
         cmd: &str,
         args: &[String],
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<RunReport, Box<dyn Error>> {
         info!("Running command with config set: {}", config_set_name);
 
         // 1. Read the config set
@@ -56,39 +71,93 @@ impl App {
         let handlers = match config_set.get_notification_handlers() {
             Ok(h) => h,
             Err(e) => {
-                return Err(Box::new(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!("Failed to get notification handlers: {}", e),
-                )))
+                return Err(Box::new(std::io::Error::other(format!(
+                    "Failed to get notification handlers: {}",
+                    e
+                ))))
             }
         };
-
-        // 3. Execute the command
-        let mut executor = CommandExecutor::new(cmd.to_string(), args.to_vec());
-        let execute_err = match executor.execute().await {
-            Ok(_) => None,
-            Err(e) => Some(e),
+        #[cfg(feature = "terminal")]
+        let handlers = if handlers.is_empty() {
+            info!("No notification channel configured, alerting on the terminal");
+            let config = crate::config::TerminalConfig::default();
+            vec![
+                Box::new(crate::notifications::terminal::TerminalNotifier::from_config(&config)?)
+                    as Box<dyn NotificationSender>,
+            ]
+        } else {
+            handlers
         };
 
-        // 4. Get the output and prepare notification message
-        let mut message = match executor.get_output() {
-            Some(output) => format!("Command output:\n{}", output),
-            None => "Command executed but produced no output".to_string(),
-        };
-
-        if let Some(e) = execute_err {
-            message.push_str(&format!("\nCommand failed with error: {}", e));
+        // 3. Execute the command, keeping senders posted on its progress
+        let mut report = RunReport::new(cmd, args.to_vec());
+        let mut executor = CommandExecutor::new(cmd.to_string(), args.to_vec());
+        let mut context = RunContext::new(config_set_name, &report);
+        context.notify_on_start = config_set.notify_on_start.unwrap_or(false);
+        for handler in &handlers {
+            if let Err(e) = handler.on_start(&context).await {
+                error!("Failed to send start notification: {}", e);
+            }
         }
 
-        // 5. Send notifications through all handlers
-        for handler in handlers {
-            if let Err(e) = handler.send(&message).await {
-                error!("Failed to send notification: {}", e);
+        let started = Instant::now();
+        let output_tail = executor.output_tail();
+        let finished = Notify::new();
+        let (execute_result, ()) = tokio::join!(
+            async {
+                let result = executor.execute().await;
+                finished.notify_one();
+                result
+            },
+            async {
+                let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+                // The first tick completes immediately
+                ticker.tick().await;
+                let mut progress = RunProgress::default();
+                loop {
+                    tokio::select! {
+                        _ = finished.notified() => break,
+                        _ = ticker.tick() => {
+                            progress.elapsed = started.elapsed();
+                            progress.output_tail = output_tail.contents();
+                            report_progress(&handlers, &context, &progress).await;
+                        }
+                    }
+                }
             }
+        );
+        let execute_err = execute_result.err();
+
+        // 4. Collect the outcome into a report
+        report.duration = started.elapsed();
+        report.exit_code = executor.get_exit_code();
+        report.output = executor.get_output().cloned();
+        // An interrupted command ends without an error from the executor, so
+        // only a clean zero exit counts as success
+        report.success = execute_err.is_none() && report.exit_code == Some(0);
+        report.error = match execute_err {
+            Some(e) => Some(e.to_string()),
+            None if !report.success => Some(match report.exit_code {
+                Some(code) => format!("Command was interrupted and exited with {}", code),
+                None => "Command was interrupted".to_string(),
+            }),
+            None => None,
+        };
+
+        // 5. Send notifications through all handlers at once, so that one
+        // waiting on a slow channel or a click holds up no other
+        let results = join_all(
+            handlers
+                .iter()
+                .map(|handler| handler.on_finish(&context, &report)),
+        )
+        .await;
+        for e in results.into_iter().filter_map(Result::err) {
+            error!("Failed to send notification: {}", e);
         }
 
         info!("Command executed and notifications sent successfully");
-        Ok(())
+        Ok(report)
     }
 
     pub fn delete_config(&self, name: &str) -> Result<(), Box<dyn Error>> {
@@ -97,6 +166,7 @@ impl App {
         Ok(())
     }
 
+    #[cfg(feature = "editor")]
     pub fn edit_config(&self, name: &str) -> Result<(), Box<dyn Error>> {
         // Read the existing config
         let config_set = self.config_manager.read_config(name)?;
@@ -111,6 +181,11 @@ impl App {
         println!("Config set '{}' updated.", name);
         Ok(())
     }
+
+    #[cfg(not(feature = "editor"))]
+    pub fn edit_config(&self, _name: &str) -> Result<(), Box<dyn Error>> {
+        Err("The interactive editor is not compiled in, rebuild with `--features editor`".into())
+    }
 }
 
 // Keep these for backward compatibility
@@ -134,6 +209,18 @@ pub async fn run_command(
     config_set_name: &str,
     cmd: &str,
     args: &[String],
-) -> Result<(), Box<dyn Error>> {
+) -> Result<RunReport, Box<dyn Error>> {
     App::new().run_command(config_set_name, cmd, args).await
 }
+
+async fn report_progress(
+    handlers: &[Box<dyn NotificationSender>],
+    context: &RunContext,
+    progress: &RunProgress,
+) {
+    for handler in handlers {
+        if let Err(e) = handler.on_progress(context, progress).await {
+            error!("Failed to send progress update: {}", e);
+        }
+    }
+}
@@ -1,22 +1,27 @@
 use crate::notifications::NotificationSender;
-use log::{error, info};
+use log::error;
 use quick_xml::de::from_str;
 use quick_xml::se::to_string;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use std::collections::HashMap;
 use std::fs;
-use std::os::unix::ffi::OsStrExt;
 use std::path::PathBuf;
-use std::{collections::HashMap, io::Error};
 
 const CONFIG_DIR: &str = ".config/notifyme/configs/";
-const DEFAULT_CONFIG_NAME: &str = "default";
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename = "config-set")]
 pub struct ConfigSet {
     #[serde(rename = "@name")]
     pub name: String,
+    /// Announce the start of every run, senders with a richer view of
+    /// the run (such as a live status message) decide on their own.
+    #[serde(
+        rename = "@notify-on-start",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub notify_on_start: Option<bool>,
     #[serde(rename = "notification-configs")]
     pub notification_configs: NotificationConfigs,
 }
@@ -41,12 +46,92 @@ pub enum NotificationConfigType {
     PhoneCall(PhoneCallConfig),
     #[serde(rename = "lark")]
     Lark(LarkConfig),
+    #[serde(rename = "dingtalk")]
+    DingTalk(DingTalkConfig),
+    #[serde(rename = "wecom")]
+    WeCom(WeComConfig),
+    Teams(TeamsConfig),
+    Slack(SlackConfig),
+    Discord(DiscordConfig),
+    Ntfy(NtfyConfig),
+    Gotify(GotifyConfig),
+    Pushover(PushoverConfig),
+    Bark(BarkConfig),
+    Desktop(DesktopConfig),
+    Terminal(TerminalConfig),
+    Matrix(MatrixConfig),
+    #[serde(rename = "pagerduty")]
+    PagerDuty(PagerDutyConfig),
+    Opsgenie(OpsgenieConfig),
+    Metrics(MetricsConfig),
+    Healthchecks(HealthchecksConfig),
+    Plugin(PluginConfig),
+}
+
+impl NotificationConfigType {
+    /// Element name of the entry in the config file, which is also the name
+    /// its provider is registered under.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            NotificationConfigType::Telegram(_) => "telegram",
+            NotificationConfigType::Email(_) => "email",
+            NotificationConfigType::Http(_) => "http",
+            NotificationConfigType::Cmd(_) => "cmd",
+            NotificationConfigType::TwilioSms(_) => "sms-twilio",
+            NotificationConfigType::PhoneCall(_) => "phone-call",
+            NotificationConfigType::Lark(_) => "lark",
+            NotificationConfigType::DingTalk(_) => "dingtalk",
+            NotificationConfigType::WeCom(_) => "wecom",
+            NotificationConfigType::Teams(_) => "teams",
+            NotificationConfigType::Slack(_) => "slack",
+            NotificationConfigType::Discord(_) => "discord",
+            NotificationConfigType::Ntfy(_) => "ntfy",
+            NotificationConfigType::Gotify(_) => "gotify",
+            NotificationConfigType::Pushover(_) => "pushover",
+            NotificationConfigType::Bark(_) => "bark",
+            NotificationConfigType::Desktop(_) => "desktop",
+            NotificationConfigType::Terminal(_) => "terminal",
+            NotificationConfigType::Matrix(_) => "matrix",
+            NotificationConfigType::PagerDuty(_) => "pagerduty",
+            NotificationConfigType::Opsgenie(_) => "opsgenie",
+            NotificationConfigType::Metrics(_) => "metrics",
+            NotificationConfigType::Healthchecks(_) => "healthchecks",
+            NotificationConfigType::Plugin(_) => "plugin",
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct TelegramConfig {
     pub token: String,
     pub chat_id: String,
+    /// `MarkdownV2` or `HTML`, plain text when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parse_mode: Option<String>,
+    /// Forum topic to post into.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_thread_id: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disable_notification: Option<bool>,
+    /// Deliver successful runs silently, failures still notify.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disable_notification_on_success: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disable_web_page_preview: Option<bool>,
+    /// Upload the output as a file once it is longer than this many
+    /// characters instead of splitting it over several messages.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document_threshold: Option<u32>,
+    /// Bot API server, `https://api.telegram.org` unless self-hosted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_url: Option<String>,
+    /// Post a status message when the command starts and keep editing it
+    /// until the command finishes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub live_updates: Option<bool>,
+    /// Seconds between edits of the live status message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub live_update_interval: Option<u32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -54,14 +139,423 @@ pub struct LarkConfig {
     pub webhook_url: String,
     pub sign_key: String,
     pub at: Option<String>,
+    /// `text` (default) or `card` for an interactive card per run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_type: Option<String>,
+    /// Target of the card's "View log" button.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_url: Option<String>,
+    #[serde(rename = "mention", default)]
+    pub mentions: Vec<LarkMentionConfig>,
+}
+
+/// `<mention type="open_id" id="ou_xxx" when="failure"/>`
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct LarkMentionConfig {
+    /// `user_id` (default), `open_id`, `email` or `all`.
+    #[serde(rename = "@type", default, skip_serializing_if = "Option::is_none")]
+    pub id_type: Option<String>,
+    /// Ignored for `all`.
+    #[serde(rename = "@id", default)]
+    pub id: String,
+    /// `always` (default), `success` or `failure`.
+    #[serde(rename = "@when", default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+}
+
+/// DingTalk custom robot.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DingTalkConfig {
+    /// `https://oapi.dingtalk.com/robot/send?access_token=...`
+    pub webhook_url: String,
+    /// `SEC...` secret of robots using the signing security setting.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    /// Phone numbers of members to mention.
+    #[serde(rename = "at_mobile", default)]
+    pub at_mobiles: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub at_all: Option<bool>,
+    /// `always` (default), `success` or `failure`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mention_when: Option<String>,
+}
+
+/// WeCom (WeChat Work) group robot.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct WeComConfig {
+    /// `https://qyapi.weixin.qq.com/cgi-bin/webhook/send?key=...`
+    pub webhook_url: String,
+    /// User ids to mention, `@all` for everyone.
+    #[serde(rename = "mentioned_user", default)]
+    pub mentioned_list: Vec<String>,
+    #[serde(rename = "mentioned_mobile", default)]
+    pub mentioned_mobile_list: Vec<String>,
+    /// `always` (default), `success` or `failure`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mention_when: Option<String>,
+    /// Upload the output as a file once it is longer than this many
+    /// characters.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_threshold: Option<u32>,
+}
+
+/// Incoming webhook or Workflows URL of a Microsoft Teams channel.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TeamsConfig {
+    pub webhook_url: String,
+    /// Target of the card's "View log" button.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_url: Option<String>,
+}
+
+/// Posts through an incoming webhook, or through `chat.postMessage` when a
+/// bot token is set.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SlackConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook_url: Option<String>,
+    /// Bot token (`xoxb-...`), required for thread replies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Channel id or name, required with a bot token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    /// Web API base URL, `https://slack.com/api` unless overridden.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_url: Option<String>,
+    #[serde(rename = "mention", default)]
+    pub mentions: Vec<SlackMentionConfig>,
+}
+
+/// `<mention type="group" id="S0123" when="failure"/>`
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SlackMentionConfig {
+    /// `user` (default), `group`, `here` or `channel`.
+    #[serde(rename = "@type", default, skip_serializing_if = "Option::is_none")]
+    pub mention_type: Option<String>,
+    /// Member or user group id, ignored for `here` and `channel`.
+    #[serde(rename = "@id", default)]
+    pub id: String,
+    /// `always` (default), `success` or `failure`.
+    #[serde(rename = "@when", default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DiscordConfig {
+    pub webhook_url: String,
+    /// Overrides the webhook's default name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar_url: Option<String>,
+    /// Attach the output as a file once it is longer than this many
+    /// characters, 3500 by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachment_threshold: Option<u32>,
+}
+
+/// Publishes to an ntfy topic.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct NtfyConfig {
+    /// `https://ntfy.sh` unless overridden.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_url: Option<String>,
+    pub topic: String,
+    /// Access token (`tk_...`) of protected topics.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// 1 (min) to 5 (urgent), 3 by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success_priority: Option<u8>,
+    /// 5 (urgent) by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_priority: Option<u8>,
+    /// Tags added to the outcome emoji, emoji short codes show as icons.
+    #[serde(rename = "tag", default)]
+    pub tags: Vec<String>,
+    /// URL opened when the notification is tapped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub click: Option<String>,
+    /// Attach the output as a file once it is longer than this many
+    /// characters, 3000 by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachment_threshold: Option<u32>,
+}
+
+/// Gotify application.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct GotifyConfig {
+    pub server_url: String,
+    /// Application token.
+    pub token: String,
+    /// 0 to 10, 4 by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success_priority: Option<u8>,
+    /// 8 by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_priority: Option<u8>,
+    /// URL opened when the notification is clicked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub click: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PushoverConfig {
+    /// `https://api.pushover.net/1` unless overridden.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_url: Option<String>,
+    /// Application API token.
+    pub token: String,
+    /// User or group key.
+    pub user: String,
+    /// Device names to notify, all of the user's devices if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    /// -2 (lowest) to 2 (emergency), 0 by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success_priority: Option<i8>,
+    /// 1 (high) by default, 2 repeats the alert until acknowledged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_priority: Option<i8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success_sound: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_sound: Option<String>,
+    /// Seconds between repeats of emergency alerts, at least 30, 60 by
+    /// default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<u32>,
+    /// Seconds emergency alerts are repeated for, at most 10800, 3600 by
+    /// default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expire: Option<u32>,
+    /// Supplementary URL shown with the message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+/// Bark iOS push server.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BarkConfig {
+    /// `https://api.day.app` unless overridden.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_url: Option<String>,
+    pub device_key: String,
+    /// `active`, `timeSensitive`, `passive` or `critical`, `active` by
+    /// default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success_level: Option<String>,
+    /// `timeSensitive` by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_level: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success_sound: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_sound: Option<String>,
+    /// Notification group in the Bark app.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// URL opened when the notification is tapped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+}
+
+/// Desktop popup through the session bus `org.freedesktop.Notifications`
+/// service.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DesktopConfig {
+    /// `notifyme` unless overridden.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_name: Option<String>,
+    /// Icon name or path, an information or error icon by outcome if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    /// Milliseconds before the popup expires, 0 never expires, the server
+    /// decides if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<i32>,
+    /// `low`, `normal` or `critical`, `normal` by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success_urgency: Option<String>,
+    /// `critical` by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_urgency: Option<String>,
+    /// Save the output to a private file (below `$XDG_RUNTIME_DIR` when set)
+    /// and offer to open it from the popup, notifyme waits for the popup to
+    /// close before exiting.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_log: Option<bool>,
+}
+
+/// Escape sequences written to the controlling terminal.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TerminalConfig {
+    /// Ring the terminal bell, on by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bell: Option<bool>,
+    /// Desktop notification sequence: `9`, `777`, `none` or `auto` (default)
+    /// to pick one from the terminal in use.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub osc: Option<String>,
+    /// Pass sequences through tmux and show the message in its status line,
+    /// on by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmux: Option<bool>,
+}
+
+/// Room messages sent through the Matrix client-server API.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct MatrixConfig {
+    /// `https://matrix.example.org`
+    pub homeserver_url: String,
+    pub access_token: String,
+    /// `!opaque:example.org`
+    pub room_id: String,
+    /// Post the result in a thread under the start message instead of
+    /// replying to it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread: Option<bool>,
+    /// Upload the full output as a file after the report.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upload_log: Option<bool>,
+}
+
+/// PagerDuty Events API v2 integration.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PagerDutyConfig {
+    /// Integration key of the service.
+    pub routing_key: String,
+    /// `critical`, `error` (default), `warning` or `info`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<String>,
+    /// Replaces the key derived from the host and the command line.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dedup_key: Option<String>,
+    /// Resolve the incident once the command succeeds, on unless disabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolve_on_success: Option<bool>,
+    /// `https://events.pagerduty.com` unless set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events_url: Option<String>,
+}
+
+/// Opsgenie alerts.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct OpsgenieConfig {
+    /// Key of an API integration.
+    pub api_key: String,
+    /// `P1` to `P5`, `P3` unless set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<String>,
+    /// Replaces the alias derived from the host and the command line.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    #[serde(rename = "tag", default)]
+    pub tags: Vec<String>,
+    /// Close the alert once the command succeeds, on unless disabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub close_on_success: Option<bool>,
+    /// `https://api.opsgenie.com`, `https://api.eu.opsgenie.com` for EU
+    /// accounts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_url: Option<String>,
+}
+
+/// Job metrics pushed to a Prometheus Pushgateway or emitted over StatsD.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct MetricsConfig {
+    /// `pushgateway` (default) or `statsd`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sink: Option<String>,
+    /// `job` label, the command name unless set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job: Option<String>,
+    /// Base URL of the Pushgateway, e.g. `http://pushgateway:9091`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pushgateway_url: Option<String>,
+    /// `host:port` of the StatsD daemon, `127.0.0.1:8125` unless set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub statsd_address: Option<String>,
+}
+
+/// Healthchecks.io style start, success and failure pings.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct HealthchecksConfig {
+    /// UUID of the check, or `<ping key>/<slug>`.
+    pub uuid: String,
+    /// `https://hc-ping.com` unless set, e.g. the `/ping` URL of a
+    /// self-hosted instance.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    /// Ping `/start` before the command runs, on unless disabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ping_start: Option<bool>,
+    /// Seconds before a ping is abandoned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u32>,
+}
+
+/// External provider executed as `notifyme-provider-<name>` (or `path`),
+/// see `notifications::plugin` for the protocol.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PluginConfig {
+    #[serde(rename = "@name")]
+    pub name: String,
+    #[serde(rename = "@path", default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(rename = "@timeout", default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u32>,
+    #[serde(rename = "param", default)]
+    pub params: Vec<PluginParam>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PluginParam {
+    #[serde(rename = "@key")]
+    pub key: String,
+    #[serde(rename = "@value")]
+    pub value: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct EmailConfig {
-    pub to: String,
+    /// One `<to>` element per recipient.
+    #[serde(default)]
+    pub to: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cc: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bcc: Vec<String>,
     pub from: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<String>,
+    /// Template with `{command}`, `{status}`, `{host}` and the other
+    /// report placeholders.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub subject: Option<String>,
+    /// Plain text template like `subject`. Unset, the message carries a
+    /// text and an HTML summary with the output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub body: Option<String>,
+    /// Attach the full output as a file, on unless disabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attach_log: Option<bool>,
+    /// Gzip the attached output once it is larger than this many bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compress_threshold: Option<u32>,
+    /// `smtp` or `sendmail`, SMTP when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transport: Option<String>,
+    /// Binary the message is piped to with `-t`, `/usr/sbin/sendmail`
+    /// unless set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sendmail_path: Option<String>,
+    /// Only needed for the SMTP transport.
+    #[serde(default)]
     pub smtp: SmtpConfig,
 }
 
@@ -71,26 +565,80 @@ pub struct SmtpConfig {
     pub port: u16,
     pub username: String,
     pub password: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub encryption: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub auth: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub debug: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub timeout: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tls_verify: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tls_ca_certs: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tls_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tls_cert: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tls_ciphers: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct HttpConfig {
     pub url: String,
+    /// `POST` when empty.
     pub method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub headers: Option<Vec<HttpHeader>>,
+    /// Template with the report placeholders, values are JSON escaped when
+    /// the `Content-Type` header names JSON. The report as JSON when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub body: Option<String>,
+    /// Seconds before a request is abandoned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub timeout: Option<u32>,
+    /// Further attempts after a connection error or a 5xx response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub retry: Option<u32>,
+    /// Seconds between attempts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub retry_delay: Option<u32>,
+    /// HMAC-SHA256 key signing `<timestamp>.<body>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signing_secret: Option<String>,
+    /// Header carrying `sha256=<hex digest>`, `X-Notifyme-Signature` unless
+    /// set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature_header: Option<String>,
+    /// Header carrying the signed Unix timestamp, `X-Notifyme-Timestamp`
+    /// unless set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp_header: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bearer_token: Option<String>,
+    /// Basic auth user, used together with `password`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// PEM client certificate for mutual TLS, its key either in the same
+    /// file or in `client_key`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_key: Option<String>,
+    /// PEM bundle trusted in addition to the built-in roots.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<String>,
+    /// `http://proxy.example.org:3128`, the `HTTPS_PROXY` family of
+    /// variables applies when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// Comma separated hosts reached without the proxy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -145,6 +693,7 @@ impl ConfigSet {
     pub fn new(name: String) -> Self {
         Self {
             name,
+            notify_on_start: None,
             notification_configs: NotificationConfigs {
                 configs: Vec::new(),
             },
@@ -163,8 +712,33 @@ impl ConfigSet {
         Ok(handlers)
     }
 
+    /// Checks every notification entry against its provider's schema.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let errors: Vec<String> = self
+            .notification_configs
+            .configs
+            .iter()
+            .flat_map(
+                |config| match crate::notifications::find_provider(config.kind()) {
+                    Some(provider) => provider.validate(config),
+                    None => Vec::new(),
+                },
+            )
+            .collect();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
     // unused temporarily, will be used for config write in the future
-    pub fn add_notification_config(&mut self, config_type: &str, params: HashMap<String, String>) {
+    pub fn add_notification_config(
+        &mut self,
+        _config_type: &str,
+        _params: HashMap<String, String>,
+    ) {
         // let value_params: HashMap<String, Value> = params
         //     .into_iter()
         //     .map(|(k, v)| (k, Value::String(v)))
@@ -288,6 +862,12 @@ pub struct ConfigManager {
     config_dir: PathBuf,
 }
 
+impl Default for ConfigManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ConfigManager {
     pub fn new() -> Self {
         Self {
@@ -295,6 +875,11 @@ impl ConfigManager {
         }
     }
 
+    /// Uses `config_dir` instead of `~/.config/notifyme/configs/`.
+    pub fn with_config_dir(config_dir: PathBuf) -> Self {
+        Self { config_dir }
+    }
+
     pub fn read_config(&self, name: &str) -> Result<ConfigSet, Box<dyn std::error::Error>> {
         let config_path = self.config_dir.join(format!("{}.xml", name));
         let content = match fs::read_to_string(&config_path) {
@@ -335,10 +920,10 @@ impl ConfigManager {
                         self.config_dir.display(),
                         e
                     );
-                    return Err(Box::new(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        format!("Failed to create config dir: {}", e),
-                    )));
+                    return Err(Box::new(std::io::Error::other(format!(
+                        "Failed to create config dir: {}",
+                        e
+                    ))));
                 }
             }
         }
@@ -351,10 +936,10 @@ impl ConfigManager {
                     config_path.display(),
                     e
                 );
-                return Err(Box::new(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!("Failed to write config : {}", e),
-                )));
+                return Err(Box::new(std::io::Error::other(format!(
+                    "Failed to write config : {}",
+                    e
+                ))));
             }
         }
         Ok(())
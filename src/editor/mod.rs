@@ -1,14 +1,16 @@
-use crate::config::{ConfigSet, LarkConfig, NotificationConfigType, TelegramConfig};
+use crate::config::{ConfigSet, NotificationConfigType};
+use crate::notifications::registry::{FieldSpec, ProviderDescriptor};
+use crate::notifications::{find_provider, PROVIDERS};
 use crossterm::{
     event::{self, Event, KeyCode},
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
 use ratatui::{
-    backend::{Backend, CrosstermBackend},
+    backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    text::{Line, Span, Text},
+    text::{Line, Span},
     widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
     Frame, Terminal,
 };
@@ -32,6 +34,7 @@ pub struct Editor {
     mode: EditorMode,
     notification_index: Option<usize>,
     notification_field_index: Option<usize>,
+    status: Option<String>,
 }
 
 impl Editor {
@@ -43,6 +46,7 @@ impl Editor {
             mode: EditorMode::Normal,
             notification_index: None,
             notification_field_index: None,
+            status: None,
         }
     }
 
@@ -66,7 +70,7 @@ impl Editor {
         terminal: &mut Terminal<CrosstermBackend<Stdout>>,
     ) -> Result<ConfigSet, Box<dyn Error>> {
         loop {
-            terminal.draw(|f| self.ui::<CrosstermBackend<Stdout>>(f))?;
+            terminal.draw(|f| self.ui(f))?;
 
             if let Event::Key(key) = event::read()? {
                 if let Ok(true) = self.handle_input(key.code) {
@@ -76,10 +80,7 @@ impl Editor {
         }
     }
 
-    fn ui<B>(&self, f: &mut Frame)
-    where
-        B: Backend,
-    {
+    fn ui(&self, f: &mut Frame) {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints(
@@ -92,19 +93,16 @@ impl Editor {
             .split(f.size());
 
         match self.mode {
-            EditorMode::Normal => self.render_main_view::<B>(f, chunks[0]),
+            EditorMode::Normal => self.render_main_view(f, chunks[0]),
             EditorMode::AddingConfig => self.render_add_config_view(f, chunks[0]),
-            EditorMode::Editing => self.render_editing_view::<B>(f, chunks[0]),
-            EditorMode::Notification => self.render_notification_view::<B>(f, chunks[0]),
+            EditorMode::Editing => self.render_editing_view(f, chunks[0]),
+            EditorMode::Notification => self.render_notification_view(f, chunks[0]),
         }
 
-        self.render_hints::<B>(f, chunks[1]);
+        self.render_hints(f, chunks[1]);
     }
 
-    fn render_main_view<B>(&self, f: &mut Frame, area: Rect)
-    where
-        B: Backend,
-    {
+    fn render_main_view(&self, f: &mut Frame, area: Rect) {
         let items: Vec<ListItem> = self
             .get_main_items()
             .iter()
@@ -140,7 +138,7 @@ impl Editor {
         f.render_widget(list, area);
     }
 
-    fn render_notification_view<B: Backend>(&self, f: &mut Frame, area: Rect) {
+    fn render_notification_view(&self, f: &mut Frame, area: Rect) {
         if let Some(notif_idx) = self.notification_index {
             // Render notification details here
             // This would show all fields of the selected notification
@@ -171,20 +169,24 @@ impl Editor {
         }
     }
 
-    fn render_editing_view<B: Backend>(&self, f: &mut Frame, area: Rect) {
+    fn render_editing_view(&self, f: &mut Frame, area: Rect) {
         let input = Paragraph::new(self.editing_value.as_str())
             .block(Block::default().title("Editing").borders(Borders::ALL))
             .wrap(Wrap { trim: true });
         f.render_widget(input, area);
     }
 
-    fn render_hints<B: Backend>(&self, f: &mut Frame, area: Rect) {
+    fn render_hints(&self, f: &mut Frame, area: Rect) {
         let hints = match self.mode {
             EditorMode::Normal => "↑↓: Navigate | Enter: Edit | q: Quit",
             EditorMode::AddingConfig => "↑↓: Navigate | Enter: Add | Esc: Cancel",
             EditorMode::Editing => "Enter: Save | Esc: Cancel",
             EditorMode::Notification => "↑↓: Navigate | Enter: Edit Field | Esc: Back | q: Quit",
         };
+        let hints = match &self.status {
+            Some(status) => format!("{} | {}", status, hints),
+            None => hints.to_string(),
+        };
 
         let hints = Paragraph::new(hints).block(Block::default().borders(Borders::ALL));
         f.render_widget(hints, area);
@@ -197,11 +199,9 @@ impl Editor {
 
         // Add notifications with indent
         for notification in self.config.notification_configs.configs.iter() {
-            let type_str = match notification {
-                NotificationConfigType::Telegram(_) => "Telegram",
-                NotificationConfigType::Lark(_) => "Lark",
-                // Add other types here
-                _ => "Unknown",
+            let type_str = match find_provider(notification.kind()) {
+                Some(provider) => provider.display_name,
+                None => notification.kind(),
             };
             items.push(format!("  Notification: {}", type_str));
         }
@@ -210,88 +210,60 @@ impl Editor {
     }
 
     fn get_notification_items(&self, notification: &NotificationConfigType) -> Vec<String> {
-        // Convert notification fields to displayable items
-        // This will depend on your NotificationConfigs structure
-        match notification {
-            // NotificationConfigType::Email(config) => {
-            //     vec![
-            //         format!("Type: Email"),
-            //         format!("SMTP Server: {}", config.smtp.server),
-            //         format!("From: {}", config.from),
-            //         format!("To: {}", config.to),
-            //         // ... other email fields
-            //     ]
-            // }
-            NotificationConfigType::Telegram(config) => {
-                vec![
-                    format!("Type: Telegram"),
-                    format!("Token: {}", config.token),
-                    format!("Chat ID: {}", config.chat_id),
-                ]
-            }
-            NotificationConfigType::Lark(config) => {
-                vec![
-                    format!("Type: Lark"),
-                    format!("Webhook URL: {}", config.webhook_url),
-                    format!("Sign Key: {}", config.sign_key),
-                    format!("At: {}", config.at.clone().unwrap_or_default()),
-                ]
-            }
-            // Add other notification types
-            _ => vec![format!("Unsupported notification type")],
+        let Some(provider) = find_provider(notification.kind()) else {
+            return vec![format!(
+                "Unsupported notification type: {}",
+                notification.kind()
+            )];
+        };
+
+        let mut items = vec![format!("Type: {}", provider.display_name)];
+        for field in provider.fields {
+            let marker = if field.required { " *" } else { "" };
+            items.push(format!(
+                "{}{}: {}",
+                field.label,
+                marker,
+                provider.display_field(notification, field)
+            ));
         }
+        items
+    }
+
+    /// Provider and field under the cursor, item 0 being the type header.
+    fn selected_field(&self) -> Option<(&'static ProviderDescriptor, &'static FieldSpec)> {
+        let notification = &self.config.notification_configs.configs[self.notification_index?];
+        let provider = find_provider(notification.kind())?;
+        let field = provider
+            .fields
+            .get(self.notification_field_index?.checked_sub(1)?)?;
+        Some((provider, field))
     }
 
     fn start_editing(&mut self) {
         self.mode = EditorMode::Editing;
+        self.status = None;
         self.editing_value = self.get_current_value();
     }
 
     fn get_current_value(&self) -> String {
-        if let Some(notif_idx) = self.notification_index {
-            let notification = &self.config.notification_configs.configs[notif_idx];
-            if let Some(field_idx) = self.notification_field_index {
-                match notification {
-                    NotificationConfigType::Telegram(config) => match field_idx {
-                        1 => config.token.clone(),
-                        2 => config.chat_id.clone(),
-                        _ => String::new(),
-                    },
-                    NotificationConfigType::Lark(config) => match field_idx {
-                        1 => config.webhook_url.clone(),
-                        2 => config.sign_key.clone(),
-                        3 => config.at.clone().unwrap_or_default(),
-                        _ => String::new(),
-                    },
-                    _ => String::new(),
-                }
-            } else {
-                String::new()
+        match (self.selected_field(), self.notification_index) {
+            (Some((provider, field)), Some(notif_idx)) => {
+                provider.get_field(&self.config.notification_configs.configs[notif_idx], field)
             }
-        } else {
-            String::new()
+            _ => String::new(),
         }
     }
 
     fn apply_edit(&mut self) {
-        if let Some(notif_idx) = self.notification_index {
-            if let Some(field_idx) = self.notification_field_index {
-                let notification = &mut self.config.notification_configs.configs[notif_idx];
-                match notification {
-                    NotificationConfigType::Telegram(config) => match field_idx {
-                        1 => config.token = self.editing_value.clone(),
-                        2 => config.chat_id = self.editing_value.clone(),
-                        _ => {}
-                    },
-                    NotificationConfigType::Lark(config) => match field_idx {
-                        1 => config.webhook_url = self.editing_value.clone(),
-                        2 => config.sign_key = self.editing_value.clone(),
-                        3 => config.at = Some(self.editing_value.clone()),
-                        _ => {}
-                    },
-                    _ => {}
-                }
-            }
+        if let (Some((provider, field)), Some(notif_idx)) =
+            (self.selected_field(), self.notification_index)
+        {
+            let notification = &mut self.config.notification_configs.configs[notif_idx];
+            self.status = provider
+                .set_field(notification, field, &self.editing_value)
+                .err()
+                .map(|e| e.to_string());
         }
         self.editing_value.clear();
     }
@@ -332,13 +304,19 @@ impl Editor {
                     self.selected_index = self.selected_index.saturating_sub(1);
                 }
                 KeyCode::Down => {
-                    self.selected_index = (self.selected_index + 1)
-                        .min(self.get_available_notification_types().len() - 1);
+                    self.selected_index = (self.selected_index + 1).min(
+                        self.get_available_notification_types()
+                            .len()
+                            .saturating_sub(1),
+                    );
                 }
                 KeyCode::Enter => {
-                    let notification_type =
-                        &self.get_available_notification_types()[self.selected_index];
-                    self.create_new_notification(notification_type);
+                    if let Some(notification_type) = self
+                        .get_available_notification_types()
+                        .get(self.selected_index)
+                    {
+                        self.create_new_notification(notification_type);
+                    }
                     self.mode = EditorMode::Normal;
                     self.selected_index = self.get_main_items().len() - 1; // Select the newly added notification
                 }
@@ -388,22 +366,22 @@ impl Editor {
     }
 
     fn get_available_notification_types(&self) -> Vec<String> {
-        vec![
-            "Telegram".to_string(),
-            "Lark".to_string(),
-            "Email".to_string(),
-            // Add other notification types here
-        ]
+        PROVIDERS
+            .iter()
+            .map(|provider| provider.display_name.to_string())
+            .collect()
     }
 
     fn create_new_notification(&mut self, notification_type: &str) {
-        let new_config = match notification_type {
-            "Telegram" => NotificationConfigType::Telegram(TelegramConfig::default()),
-            "Lark" => NotificationConfigType::Lark(LarkConfig::default()),
-            // Add other types here
-            _ => return,
-        };
-        self.config.notification_configs.configs.push(new_config);
+        if let Some(provider) = PROVIDERS
+            .iter()
+            .find(|provider| provider.display_name == notification_type)
+        {
+            self.config
+                .notification_configs
+                .configs
+                .push(provider.new_config());
+        }
     }
 
     fn render_add_config_view(&self, f: &mut Frame, area: Rect) {
@@ -432,18 +410,6 @@ impl Editor {
         f.render_widget(list, area);
     }
 
-    fn render_add_config_hints(&self, f: &mut Frame, area: Rect) {
-        let hints = match self.mode {
-            EditorMode::Normal => "↑↓: Navigate | Enter: Select | q: Quit",
-            EditorMode::AddingConfig => "↑↓: Navigate | Enter: Add | Esc: Cancel",
-            EditorMode::Editing => "Enter: Save | Esc: Cancel",
-            EditorMode::Notification => "↑↓: Navigate | Enter: Edit Field | Esc: Back | q: Quit",
-        };
-
-        let hints = Paragraph::new(hints).block(Block::default().borders(Borders::ALL));
-        f.render_widget(hints, area);
-    }
-
     fn get_notification_field_count(&self) -> usize {
         if let Some(notif_idx) = self.notification_index {
             let notification = &self.config.notification_configs.configs[notif_idx];
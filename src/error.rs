@@ -8,6 +8,8 @@ pub enum NotificationError {
     ExecutionError(String),
     #[error("Notification error: {0}")]
     NotificationError(String),
+    #[error("Notification provider '{0}' is not compiled in, rebuild with `--features {0}`")]
+    ProviderNotCompiledIn(String),
     #[error("Unknown error: {0}")]
     Unknown(String),
 }
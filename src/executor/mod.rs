@@ -1,15 +1,43 @@
-use libc;
 use log::{error, info};
 use std::error::Error;
 use std::process::Stdio;
+use std::sync::{Arc, Mutex};
 use tokio::io::AsyncReadExt;
 use tokio::process::Command as TokioCommand;
 use tokio::signal::unix::{signal, SignalKind};
 
+/// Bytes of recent output kept for progress reports.
+const OUTPUT_TAIL_BYTES: usize = 8192;
+
+/// The most recent output of a running command, readable while
+/// `CommandExecutor::execute` is still in progress.
+#[derive(Debug, Clone, Default)]
+pub struct OutputTail(Arc<Mutex<String>>);
+
+impl OutputTail {
+    fn push(&self, chunk: &str) {
+        let mut tail = self.0.lock().unwrap();
+        tail.push_str(chunk);
+        if tail.len() > OUTPUT_TAIL_BYTES {
+            let mut cut = tail.len() - OUTPUT_TAIL_BYTES;
+            while !tail.is_char_boundary(cut) {
+                cut += 1;
+            }
+            tail.drain(..cut);
+        }
+    }
+
+    pub fn contents(&self) -> String {
+        self.0.lock().unwrap().clone()
+    }
+}
+
 pub struct CommandExecutor {
     cmd: String,
     args: Vec<String>,
     output: Option<String>,
+    output_tail: OutputTail,
+    exit_code: Option<i32>,
 }
 
 impl CommandExecutor {
@@ -18,6 +46,8 @@ impl CommandExecutor {
             cmd,
             args,
             output: None,
+            output_tail: OutputTail::default(),
+            exit_code: None,
         }
     }
 
@@ -47,12 +77,14 @@ impl CommandExecutor {
                     let chunk = String::from_utf8_lossy(&buffer[..n]);
                     print!("{}", chunk);
                     self.output.as_mut().unwrap().push_str(&chunk);
+                    self.output_tail.push(&chunk);
                 }
 
                 let status = child.wait().await.map_err(|e| {
                     error!("Failed to wait for child process: {}", e);
                     e
                 })?;
+                self.exit_code = status.code();
 
                 if status.success() {
                     info!("Command executed successfully");
@@ -73,7 +105,8 @@ impl CommandExecutor {
                         libc::kill(pid as i32, libc::SIGINT);
                     }
                 }
-                child.wait().await?;
+                let status = child.wait().await?;
+                self.exit_code = status.code();
                 Ok(())
             }
         }
@@ -82,4 +115,16 @@ impl CommandExecutor {
     pub fn get_output(&self) -> Option<&String> {
         self.output.as_ref()
     }
+
+    /// Handle on the output seen so far, for reporting progress while the
+    /// command runs.
+    pub fn output_tail(&self) -> OutputTail {
+        self.output_tail.clone()
+    }
+
+    /// Exit code of the finished process, `None` if it never ran or was
+    /// killed by a signal.
+    pub fn get_exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
 }
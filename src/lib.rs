@@ -0,0 +1,97 @@
+//! NotifyMe monitors commands and sends notifications through the channels
+//! configured in a config set (`~/.config/notifyme/configs/<name>.xml`).
+//!
+//! The `notifyme` binary is a thin wrapper around this crate, so the same
+//! config sets and providers can be reused from other Rust programs:
+//!
+//! ```no_run
+//! use notifyme::RunReport;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut report = RunReport::new("backup.sh", vec!["--full".to_string()]);
+//! report.output = Some("42 files archived".to_string());
+//! notifyme::notify("default", report).await?;
+//!
+//! // Or let notifyme run and monitor the command itself
+//! let report = notifyme::run("default", "make", &["release".to_string()]).await?;
+//! println!("exit code: {:?}", report.exit_code);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Every notification provider and the interactive editor sit behind a cargo
+//! feature of the same name (`telegram`, `lark`, `dingtalk`, `wecom`, `teams`,
+//! `slack`, `discord`, `matrix`, `ntfy`, `gotify`, `pushover`, `bark`,
+//! `desktop`, `terminal`, `email`, `http`, `pagerduty`, `opsgenie`, `metrics`,
+//! `healthchecks`, `plugin`, `editor`), all enabled by default. Config entries for a provider that is
+//! not compiled in fail with [`error::NotificationError::ProviderNotCompiledIn`].
+
+pub mod app;
+pub mod config;
+#[cfg(feature = "editor")]
+pub mod editor;
+pub mod error;
+pub mod executor;
+pub mod notifications;
+pub mod report;
+
+pub use config::{ConfigManager, ConfigSet, NotificationConfigType};
+pub use notifications::{create_notification_sender, NotificationSender};
+pub use report::RunReport;
+
+use futures_util::future::join_all;
+use log::error;
+use report::RunContext;
+use std::error::Error;
+
+/// Reads the config set `name` from the notifyme config directory.
+pub fn load_config(name: &str) -> Result<ConfigSet, Box<dyn Error>> {
+    ConfigManager::new().read_config(name)
+}
+
+/// Builds one sender for every notification entry of `config_set`.
+pub fn build_senders(
+    config_set: &ConfigSet,
+) -> Result<Vec<Box<dyn NotificationSender>>, Box<dyn Error>> {
+    config_set.get_notification_handlers()
+}
+
+/// Sends `report` through every channel of the config set `config_set_name`.
+///
+/// All channels are tried at once and even if some of them fail; the
+/// returned error lists every failure.
+pub async fn notify(config_set_name: &str, report: RunReport) -> Result<(), Box<dyn Error>> {
+    let config_set = load_config(config_set_name)?;
+    let senders = build_senders(&config_set)?;
+
+    // Through the finish hook like `run`, so senders that label a run
+    // with its config set do so here too
+    let context = RunContext::new(config_set_name, &report);
+    let results = join_all(
+        senders
+            .iter()
+            .map(|sender| sender.on_finish(&context, &report)),
+    )
+    .await;
+    let mut failures = Vec::new();
+    for e in results.into_iter().filter_map(Result::err) {
+        error!("Failed to send notification: {}", e);
+        failures.push(e.to_string());
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Failed to send notifications: {}", failures.join("; ")).into())
+    }
+}
+
+/// Runs `cmd` with `args`, monitors it and notifies through the config set
+/// `config_set_name` once it finishes.
+pub async fn run(
+    config_set_name: &str,
+    cmd: &str,
+    args: &[String],
+) -> Result<RunReport, Box<dyn Error>> {
+    app::run_command(config_set_name, cmd, args).await
+}
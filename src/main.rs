@@ -1,10 +1,4 @@
-mod app;
 mod cli;
-mod config;
-mod editor;
-mod error;
-mod executor;
-mod notifications;
 
 use chrono::Local;
 use clap::Parser;
@@ -12,6 +6,7 @@ use cli::{Cli, Commands};
 use env_logger::Builder;
 use log::LevelFilter;
 use log::{error, info};
+use notifyme::app;
 use std::io::Write;
 
 fn main() {
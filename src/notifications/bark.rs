@@ -0,0 +1,303 @@
+use crate::config::{BarkConfig, NotificationConfigType};
+use crate::notifications::registry::{FieldSpec, FieldType, ProviderDescriptor};
+use crate::notifications::{
+    config_mismatch, head_within_bytes, tail_within_bytes, NotificationSender,
+};
+use crate::report::{format_duration, RunReport};
+use log::{error, info};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::error::Error;
+
+pub const DEFAULT_SERVER_URL: &str = "https://api.day.app";
+
+pub const PROVIDER: ProviderDescriptor = ProviderDescriptor {
+    name: "bark",
+    display_name: "Bark",
+    fields: &[
+        FieldSpec::new("server_url", "Server URL", FieldType::String)
+            .default_value(DEFAULT_SERVER_URL),
+        FieldSpec::new("device_key", "Device Key", FieldType::String)
+            .required()
+            .secret(),
+        FieldSpec::new("success_level", "Success Level", FieldType::String),
+        FieldSpec::new("failure_level", "Failure Level", FieldType::String),
+        FieldSpec::new("success_sound", "Success Sound", FieldType::String),
+        FieldSpec::new("failure_sound", "Failure Sound", FieldType::String),
+        FieldSpec::new("group", "Group", FieldType::String),
+        FieldSpec::new("url", "URL", FieldType::String),
+        FieldSpec::new("icon", "Icon URL", FieldType::String),
+    ],
+    default_config: || NotificationConfigType::Bark(BarkConfig::default()),
+    build: |config| match config {
+        NotificationConfigType::Bark(config) => Ok(Box::new(BarkNotifier::from_config(config)?)),
+        other => Err(config_mismatch(&PROVIDER, other)),
+    },
+};
+
+/// Interruption levels understood by iOS.
+const LEVELS: &[&str] = &["active", "timeSensitive", "passive", "critical"];
+/// Bytes of the notification body, APNs rejects payloads over 4 KB and
+/// the title and the other fields need the rest.
+const BODY_LIMIT: usize = 3000;
+/// Characters of the error shown in a notification.
+const ERROR_LIMIT: usize = 500;
+
+/// Level and sound of the notification for one outcome.
+struct Alert {
+    level: String,
+    sound: Option<String>,
+}
+
+pub struct BarkNotifier {
+    server_url: String,
+    device_key: String,
+    success: Alert,
+    failure: Alert,
+    group: Option<String>,
+    url: Option<String>,
+    icon: Option<String>,
+    client: Client,
+}
+
+impl BarkNotifier {
+    pub fn from_config(config: &BarkConfig) -> Result<Self, Box<dyn Error>> {
+        let non_empty = |value: &Option<String>| value.clone().filter(|v| !v.is_empty());
+        let alert = |level: &Option<String>, default: &str, sound: &Option<String>| {
+            let level = non_empty(level).unwrap_or_else(|| default.to_string());
+            if !LEVELS.contains(&level.as_str()) {
+                return Err(format!(
+                    "Unknown Bark level '{}', expected one of {}",
+                    level,
+                    LEVELS.join(", ")
+                ));
+            }
+            Ok(Alert {
+                level,
+                sound: non_empty(sound),
+            })
+        };
+
+        Ok(Self {
+            server_url: non_empty(&config.server_url)
+                .unwrap_or_else(|| DEFAULT_SERVER_URL.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            device_key: config.device_key.clone(),
+            success: alert(&config.success_level, "active", &config.success_sound)?,
+            failure: alert(
+                &config.failure_level,
+                "timeSensitive",
+                &config.failure_sound,
+            )?,
+            group: non_empty(&config.group),
+            url: non_empty(&config.url),
+            icon: non_empty(&config.icon),
+            client: Client::new(),
+        })
+    }
+
+    /// Push request with the fields shared by every message.
+    fn payload(&self, title: Option<String>, body: String) -> Value {
+        let mut payload = json!({"device_key": self.device_key, "body": body});
+        if let Some(title) = title {
+            payload["title"] = json!(title);
+        }
+        for (key, value) in [
+            ("group", &self.group),
+            ("url", &self.url),
+            ("icon", &self.icon),
+        ] {
+            if let Some(value) = value {
+                payload[key] = json!(value);
+            }
+        }
+        payload
+    }
+
+    fn report_payload(&self, report: &RunReport) -> Value {
+        let (alert, icon) = if report.success {
+            (&self.success, "✅")
+        } else {
+            (&self.failure, "❌")
+        };
+        let exit_code = report
+            .exit_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let mut body = format!(
+            "{} on {}\nDuration: {}, exit code {}",
+            report.command_line(),
+            report.host,
+            format_duration(report.duration),
+            exit_code
+        );
+        if report.error.is_some() {
+            body.push_str(&format!("\nError: {}", report.error_head(ERROR_LIMIT)));
+        }
+        // Only a very long command line gets here, keep the status line
+        body = head_within_bytes(&body, BODY_LIMIT).to_string();
+        let budget = BODY_LIMIT.saturating_sub(body.len() + 2);
+        let output = tail_within_bytes(
+            report.output.as_deref().unwrap_or_default().trim_end(),
+            budget,
+        );
+        if !output.is_empty() {
+            body.push_str("\n\n");
+            body.push_str(output);
+        }
+
+        let title = format!("{} {} {}", icon, report.command, report.status_text());
+        let mut payload = self.payload(Some(title), body);
+        payload["level"] = json!(alert.level);
+        if let Some(sound) = &alert.sound {
+            payload["sound"] = json!(sound);
+        }
+        payload
+    }
+
+    async fn post(&self, payload: Value) -> Result<(), Box<dyn Error>> {
+        let response = self
+            .client
+            .post(format!("{}/push", self.server_url))
+            .json(&payload)
+            .send()
+            .await?;
+
+        let status = response.status();
+        let text = response.text().await?;
+        let body: BarkResponse = serde_json::from_str(&text).map_err(|e| {
+            error!("Failed to parse Bark response: {}, body: {}", e, text);
+            format!("Invalid Bark response: {}, body: {}", e, text)
+        })?;
+
+        if status.is_success() && body.code == 200 {
+            info!("Bark notification sent successfully");
+            Ok(())
+        } else {
+            error!(
+                "Failed to send Bark notification: code={}, message={}",
+                body.code, body.message
+            );
+            Err(format!(
+                "Failed to send Bark notification: code={}, message={}",
+                body.code, body.message
+            )
+            .into())
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct BarkResponse {
+    code: i64,
+    #[serde(default)]
+    message: String,
+}
+
+#[async_trait::async_trait]
+impl NotificationSender for BarkNotifier {
+    async fn send(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.post(self.payload(None, message.to_string())).await
+    }
+
+    async fn send_report(&self, report: &RunReport) -> Result<(), Box<dyn Error>> {
+        self.post(self.report_payload(report)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::test_server::{MockResponse, MockServer};
+
+    fn config(server_url: &str) -> BarkConfig {
+        BarkConfig {
+            server_url: Some(server_url.to_string()),
+            device_key: "key123".to_string(),
+            success_level: Some("passive".to_string()),
+            failure_level: None,
+            success_sound: None,
+            failure_sound: Some("alarm".to_string()),
+            group: Some("builds".to_string()),
+            url: None,
+            icon: None,
+        }
+    }
+
+    fn report(success: bool) -> RunReport {
+        let mut report = RunReport::new("make", vec!["test".to_string()]);
+        report.host = "build-1".to_string();
+        report.duration = std::time::Duration::from_secs(3);
+        report.exit_code = Some(if success { 0 } else { 2 });
+        report.success = success;
+        report.output = Some("one\ntwo\n".to_string());
+        report
+    }
+
+    #[test]
+    fn test_report_payload() {
+        let notifier = BarkNotifier::from_config(&config("https://bark.example.com/")).unwrap();
+
+        assert_eq!(
+            notifier.report_payload(&report(true)),
+            json!({
+                "device_key": "key123",
+                "title": "✅ make succeeded",
+                "body": "make test on build-1\nDuration: 3s, exit code 0\n\none\ntwo",
+                "group": "builds",
+                "level": "passive"
+            })
+        );
+
+        let failure = notifier.report_payload(&report(false));
+        assert_eq!(failure["level"], "timeSensitive");
+        assert_eq!(failure["sound"], "alarm");
+
+        let mut long = report(false);
+        long.output = Some("输出\n".repeat(2000));
+        long.error = Some("错误\n".repeat(5000));
+        let body = notifier.report_payload(&long)["body"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        assert!(body.len() <= BODY_LIMIT);
+        assert!(body.starts_with("make test on build-1\n"));
+        assert!(body.contains("\nError: 错误\n"));
+        assert!(body.ends_with("\n输出"));
+
+        let mut invalid = config("https://bark.example.com");
+        invalid.failure_level = Some("loud".to_string());
+        assert!(BarkNotifier::from_config(&invalid).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_push() {
+        let server = MockServer::start(vec![MockResponse::json(
+            json!({"code": 200, "message": "success", "timestamp": 1700000000}),
+        )])
+        .await;
+        let notifier = BarkNotifier::from_config(&config(&server.url)).unwrap();
+        notifier.send("hello").await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/push");
+        assert_eq!(
+            requests[0].json(),
+            json!({"device_key": "key123", "body": "hello", "group": "builds"})
+        );
+
+        let server = MockServer::start(vec![MockResponse::new(
+            400,
+            r#"{"code":400,"message":"failed to get device token: device key not found"}"#,
+        )])
+        .await;
+        let err = BarkNotifier::from_config(&config(&server.url))
+            .unwrap()
+            .send("hello")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("device key not found"));
+    }
+}
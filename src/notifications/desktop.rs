@@ -0,0 +1,574 @@
+use crate::config::{DesktopConfig, NotificationConfigType};
+use crate::notifications::registry::{FieldSpec, FieldType, ProviderDescriptor};
+use crate::notifications::{config_mismatch, NotificationSender};
+use crate::report::{format_duration, RunReport};
+use futures_util::StreamExt;
+use log::{info, warn};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{DirBuilder, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use zbus::message::Type as MessageType;
+use zbus::zvariant::Value;
+use zbus::{Connection, MatchRule, MessageStream};
+
+pub const PROVIDER: ProviderDescriptor = ProviderDescriptor {
+    name: "desktop",
+    display_name: "Desktop",
+    fields: &[
+        FieldSpec::new("app_name", "App Name", FieldType::String).default_value(DEFAULT_APP_NAME),
+        FieldSpec::new("icon", "Icon", FieldType::String),
+        FieldSpec::new("timeout", "Timeout (ms)", FieldType::Integer),
+        FieldSpec::new("success_urgency", "Success Urgency", FieldType::String),
+        FieldSpec::new("failure_urgency", "Failure Urgency", FieldType::String),
+        FieldSpec::new("open_log", "Open Log Action", FieldType::Bool),
+    ],
+    default_config: || NotificationConfigType::Desktop(DesktopConfig::default()),
+    build: |config| match config {
+        NotificationConfigType::Desktop(config) => {
+            Ok(Box::new(DesktopNotifier::from_config(config)?))
+        }
+        other => Err(config_mismatch(&PROVIDER, other)),
+    },
+};
+
+pub const DEFAULT_APP_NAME: &str = "notifyme";
+
+const SERVICE: &str = "org.freedesktop.Notifications";
+const OBJECT_PATH: &str = "/org/freedesktop/Notifications";
+/// Lines of command output shown in the popup.
+const OUTPUT_LINES: usize = 5;
+/// Characters of the error shown in the popup.
+const ERROR_LIMIT: usize = 300;
+/// Longest wait for the "Open log" action of a popup that does not expire.
+const MAX_ACTION_WAIT: Duration = Duration::from_secs(120);
+const OPEN_LOG_ACTION: &str = "default";
+
+fn parse_urgency(value: Option<&str>, default: u8) -> Result<u8, String> {
+    match value.filter(|v| !v.is_empty()) {
+        None => Ok(default),
+        Some("low") => Ok(0),
+        Some("normal") => Ok(1),
+        Some("critical") => Ok(2),
+        Some(other) => Err(format!(
+            "Unknown desktop urgency '{}', expected low, normal or critical",
+            other
+        )),
+    }
+}
+
+/// Arguments of an `org.freedesktop.Notifications.Notify` call.
+#[derive(Debug, PartialEq)]
+struct Popup {
+    icon: String,
+    summary: String,
+    body: String,
+    urgency: u8,
+    actions: Vec<String>,
+}
+
+pub struct DesktopNotifier {
+    app_name: String,
+    icon: Option<String>,
+    timeout: i32,
+    success_urgency: u8,
+    failure_urgency: u8,
+    open_log: bool,
+    /// Bus to connect to instead of the session bus.
+    address: Option<String>,
+    /// Program opening the saved log.
+    opener: String,
+    /// Connection reused across the start and finish notifications.
+    connection: Mutex<Option<Connection>>,
+}
+
+impl DesktopNotifier {
+    pub fn from_config(config: &DesktopConfig) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            app_name: config
+                .app_name
+                .clone()
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| DEFAULT_APP_NAME.to_string()),
+            icon: config.icon.clone().filter(|icon| !icon.is_empty()),
+            timeout: config.timeout.unwrap_or(-1),
+            success_urgency: parse_urgency(config.success_urgency.as_deref(), 1)?,
+            failure_urgency: parse_urgency(config.failure_urgency.as_deref(), 2)?,
+            open_log: config.open_log.unwrap_or(false),
+            address: None,
+            opener: "xdg-open".to_string(),
+            connection: Mutex::new(None),
+        })
+    }
+
+    fn report_popup(&self, report: &RunReport) -> Popup {
+        let (urgency, icon, mark) = if report.success {
+            (self.success_urgency, "dialog-information", "✅")
+        } else {
+            (self.failure_urgency, "dialog-error", "❌")
+        };
+        let exit_code = report
+            .exit_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "-".to_string());
+
+        let mut body = format!(
+            "{} on {}\nDuration: {}, exit code {}",
+            report.command_line(),
+            report.host,
+            format_duration(report.duration),
+            exit_code
+        );
+        if report.error.is_some() {
+            body.push_str(&format!("\n{}", report.error_head(ERROR_LIMIT)));
+        }
+        let output = report.output.as_deref().unwrap_or_default().trim_end();
+        let lines: Vec<&str> = output.lines().collect();
+        let tail = lines[lines.len().saturating_sub(OUTPUT_LINES)..].join("\n");
+        if !tail.is_empty() {
+            body.push_str(&format!("\n\n{}", tail));
+        }
+
+        Popup {
+            icon: self.icon.clone().unwrap_or_else(|| icon.to_string()),
+            summary: format!("{} {} {}", mark, report.command, report.status_text()),
+            // Servers may interpret a subset of HTML in the body
+            body: escape_markup(&body),
+            urgency,
+            actions: Vec::new(),
+        }
+    }
+
+    /// The cached connection, connecting on first use.
+    async fn connection(&self) -> zbus::Result<Connection> {
+        if let Some(connection) = self.connection.lock().unwrap().clone() {
+            return Ok(connection);
+        }
+        let connection = match &self.address {
+            Some(address) => {
+                zbus::ConnectionBuilder::address(address.as_str())?
+                    .build()
+                    .await?
+            }
+            None => Connection::session().await?,
+        };
+        *self.connection.lock().unwrap() = Some(connection.clone());
+        Ok(connection)
+    }
+
+    async fn notify(&self, connection: &Connection, popup: &Popup) -> zbus::Result<u32> {
+        let hints = HashMap::from([("urgency", Value::U8(popup.urgency))]);
+        let reply = connection
+            .call_method(
+                Some(SERVICE),
+                OBJECT_PATH,
+                Some(SERVICE),
+                "Notify",
+                &(
+                    self.app_name.as_str(),
+                    0u32,
+                    popup.icon.as_str(),
+                    popup.summary.as_str(),
+                    popup.body.as_str(),
+                    &popup.actions,
+                    hints,
+                    self.timeout,
+                ),
+            )
+            .await?;
+        reply.body().deserialize()
+    }
+
+    /// Shows `popup`, returning whether the user picked its action.
+    async fn show(&self, popup: &Popup) -> zbus::Result<bool> {
+        let connection = self.connection().await?;
+        if popup.actions.is_empty() {
+            let id = self.notify(&connection, popup).await?;
+            info!("Desktop notification {} shown", id);
+            return Ok(false);
+        }
+
+        // Subscribe first so a click right after the popup shows is not missed
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .interface(SERVICE)?
+            .path(OBJECT_PATH)?
+            .build();
+        let mut signals = MessageStream::for_match_rule(rule, &connection, None).await?;
+        let id = self.notify(&connection, popup).await?;
+        info!("Desktop notification {} shown, waiting for its action", id);
+
+        let wait = match self.timeout {
+            timeout if timeout > 0 => Duration::from_millis(timeout as u64).min(MAX_ACTION_WAIT),
+            _ => MAX_ACTION_WAIT,
+        };
+        let clicked = tokio::time::timeout(wait, async {
+            while let Some(message) = signals.next().await {
+                let message = message?;
+                let member = message.header().member().map(|m| m.to_string());
+                match member.as_deref() {
+                    Some("ActionInvoked") => {
+                        let (signal_id, action): (u32, String) = message.body().deserialize()?;
+                        if signal_id == id && action == OPEN_LOG_ACTION {
+                            return Ok(true);
+                        }
+                    }
+                    Some("NotificationClosed") => {
+                        let (signal_id, _reason): (u32, u32) = message.body().deserialize()?;
+                        if signal_id == id {
+                            return Ok(false);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Ok::<bool, zbus::Error>(false)
+        })
+        .await;
+        clicked.unwrap_or(Ok(false))
+    }
+
+    /// Shows `popup`, skipping it when no desktop session is available.
+    async fn deliver(&self, popup: &Popup) -> Result<bool, Box<dyn Error>> {
+        match self.show(popup).await {
+            Ok(clicked) => Ok(clicked),
+            Err(e) if is_unavailable(&e) => {
+                warn!("No desktop notification service available, skipping: {}", e);
+                Ok(false)
+            }
+            Err(e) => Err(format!("Failed to show desktop notification: {}", e).into()),
+        }
+    }
+
+    fn open(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        std::process::Command::new(&self.opener)
+            .arg(path)
+            .spawn()
+            .map_err(|e| format!("Failed to run {}: {}", self.opener, e))?;
+        Ok(())
+    }
+}
+
+/// Whether `error` means there is no session bus or nobody serving
+/// notifications on it, as on headless machines.
+fn is_unavailable(error: &zbus::Error) -> bool {
+    match error {
+        zbus::Error::Address(_) | zbus::Error::InputOutput(_) | zbus::Error::Handshake(_) => true,
+        zbus::Error::MethodError(name, _, _) => matches!(
+            name.as_str(),
+            "org.freedesktop.DBus.Error.ServiceUnknown"
+                | "org.freedesktop.DBus.Error.NameHasNoOwner"
+        ),
+        _ => false,
+    }
+}
+
+fn escape_markup(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Per-user directory for saved logs: `$XDG_RUNTIME_DIR/notifyme`, or a
+/// `notifyme-<uid>` directory in the temp dir that nobody else may own.
+fn log_dir() -> std::io::Result<PathBuf> {
+    let dir = match std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(runtime) => PathBuf::from(runtime).join("notifyme"),
+        // SAFETY: getuid cannot fail
+        None => std::env::temp_dir().join(format!("notifyme-{}", unsafe { libc::getuid() })),
+    };
+    match DirBuilder::new().mode(0o700).create(&dir) {
+        Ok(()) => return Ok(dir),
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e),
+    }
+
+    // An existing directory is only used if it cannot be a planted one
+    let metadata = std::fs::symlink_metadata(&dir)?;
+    // SAFETY: getuid cannot fail
+    let uid = unsafe { libc::getuid() };
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+        return Err(std::io::Error::new(
+            ErrorKind::PermissionDenied,
+            format!(
+                "{} is not a private directory of the current user",
+                dir.display()
+            ),
+        ));
+    }
+    Ok(dir)
+}
+
+/// Saves `output` readable only by the current user, never following or
+/// replacing an existing file.
+fn write_log(report: &RunReport, output: &str) -> std::io::Result<PathBuf> {
+    let dir = log_dir()?;
+    let name = report.log_file_name();
+    let stem = name.trim_end_matches(".log");
+    for attempt in 0..100 {
+        let path = if attempt == 0 {
+            dir.join(&name)
+        } else {
+            dir.join(format!("{}-{}.log", stem, attempt))
+        };
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+        {
+            Ok(mut file) => {
+                file.write_all(output.as_bytes())?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(std::io::Error::new(
+        ErrorKind::AlreadyExists,
+        format!("Too many logs named {} in {}", name, dir.display()),
+    ))
+}
+
+#[async_trait::async_trait]
+impl NotificationSender for DesktopNotifier {
+    async fn send(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        let popup = Popup {
+            icon: self
+                .icon
+                .clone()
+                .unwrap_or_else(|| "dialog-information".to_string()),
+            summary: self.app_name.clone(),
+            body: escape_markup(message),
+            urgency: self.success_urgency,
+            actions: Vec::new(),
+        };
+        self.deliver(&popup).await.map(|_| ())
+    }
+
+    async fn send_report(&self, report: &RunReport) -> Result<(), Box<dyn Error>> {
+        let mut popup = self.report_popup(report);
+        let output = report.output.as_deref().unwrap_or_default();
+        if !self.open_log || output.is_empty() {
+            return self.deliver(&popup).await.map(|_| ());
+        }
+
+        let path = write_log(report, output)?;
+        popup.actions = vec![OPEN_LOG_ACTION.to_string(), "Open log".to_string()];
+        if self.deliver(&popup).await? {
+            self.open(&path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::sync::Arc;
+    use zbus::object_server::SignalContext;
+    use zbus::zvariant::OwnedValue;
+
+    fn config() -> DesktopConfig {
+        DesktopConfig {
+            app_name: None,
+            icon: None,
+            timeout: Some(5000),
+            success_urgency: Some("low".to_string()),
+            failure_urgency: None,
+            open_log: Some(true),
+        }
+    }
+
+    fn report(success: bool) -> RunReport {
+        let mut report = RunReport::new("make", vec!["test".to_string()]);
+        report.host = "build-1".to_string();
+        report.duration = Duration::from_secs(3);
+        report.exit_code = Some(if success { 0 } else { 2 });
+        report.success = success;
+        report.output = Some("1\n2\n3\n4\n5\n<6>\n".to_string());
+        report
+    }
+
+    /// Records `Notify` calls, answering with id 7 and optionally clicking
+    /// the first action.
+    struct FakeServer {
+        calls: Arc<Mutex<Vec<Popup>>>,
+        click: bool,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Notifications")]
+    impl FakeServer {
+        #[allow(clippy::too_many_arguments)]
+        async fn notify(
+            &self,
+            #[zbus(signal_context)] context: SignalContext<'_>,
+            _app_name: String,
+            _replaces_id: u32,
+            app_icon: String,
+            summary: String,
+            body: String,
+            actions: Vec<String>,
+            hints: HashMap<String, OwnedValue>,
+            _expire_timeout: i32,
+        ) -> u32 {
+            let urgency = u8::try_from(&hints["urgency"]).unwrap();
+            self.calls.lock().unwrap().push(Popup {
+                icon: app_icon,
+                summary,
+                body,
+                urgency,
+                actions: actions.clone(),
+            });
+            if self.click && !actions.is_empty() {
+                Self::action_invoked(&context, 7, &actions[0])
+                    .await
+                    .unwrap();
+            }
+            7
+        }
+
+        #[zbus(signal)]
+        async fn action_invoked(
+            context: &SignalContext<'_>,
+            id: u32,
+            action_key: &str,
+        ) -> zbus::Result<()>;
+    }
+
+    /// A private bus, killed on drop.
+    struct Bus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+        }
+    }
+
+    fn start_bus() -> Option<Bus> {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address=1"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take()?)
+            .read_line(&mut address)
+            .ok()?;
+        Some(Bus {
+            daemon,
+            address: address.trim().to_string(),
+        })
+    }
+
+    #[test]
+    fn test_report_popup() {
+        let notifier = DesktopNotifier::from_config(&config()).unwrap();
+
+        let success = notifier.report_popup(&report(true));
+        assert_eq!(
+            success,
+            Popup {
+                icon: "dialog-information".to_string(),
+                summary: "✅ make succeeded".to_string(),
+                body: "make test on build-1\nDuration: 3s, exit code 0\n\n2\n3\n4\n5\n&lt;6&gt;"
+                    .to_string(),
+                urgency: 0,
+                actions: Vec::new(),
+            }
+        );
+
+        let failure = notifier.report_popup(&report(false));
+        assert_eq!(failure.icon, "dialog-error");
+        assert_eq!(failure.urgency, 2);
+
+        let mut invalid = config();
+        invalid.failure_urgency = Some("urgent".to_string());
+        assert!(DesktopNotifier::from_config(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_write_log() {
+        let mut report = report(false);
+        report.command = format!("write-log-{}", std::process::id());
+        let first = write_log(&report, "secret\n").unwrap();
+        let second = write_log(&report, "again\n").unwrap();
+        assert_ne!(first, second);
+        assert_eq!(std::fs::read_to_string(&first).unwrap(), "secret\n");
+        assert_eq!(std::fs::read_to_string(&second).unwrap(), "again\n");
+        for path in [&first, &second] {
+            let metadata = std::fs::metadata(path).unwrap();
+            assert_eq!(metadata.mode() & 0o777, 0o600);
+            let dir = std::fs::metadata(path.parent().unwrap()).unwrap();
+            assert_eq!(dir.mode() & 0o077, 0);
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_missing_bus_is_skipped() {
+        let mut notifier = DesktopNotifier::from_config(&config()).unwrap();
+        notifier.address = Some("unix:path=/nonexistent/notifyme-bus".to_string());
+        notifier.send("hello").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_notify_over_private_bus() {
+        // Only runs where dbus-daemon is installed
+        let Some(bus) = start_bus() else {
+            return;
+        };
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let server = FakeServer {
+            calls: calls.clone(),
+            click: true,
+        };
+        let _server = zbus::ConnectionBuilder::address(bus.address.as_str())
+            .unwrap()
+            .name(SERVICE)
+            .unwrap()
+            .serve_at(OBJECT_PATH, server)
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+
+        let mut notifier = DesktopNotifier::from_config(&config()).unwrap();
+        notifier.address = Some(bus.address.clone());
+        notifier.opener = "true".to_string();
+
+        let report = report(false);
+        notifier.send("started").await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), notifier.send_report(&report))
+            .await
+            .expect("the click should end the wait")
+            .unwrap();
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].summary, "notifyme");
+        assert_eq!(calls[0].body, "started");
+        assert_eq!(calls[1].summary, "❌ make failed");
+        assert_eq!(calls[1].icon, "dialog-error");
+        assert_eq!(calls[1].actions, vec!["default", "Open log"]);
+        assert_eq!(calls[1].urgency, 2);
+
+        let path = log_dir().unwrap().join(report.log_file_name());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "1\n2\n3\n4\n5\n<6>\n"
+        );
+        let _ = std::fs::remove_file(path);
+    }
+}
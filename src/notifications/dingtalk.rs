@@ -0,0 +1,315 @@
+use crate::config::{DingTalkConfig, NotificationConfigType};
+use crate::notifications::registry::{FieldSpec, FieldType, ProviderDescriptor};
+use crate::notifications::{config_mismatch, MentionWhen, NotificationSender};
+use crate::report::{format_duration, RunReport};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use log::{error, info};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::error::Error;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const PROVIDER: ProviderDescriptor = ProviderDescriptor {
+    name: "dingtalk",
+    display_name: "DingTalk",
+    fields: &[
+        FieldSpec::new("webhook_url", "Webhook URL", FieldType::String).required(),
+        FieldSpec::new("secret", "Secret", FieldType::String).secret(),
+        FieldSpec::new("at_mobile", "At Mobiles", FieldType::List),
+        FieldSpec::new("at_all", "At All", FieldType::Bool),
+        FieldSpec::new(
+            "mention_when",
+            "Mention When (always/success/failure)",
+            FieldType::String,
+        ),
+    ],
+    default_config: || NotificationConfigType::DingTalk(DingTalkConfig::default()),
+    build: |config| match config {
+        NotificationConfigType::DingTalk(config) => {
+            Ok(Box::new(DingTalkNotifier::from_config(config)?))
+        }
+        other => Err(config_mismatch(&PROVIDER, other)),
+    },
+};
+
+/// Characters of command output shown in a report, DingTalk rejects
+/// messages over 20000 bytes.
+const OUTPUT_LIMIT: usize = 4000;
+/// Characters of the error shown in a report, so that even multi-byte
+/// output and error stay within the 20000 bytes together.
+const ERROR_LIMIT: usize = 1000;
+
+pub struct DingTalkNotifier {
+    webhook_url: String,
+    secret: Option<String>,
+    at_mobiles: Vec<String>,
+    at_all: bool,
+    mention_when: MentionWhen,
+    client: Client,
+}
+
+impl DingTalkNotifier {
+    pub fn from_config(config: &DingTalkConfig) -> Result<Self, Box<dyn Error>> {
+        let when = config.mention_when.as_deref().unwrap_or_default();
+        let mention_when = MentionWhen::parse(when)
+            .ok_or_else(|| format!("Unknown DingTalk mention condition '{}'", when))?;
+
+        Ok(Self {
+            webhook_url: config.webhook_url.clone(),
+            secret: config.secret.clone().filter(|secret| !secret.is_empty()),
+            at_mobiles: config.at_mobiles.clone(),
+            at_all: config.at_all.unwrap_or(false),
+            mention_when,
+            client: Client::new(),
+        })
+    }
+
+    /// Signature of a request sent at `timestamp` (milliseconds), before
+    /// URL encoding.
+    fn generate_sign(&self, secret: &str, timestamp: u128) -> String {
+        let string_to_sign = format!("{}\n{}", timestamp, secret);
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .expect("HMAC can take key of any size");
+        mac.update(string_to_sign.as_bytes());
+        STANDARD.encode(mac.finalize().into_bytes())
+    }
+
+    /// Webhook URL with the signature parameters of robots using a secret.
+    fn signed_url(&self, timestamp: u128) -> Result<Url, Box<dyn Error>> {
+        let mut url = Url::parse(&self.webhook_url)?;
+        if let Some(secret) = &self.secret {
+            url.query_pairs_mut()
+                .append_pair("timestamp", &timestamp.to_string())
+                .append_pair("sign", &self.generate_sign(secret, timestamp));
+        }
+        Ok(url)
+    }
+
+    /// `at` section for a message, `success` being the outcome of the
+    /// reported run if any.
+    fn at(&self, success: Option<bool>) -> Value {
+        if !self.mention_when.applies(success) {
+            return json!({"atMobiles": [], "isAtAll": false});
+        }
+        json!({"atMobiles": self.at_mobiles, "isAtAll": self.at_all})
+    }
+
+    /// `@<mobile>` tags, which DingTalk requires in the text of markdown
+    /// messages for the mention to show.
+    fn mention_text(&self, success: Option<bool>) -> String {
+        if !self.mention_when.applies(success) {
+            return String::new();
+        }
+        self.at_mobiles
+            .iter()
+            .map(|mobile| format!("@{}", mobile))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn format_report(&self, report: &RunReport) -> String {
+        let icon = if report.success { "✅" } else { "❌" };
+        let exit_code = report
+            .exit_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let mut text = format!(
+            "### {} {} {}\n\n**Command:** `{}`\n\n**Host:** {}\n\n**Duration:** {}, exit code {}\n\n",
+            icon,
+            report.command,
+            report.status_text(),
+            report.command_line(),
+            report.host,
+            format_duration(report.duration),
+            exit_code
+        );
+        if report.error.is_some() {
+            text.push_str(&format!(
+                "**Error:** {}\n\n",
+                report.error_head(ERROR_LIMIT)
+            ));
+        }
+
+        let output = report.output_tail(OUTPUT_LIMIT).trim_end();
+        if !output.is_empty() {
+            text.push_str(&format!("> {}\n\n", output.replace('\n', "\n> ")));
+        }
+
+        let mentions = self.mention_text(Some(report.success));
+        if !mentions.is_empty() {
+            text.push_str(&mentions);
+        }
+        text.trim_end().to_string()
+    }
+
+    fn report_payload(&self, report: &RunReport) -> Value {
+        json!({
+            "msgtype": "markdown",
+            "markdown": {
+                "title": format!("{} {}", report.command, report.status_text()),
+                "text": self.format_report(report)
+            },
+            "at": self.at(Some(report.success))
+        })
+    }
+
+    async fn post(&self, payload: Value) -> Result<(), Box<dyn Error>> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        let url = self.signed_url(timestamp)?;
+        let response = self.client.post(url).json(&payload).send().await?;
+
+        let status = response.status();
+        let text = response.text().await?;
+        let body: DingTalkResponse = serde_json::from_str(&text).map_err(|e| {
+            error!("Failed to parse DingTalk response: {}, body: {}", e, text);
+            format!("Invalid DingTalk response: {}, body: {}", e, text)
+        })?;
+
+        if status.is_success() && body.errcode == 0 {
+            info!("DingTalk message sent successfully");
+            Ok(())
+        } else {
+            error!(
+                "Failed to send DingTalk message: status={}, errcode={}, errmsg={}",
+                status, body.errcode, body.errmsg
+            );
+            Err(format!(
+                "Failed to send DingTalk message: status={}, errcode={}, errmsg={}",
+                status, body.errcode, body.errmsg
+            )
+            .into())
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct DingTalkResponse {
+    errcode: i64,
+    #[serde(default)]
+    errmsg: String,
+}
+
+#[async_trait::async_trait]
+impl NotificationSender for DingTalkNotifier {
+    async fn send(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.post(json!({
+            "msgtype": "text",
+            "text": {"content": message},
+            "at": self.at(None)
+        }))
+        .await
+    }
+
+    async fn send_report(&self, report: &RunReport) -> Result<(), Box<dyn Error>> {
+        self.post(self.report_payload(report)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::test_server::{MockResponse, MockServer};
+
+    fn config(webhook_url: &str) -> DingTalkConfig {
+        DingTalkConfig {
+            webhook_url: webhook_url.to_string(),
+            secret: Some("SECtest".to_string()),
+            at_mobiles: vec!["13800000000".to_string()],
+            at_all: None,
+            mention_when: Some("failure".to_string()),
+        }
+    }
+
+    fn report(success: bool) -> RunReport {
+        let mut report = RunReport::new("make", vec!["test".to_string()]);
+        report.host = "build-1".to_string();
+        report.duration = std::time::Duration::from_secs(3);
+        report.exit_code = Some(if success { 0 } else { 2 });
+        report.success = success;
+        report.output = Some("one\ntwo\n".to_string());
+        report
+    }
+
+    #[test]
+    fn test_generate_sign() {
+        let notifier = DingTalkNotifier::from_config(&config("https://example.com")).unwrap();
+        let sign = notifier.generate_sign("SECtest", 1700000000000);
+        assert_eq!(sign, "aZLLrriXgn05YbwaGR7knYsLeJADjr9NwLaNNKpxh4g=");
+
+        let url = notifier.signed_url(1700000000000).unwrap();
+        assert_eq!(
+            url.query(),
+            Some("timestamp=1700000000000&sign=aZLLrriXgn05YbwaGR7knYsLeJADjr9NwLaNNKpxh4g%3D")
+        );
+    }
+
+    #[test]
+    fn test_report_payload() {
+        let notifier = DingTalkNotifier::from_config(&config("https://example.com")).unwrap();
+
+        let success = notifier.report_payload(&report(true));
+        assert_eq!(success["msgtype"], "markdown");
+        assert_eq!(success["markdown"]["title"], "make succeeded");
+        assert_eq!(
+            success["markdown"]["text"],
+            "### ✅ make succeeded\n\n**Command:** `make test`\n\n**Host:** build-1\n\n**Duration:** 3s, exit code 0\n\n> one\n> two"
+        );
+        assert_eq!(success["at"], json!({"atMobiles": [], "isAtAll": false}));
+
+        let failure = notifier.report_payload(&report(false));
+        assert!(failure["markdown"]["text"]
+            .as_str()
+            .unwrap()
+            .ends_with("> one\n> two\n\n@13800000000"));
+        assert_eq!(
+            failure["at"],
+            json!({"atMobiles": ["13800000000"], "isAtAll": false})
+        );
+
+        let mut long = report(false);
+        long.output = Some("输出\n".repeat(5000));
+        long.error = Some("错误".repeat(10000));
+        let text = notifier.format_report(&long);
+        assert!(text.len() <= 20000);
+        assert!(text.starts_with("### ❌ make failed"));
+
+        let mut invalid = config("https://example.com");
+        invalid.mention_when = Some("sometimes".to_string());
+        assert!(DingTalkNotifier::from_config(&invalid).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_post() {
+        let server = MockServer::start(vec![MockResponse::json(
+            json!({"errcode": 0, "errmsg": "ok"}),
+        )])
+        .await;
+        let notifier = DingTalkNotifier::from_config(&config(&format!(
+            "{}/robot/send?access_token=t",
+            server.url
+        )))
+        .unwrap();
+        notifier.send("hello").await.unwrap();
+
+        let requests = server.requests();
+        assert!(requests[0]
+            .path
+            .starts_with("/robot/send?access_token=t&timestamp="));
+        assert!(requests[0].path.contains("&sign="));
+        assert_eq!(requests[0].json()["text"]["content"], "hello");
+
+        let server = MockServer::start(vec![MockResponse::json(
+            json!({"errcode": 310000, "errmsg": "sign not match"}),
+        )])
+        .await;
+        let err = DingTalkNotifier::from_config(&config(&server.url))
+            .unwrap()
+            .send("hello")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("sign not match"));
+    }
+}
@@ -0,0 +1,410 @@
+use crate::config::{DiscordConfig, NotificationConfigType};
+use crate::notifications::registry::{FieldSpec, FieldType, ProviderDescriptor};
+use crate::notifications::{config_mismatch, tail_within_bytes, NotificationSender};
+use crate::report::{format_duration, RunReport};
+use log::{error, info, warn};
+use reqwest::multipart::{Form, Part};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde_json::{json, Value};
+use std::error::Error;
+use std::time::Duration;
+
+pub const PROVIDER: ProviderDescriptor = ProviderDescriptor {
+    name: "discord",
+    display_name: "Discord",
+    fields: &[
+        FieldSpec::new("webhook_url", "Webhook URL", FieldType::String).required(),
+        FieldSpec::new("username", "Username", FieldType::String),
+        FieldSpec::new("avatar_url", "Avatar URL", FieldType::String),
+        FieldSpec::new(
+            "attachment_threshold",
+            "Attach Output Above (chars)",
+            FieldType::Integer,
+        ),
+    ],
+    default_config: || NotificationConfigType::Discord(DiscordConfig::default()),
+    build: |config| match config {
+        NotificationConfigType::Discord(config) => Ok(Box::new(DiscordNotifier::new(config))),
+        other => Err(config_mismatch(&PROVIDER, other)),
+    },
+};
+
+/// Discord rejects message content longer than this many characters.
+const CONTENT_LIMIT: usize = 2000;
+/// Characters of output shown in the embed, whose description is limited
+/// to 4096 characters.
+const EMBED_OUTPUT_LIMIT: usize = 3500;
+/// Characters of an embed field value, Discord allows 1024.
+const FIELD_LIMIT: usize = 1000;
+/// Characters of an embed title.
+const TITLE_LIMIT: usize = 256;
+/// Characters Discord allows across title, field names and values and the
+/// description of an embed.
+const EMBED_TOTAL_LIMIT: usize = 6000;
+const DEFAULT_ATTACHMENT_THRESHOLD: usize = EMBED_OUTPUT_LIMIT;
+/// Bytes of output uploaded as a file, the upload limit of servers without
+/// boosts. Larger uploads fail with 413 and take the embed down with them.
+const ATTACHMENT_LIMIT: usize = 8 * 1024 * 1024;
+
+/// Times a rate limited request is retried before giving up.
+const MAX_RETRIES: u32 = 3;
+/// Upper bound on the wait requested by a `Retry-After` header.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+const SUCCESS_COLOR: u32 = 0x2eb886;
+const FAILURE_COLOR: u32 = 0xe01e5a;
+
+/// A file uploaded along with the message.
+struct Attachment {
+    file_name: String,
+    content: String,
+}
+
+pub struct DiscordNotifier {
+    webhook_url: String,
+    username: Option<String>,
+    avatar_url: Option<String>,
+    attachment_threshold: usize,
+    client: Client,
+}
+
+impl DiscordNotifier {
+    pub fn new(config: &DiscordConfig) -> Self {
+        let non_empty = |value: &Option<String>| value.clone().filter(|v| !v.is_empty());
+        Self {
+            webhook_url: config.webhook_url.clone(),
+            username: non_empty(&config.username),
+            avatar_url: non_empty(&config.avatar_url),
+            attachment_threshold: config
+                .attachment_threshold
+                .map(|t| t as usize)
+                .unwrap_or(DEFAULT_ATTACHMENT_THRESHOLD),
+            client: Client::new(),
+        }
+    }
+
+    /// Payload with the webhook identity overrides applied.
+    fn payload(&self, mut body: Value) -> Value {
+        if let Some(username) = &self.username {
+            body["username"] = json!(username);
+        }
+        if let Some(avatar_url) = &self.avatar_url {
+            body["avatar_url"] = json!(avatar_url);
+        }
+        body
+    }
+
+    fn build_embed(&self, report: &RunReport) -> Value {
+        let (color, icon) = if report.success {
+            (SUCCESS_COLOR, "✅")
+        } else {
+            (FAILURE_COLOR, "❌")
+        };
+        let exit_code = report
+            .exit_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let field = |name: &str, value: &str, inline: bool| {
+            let value: String = value.chars().take(FIELD_LIMIT).collect();
+            json!({"name": name, "value": value, "inline": inline})
+        };
+
+        let mut fields = vec![
+            field(
+                "Command",
+                &format!("`{}`", report.command_line().replace('`', "'")),
+                false,
+            ),
+            field("Host", &report.host, true),
+            field("Duration", &format_duration(report.duration), true),
+            field("Exit Code", &exit_code, true),
+        ];
+        if let Some(error) = &report.error {
+            fields.push(field("Error", error, false));
+        }
+
+        let title: String = format!("{} {} {}", icon, report.command, report.status_text())
+            .chars()
+            .take(TITLE_LIMIT)
+            .collect();
+        let used = title.chars().count()
+            + fields
+                .iter()
+                .map(|field| {
+                    let text = |key: &str| field[key].as_str().unwrap_or_default().chars().count();
+                    text("name") + text("value")
+                })
+                .sum::<usize>();
+
+        let mut embed = json!({
+            "title": title,
+            "color": color,
+            "fields": fields,
+            "timestamp": report.started_at.to_rfc3339(),
+        });
+        let output = report.output_tail(EMBED_OUTPUT_LIMIT).trim_end();
+        if !output.is_empty() {
+            // A zero width space keeps fences in the output from closing the block
+            let output = output.replace("```", "`\u{200b}``");
+            let budget = EMBED_TOTAL_LIMIT
+                .saturating_sub(used + "```\n\n```".len())
+                .min(EMBED_OUTPUT_LIMIT);
+            let skip = output.chars().count().saturating_sub(budget);
+            let output: String = output.chars().skip(skip).collect();
+            embed["description"] = json!(format!("```\n{}\n```", output));
+        }
+        embed
+    }
+
+    /// The output as a log file, keeping its end within `ATTACHMENT_LIMIT`.
+    fn output_attachment(report: &RunReport) -> Attachment {
+        let output = report.output.as_deref().unwrap_or_default();
+        Attachment {
+            file_name: report.log_file_name(),
+            content: tail_within_bytes(output, ATTACHMENT_LIMIT).to_string(),
+        }
+    }
+
+    /// Posts `body`, as multipart form data when a file is attached.
+    async fn post(
+        &self,
+        body: Value,
+        attachment: Option<Attachment>,
+    ) -> Result<(), Box<dyn Error>> {
+        let body = self.payload(body);
+        let response = self
+            .execute(|| match &attachment {
+                None => Ok(self.client.post(&self.webhook_url).json(&body)),
+                Some(attachment) => {
+                    let file = Part::bytes(attachment.content.clone().into_bytes())
+                        .file_name(attachment.file_name.clone())
+                        .mime_str("text/plain")?;
+                    let form = Form::new()
+                        .text("payload_json", body.to_string())
+                        .part("files[0]", file);
+                    Ok(self.client.post(&self.webhook_url).multipart(form))
+                }
+            })
+            .await?;
+
+        let status = response.status();
+        if status.is_success() {
+            info!("Discord message sent successfully");
+            Ok(())
+        } else {
+            let text = response.text().await?;
+            error!("Failed to send Discord message: {} - {}", status, text);
+            Err(format!("Failed to send Discord message: {} - {}", status, text).into())
+        }
+    }
+
+    /// Sends the request made by `build`, waiting out rate limits.
+    async fn execute(
+        &self,
+        build: impl Fn() -> reqwest::Result<RequestBuilder>,
+    ) -> Result<Response, Box<dyn Error>> {
+        let mut attempt = 0;
+        loop {
+            let response = build()?.send().await?;
+            if response.status() != StatusCode::TOO_MANY_REQUESTS || attempt == MAX_RETRIES {
+                return Ok(response);
+            }
+
+            attempt += 1;
+            let delay = retry_after(&response);
+            warn!(
+                "Discord rate limit hit, retrying in {:.1}s ({}/{})",
+                delay.as_secs_f64(),
+                attempt,
+                MAX_RETRIES
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// Wait requested by a rate limited response, one second if unspecified.
+fn retry_after(response: &Response) -> Duration {
+    response
+        .headers()
+        .get("retry-after")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<f64>().ok())
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(Duration::from_secs_f64)
+        .unwrap_or(Duration::from_secs(1))
+        .min(MAX_RETRY_DELAY)
+}
+
+#[async_trait::async_trait]
+impl NotificationSender for DiscordNotifier {
+    async fn send(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        if message.chars().count() <= CONTENT_LIMIT {
+            return self.post(json!({"content": message}), None).await;
+        }
+        let attachment = Attachment {
+            file_name: "message.txt".to_string(),
+            content: message.to_string(),
+        };
+        self.post(json!({"content": ""}), Some(attachment)).await
+    }
+
+    async fn send_report(&self, report: &RunReport) -> Result<(), Box<dyn Error>> {
+        let output = report.output.as_deref().unwrap_or_default();
+        let attachment = (output.chars().count() > self.attachment_threshold)
+            .then(|| Self::output_attachment(report));
+        self.post(json!({"embeds": [self.build_embed(report)]}), attachment)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::test_server::{MockResponse, MockServer};
+
+    fn report(success: bool, output: &str) -> RunReport {
+        let mut report = RunReport::new("make", vec!["test".to_string()]);
+        report.host = "build-1".to_string();
+        report.duration = Duration::from_secs(3);
+        report.exit_code = Some(if success { 0 } else { 2 });
+        report.success = success;
+        report.output = Some(output.to_string());
+        if !success {
+            report.error = Some("Command failed: boom".to_string());
+        }
+        report
+    }
+
+    fn notifier(url: &str, attachment_threshold: Option<u32>) -> DiscordNotifier {
+        DiscordNotifier::new(&DiscordConfig {
+            webhook_url: url.to_string(),
+            username: Some("notifyme".to_string()),
+            avatar_url: None,
+            attachment_threshold,
+        })
+    }
+
+    #[test]
+    fn test_build_embed() {
+        let notifier = notifier("https://discord.com/api/webhooks/1/x", None);
+
+        let success = notifier.build_embed(&report(true, "ok\n```\n"));
+        assert_eq!(success["title"], "✅ make succeeded");
+        assert_eq!(success["color"], SUCCESS_COLOR);
+        assert_eq!(success["fields"][0]["value"], "`make test`");
+        assert_eq!(success["fields"][1]["value"], "build-1");
+        assert_eq!(success["fields"][2]["value"], "3s");
+        assert_eq!(success["fields"][3]["value"], "0");
+        assert_eq!(success["description"], "```\nok\n`\u{200b}``\n```");
+
+        let failure = notifier.build_embed(&report(false, ""));
+        assert_eq!(failure["title"], "❌ make failed");
+        assert_eq!(failure["color"], FAILURE_COLOR);
+        assert_eq!(failure["fields"][4]["name"], "Error");
+        assert!(failure.get("description").is_none());
+
+        let mut long = report(false, &"```\n".repeat(3000));
+        long.command = "x".repeat(2000);
+        long.host = "h".repeat(2000);
+        long.error = Some("e".repeat(5000));
+        let embed = notifier.build_embed(&long);
+        let count = |value: &Value| value.as_str().unwrap().chars().count();
+        assert_eq!(count(&embed["title"]), TITLE_LIMIT);
+        let total = count(&embed["title"])
+            + count(&embed["description"])
+            + embed["fields"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|field| count(&field["name"]) + count(&field["value"]))
+                .sum::<usize>();
+        assert!(total <= EMBED_TOTAL_LIMIT);
+        assert!(embed["description"].as_str().unwrap().ends_with("``\n```"));
+    }
+
+    #[tokio::test]
+    async fn test_send_report() {
+        let server = MockServer::start(vec![MockResponse::new(204, "")]).await;
+        let notifier = notifier(&format!("{}/api/webhooks/1/x", server.url), None);
+
+        notifier
+            .send_report(&report(true, "short output"))
+            .await
+            .unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/api/webhooks/1/x");
+        let body = requests[0].json();
+        assert_eq!(body["username"], "notifyme");
+        assert_eq!(body["embeds"][0]["title"], "✅ make succeeded");
+    }
+
+    #[tokio::test]
+    async fn test_long_output_is_attached() {
+        let server = MockServer::start(vec![MockResponse::new(200, "{}")]).await;
+        let notifier = notifier(&server.url, Some(10));
+
+        let report = report(false, "line one\nline two\nline three\n");
+        notifier.send_report(&report).await.unwrap();
+
+        let requests = server.requests();
+        assert!(requests[0]
+            .header("content-type")
+            .unwrap()
+            .starts_with("multipart/form-data"));
+        let body = requests[0].body_text();
+        assert!(body.contains("name=\"payload_json\""));
+        assert!(body.contains("\"title\":\"❌ make failed\""));
+        assert!(body.contains(&format!(
+            "name=\"files[0]\"; filename=\"{}\"",
+            report.log_file_name()
+        )));
+        assert!(body.contains("line one\nline two\nline three\n"));
+    }
+
+    #[test]
+    fn test_attachment_limit() {
+        let mut report = report(false, "");
+        report.output = Some(format!(
+            "first\n{}last\n",
+            "输".repeat(ATTACHMENT_LIMIT / 3)
+        ));
+        let attachment = DiscordNotifier::output_attachment(&report);
+        assert!(attachment.content.len() <= ATTACHMENT_LIMIT);
+        assert!(attachment.content.starts_with('输'));
+        assert!(attachment.content.ends_with("last\n"));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_retry() {
+        let server = MockServer::start(vec![
+            MockResponse::new(
+                429,
+                r#"{"message":"You are being rate limited.","retry_after":0.05}"#,
+            )
+            .with_header("Retry-After", "0.05"),
+            MockResponse::new(204, ""),
+        ])
+        .await;
+
+        notifier(&server.url, None).send("hello").await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].json()["content"], "hello");
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_gives_up() {
+        let server = MockServer::start(vec![
+            MockResponse::new(429, "{}").with_header("Retry-After", "0")
+        ])
+        .await;
+
+        let err = notifier(&server.url, None).send("hello").await.unwrap_err();
+        assert!(err.to_string().contains("429"));
+        assert_eq!(server.requests().len(), MAX_RETRIES as usize + 1);
+    }
+}
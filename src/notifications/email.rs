@@ -1,14 +1,608 @@
-use super::NotificationSender;
+use crate::config::{EmailConfig, NotificationConfigType, SmtpConfig};
+use crate::notifications::registry::{FieldSpec, FieldType, ProviderDescriptor};
+use crate::notifications::{config_mismatch, NotificationSender};
+use crate::report::{format_duration, RunReport};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::transport::smtp::client::{Certificate, Tls, TlsParameters};
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use log::{error, info};
 use std::error::Error;
+use std::io::Write;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+pub const DEFAULT_SENDMAIL_PATH: &str = "/usr/sbin/sendmail";
+
+pub const PROVIDER: ProviderDescriptor = ProviderDescriptor {
+    name: "email",
+    display_name: "Email",
+    fields: &[
+        FieldSpec::new("to", "To", FieldType::List).required(),
+        FieldSpec::new("cc", "CC", FieldType::List),
+        FieldSpec::new("bcc", "BCC", FieldType::List),
+        FieldSpec::new("from", "From", FieldType::String).required(),
+        FieldSpec::new("reply_to", "Reply-To", FieldType::String),
+        FieldSpec::new("subject", "Subject Template", FieldType::String),
+        FieldSpec::new("body", "Body Template", FieldType::String),
+        FieldSpec::new("attach_log", "Attach Log", FieldType::Bool),
+        FieldSpec::new(
+            "compress_threshold",
+            "Gzip Log Above (bytes)",
+            FieldType::Integer,
+        ),
+        FieldSpec::new("transport", "Transport (smtp/sendmail)", FieldType::String)
+            .default_value("smtp"),
+        FieldSpec::new("sendmail_path", "Sendmail Path", FieldType::String),
+        FieldSpec::new("smtp.host", "SMTP Host", FieldType::String),
+        FieldSpec::new("smtp.port", "SMTP Port", FieldType::Integer),
+        FieldSpec::new("smtp.username", "SMTP Username", FieldType::String),
+        FieldSpec::new("smtp.password", "SMTP Password", FieldType::String).secret(),
+        FieldSpec::new("smtp.auth", "SMTP Auth", FieldType::Bool),
+        FieldSpec::new(
+            "smtp.encryption",
+            "SMTP Encryption (starttls/tls/none)",
+            FieldType::String,
+        ),
+        FieldSpec::new("smtp.timeout", "SMTP Timeout (s)", FieldType::Integer),
+        FieldSpec::new("smtp.tls_verify", "Verify TLS Certificate", FieldType::Bool),
+        FieldSpec::new(
+            "smtp.tls_ca_certs",
+            "TLS CA Certificates",
+            FieldType::String,
+        ),
+    ],
+    default_config: || NotificationConfigType::Email(EmailConfig::default()),
+    build: |config| match config {
+        NotificationConfigType::Email(config) => Ok(Box::new(EmailNotifier::from_config(config)?)),
+        other => Err(config_mismatch(&PROVIDER, other)),
+    },
+};
+
+const DEFAULT_SUBJECT: &str = "{icon} {command} {status} on {host}";
+/// Characters of command output included in the body.
+const OUTPUT_LIMIT: usize = 20000;
+/// Bytes of output attached uncompressed.
+const DEFAULT_COMPRESS_THRESHOLD: u32 = 256 * 1024;
+
+enum Transport {
+    Smtp {
+        transport: AsyncSmtpTransport<Tokio1Executor>,
+        /// Log the server's replies.
+        debug: bool,
+    },
+    /// Path of a sendmail compatible binary.
+    Sendmail(String),
+}
 
 pub struct EmailNotifier {
-    // Fields for SMTP settings, etc.
+    from: Mailbox,
+    reply_to: Option<Mailbox>,
+    to: Vec<Mailbox>,
+    cc: Vec<Mailbox>,
+    bcc: Vec<Mailbox>,
+    subject: String,
+    body: Option<String>,
+    attach_log: bool,
+    compress_threshold: usize,
+    transport: Transport,
+}
+
+impl EmailNotifier {
+    pub fn from_config(config: &EmailConfig) -> Result<Self, Box<dyn Error>> {
+        let non_empty = |value: &Option<String>| value.clone().filter(|v| !v.is_empty());
+        let transport = match config.transport.as_deref().unwrap_or_default() {
+            "" | "smtp" => Transport::Smtp {
+                transport: smtp_transport(&config.smtp)?,
+                debug: config.smtp.debug.unwrap_or(false),
+            },
+            "sendmail" => Transport::Sendmail(
+                non_empty(&config.sendmail_path)
+                    .unwrap_or_else(|| DEFAULT_SENDMAIL_PATH.to_string()),
+            ),
+            other => {
+                return Err(format!(
+                    "Unknown email transport '{}', expected smtp or sendmail",
+                    other
+                )
+                .into())
+            }
+        };
+
+        let to = parse_mailboxes("recipient", &config.to)?;
+        if to.is_empty() {
+            return Err("Email needs at least one recipient".into());
+        }
+        Ok(Self {
+            from: parse_mailbox("sender", &config.from)?,
+            reply_to: non_empty(&config.reply_to)
+                .map(|address| parse_mailbox("reply-to address", &address))
+                .transpose()?,
+            to,
+            cc: parse_mailboxes("CC recipient", &config.cc)?,
+            bcc: parse_mailboxes("BCC recipient", &config.bcc)?,
+            subject: non_empty(&config.subject).unwrap_or_else(|| DEFAULT_SUBJECT.to_string()),
+            body: non_empty(&config.body),
+            attach_log: config.attach_log.unwrap_or(true),
+            compress_threshold: config
+                .compress_threshold
+                .unwrap_or(DEFAULT_COMPRESS_THRESHOLD) as usize,
+            transport,
+        })
+    }
+
+    /// Summary of the run followed by the end of its output.
+    fn default_body(report: &RunReport) -> String {
+        let mut body = report.render("Command: {command_line}\nHost: {host}\nStarted: {started_at}\nDuration: {duration}, exit code {exit_code}");
+        if let Some(error) = &report.error {
+            body.push_str(&format!("\nError: {}", error));
+        }
+        let output = report.output_tail(OUTPUT_LIMIT).trim_end();
+        if !output.is_empty() {
+            body.push_str(&format!("\n\nOutput:\n{}", output));
+        }
+        body
+    }
+
+    /// The default body as a styled HTML page.
+    fn html_body(report: &RunReport) -> String {
+        let color = if report.success { "#1a7f37" } else { "#cf222e" };
+        let cell = "padding: 6px 12px; border-bottom: 1px solid #d0d7de; text-align: left;";
+        let mut rows = vec![
+            (
+                "Command",
+                format!("<code>{}</code>", escape_html(&report.command_line())),
+            ),
+            ("Host", escape_html(&report.host)),
+            ("Started", report.render("{started_at}")),
+            ("Duration", format_duration(report.duration)),
+            ("Exit code", report.render("{exit_code}")),
+        ];
+        if let Some(error) = &report.error {
+            rows.push(("Error", escape_html(error)));
+        }
+
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html><body style=\"font-family: -apple-system, 'Segoe UI', Helvetica, Arial, sans-serif; color: #1f2328;\">\n\
+             <div style=\"background: {}; color: #ffffff; padding: 12px 16px; font-size: 18px; font-weight: 600;\">{}</div>\n\
+             <table style=\"border-collapse: collapse; width: 100%;\">\n",
+            color,
+            escape_html(&report.render(DEFAULT_SUBJECT))
+        );
+        for (label, value) in rows {
+            html.push_str(&format!(
+                "<tr><th style=\"{0} width: 120px;\">{1}</th><td style=\"{0}\">{2}</td></tr>\n",
+                cell, label, value
+            ));
+        }
+        html.push_str("</table>\n");
+
+        let output = report.output_tail(OUTPUT_LIMIT).trim_end();
+        if !output.is_empty() {
+            html.push_str(&format!(
+                "<pre style=\"background: #f6f8fa; padding: 12px; font-size: 12px; overflow-x: auto;\">{}</pre>\n",
+                escape_html(output)
+            ));
+        }
+        html.push_str("</body></html>\n");
+        html
+    }
+
+    /// The full output as an attachment, gzipped when it is large.
+    fn log_attachment(&self, report: &RunReport) -> Result<Option<SinglePart>, Box<dyn Error>> {
+        let output = report.output.as_deref().unwrap_or_default();
+        if !self.attach_log || output.is_empty() {
+            return Ok(None);
+        }
+        let file_name = report.log_file_name();
+        if output.len() <= self.compress_threshold {
+            return Ok(Some(
+                Attachment::new(file_name).body(output.to_string(), ContentType::TEXT_PLAIN),
+            ));
+        }
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(output.as_bytes())?;
+        Ok(Some(Attachment::new(format!("{}.gz", file_name)).body(
+            encoder.finish()?,
+            ContentType::parse("application/gzip")?,
+        )))
+    }
+
+    fn message(
+        &self,
+        subject: String,
+        text: String,
+        html: Option<String>,
+        attachment: Option<SinglePart>,
+    ) -> Result<Message, Box<dyn Error>> {
+        let mut builder = Message::builder().from(self.from.clone()).subject(subject);
+        if let Some(reply_to) = &self.reply_to {
+            builder = builder.reply_to(reply_to.clone());
+        }
+        for mailbox in &self.to {
+            builder = builder.to(mailbox.clone());
+        }
+        for mailbox in &self.cc {
+            builder = builder.cc(mailbox.clone());
+        }
+        for mailbox in &self.bcc {
+            builder = builder.bcc(mailbox.clone());
+        }
+        if matches!(self.transport, Transport::Sendmail(_)) {
+            // `sendmail -t` reads the recipients from the headers and drops
+            // the Bcc header itself
+            builder = builder.keep_bcc();
+        }
+
+        let message = match (html, attachment) {
+            (None, None) => builder.singlepart(SinglePart::plain(text))?,
+            (None, Some(attachment)) => builder.multipart(
+                MultiPart::mixed()
+                    .singlepart(SinglePart::plain(text))
+                    .singlepart(attachment),
+            )?,
+            (Some(html), None) => {
+                builder.multipart(MultiPart::alternative_plain_html(text, html))?
+            }
+            (Some(html), Some(attachment)) => builder.multipart(
+                MultiPart::mixed()
+                    .multipart(MultiPart::alternative_plain_html(text, html))
+                    .singlepart(attachment),
+            )?,
+        };
+        Ok(message)
+    }
+
+    async fn deliver(&self, message: Message) -> Result<(), Box<dyn Error>> {
+        match &self.transport {
+            Transport::Smtp { transport, debug } => {
+                let response = transport.send(message).await.map_err(|e| {
+                    error!("Failed to send email over SMTP: {}", e);
+                    format!("Failed to send email over SMTP: {}", e)
+                })?;
+                if *debug {
+                    info!("SMTP server replied: {:?}", response);
+                }
+            }
+            Transport::Sendmail(path) => sendmail(path, &message).await?,
+        }
+        info!("Email sent successfully");
+        Ok(())
+    }
+}
+
+fn parse_mailbox(kind: &str, address: &str) -> Result<Mailbox, Box<dyn Error>> {
+    address
+        .parse()
+        .map_err(|e| format!("Invalid email {} '{}': {}", kind, address, e).into())
+}
+
+fn parse_mailboxes(kind: &str, addresses: &[String]) -> Result<Vec<Mailbox>, Box<dyn Error>> {
+    addresses
+        .iter()
+        .map(|address| address.trim())
+        .filter(|address| !address.is_empty())
+        .map(|address| parse_mailbox(kind, address))
+        .collect()
+}
+
+fn smtp_transport(
+    config: &SmtpConfig,
+) -> Result<AsyncSmtpTransport<Tokio1Executor>, Box<dyn Error>> {
+    let non_empty = |value: &Option<String>| value.clone().filter(|v| !v.is_empty());
+    if config.host.is_empty() {
+        return Err("SMTP host is required for the smtp transport".into());
+    }
+    for (name, value) in [
+        ("tls_cert", &config.tls_cert),
+        ("tls_key", &config.tls_key),
+        ("tls_ciphers", &config.tls_ciphers),
+    ] {
+        if non_empty(value).is_some() {
+            return Err(format!("SMTP {} is not supported", name).into());
+        }
+    }
+
+    let tls_parameters = || -> Result<TlsParameters, Box<dyn Error>> {
+        let mut builder = TlsParameters::builder(config.host.clone())
+            .dangerous_accept_invalid_certs(!config.tls_verify.unwrap_or(true));
+        if let Some(path) = non_empty(&config.tls_ca_certs) {
+            let pem = std::fs::read(&path)
+                .map_err(|e| format!("Failed to read TLS CA certificates '{}': {}", path, e))?;
+            builder = builder.add_root_certificate(Certificate::from_pem(&pem)?);
+        }
+        Ok(builder.build()?)
+    };
+    let encryption = match non_empty(&config.encryption) {
+        Some(encryption) => encryption.to_lowercase(),
+        None if config.port == 465 => "tls".to_string(),
+        None => "starttls".to_string(),
+    };
+    let (tls, default_port) = match encryption.as_str() {
+        "starttls" => (Tls::Required(tls_parameters()?), 587),
+        "tls" | "ssl" => (Tls::Wrapper(tls_parameters()?), 465),
+        "none" => (Tls::None, 25),
+        other => {
+            return Err(format!(
+                "Unknown SMTP encryption '{}', expected starttls, tls or none",
+                other
+            )
+            .into())
+        }
+    };
+
+    let mut builder = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host)
+        .port(if config.port == 0 {
+            default_port
+        } else {
+            config.port
+        })
+        .tls(tls);
+    if config.auth.unwrap_or(!config.username.is_empty()) {
+        builder = builder.credentials(Credentials::new(
+            config.username.clone(),
+            config.password.clone(),
+        ));
+    }
+    if let Some(timeout) = config.timeout {
+        builder = builder.timeout(Some(Duration::from_secs(timeout.into())));
+    }
+    Ok(builder.build())
+}
+
+/// Pipes `message` to `path -t`, which takes the recipients from its
+/// headers.
+async fn sendmail(path: &str, message: &Message) -> Result<(), Box<dyn Error>> {
+    let mut child = tokio::process::Command::new(path)
+        .args(["-t", "-i"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", path, e))?;
+    let mut stdin = child.stdin.take().ok_or("sendmail stdin is not piped")?;
+    stdin.write_all(&message.formatted()).await?;
+    drop(stdin);
+
+    let output = child.wait_with_output().await?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        error!("{} exited with {}: {}", path, output.status, stderr.trim());
+        Err(format!("{} exited with {}: {}", path, output.status, stderr.trim()).into())
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
 }
 
 #[async_trait::async_trait]
 impl NotificationSender for EmailNotifier {
     async fn send(&self, message: &str) -> Result<(), Box<dyn Error>> {
-        // TODO: Implement email sending using lettre
-        Ok(())
+        let message = self.message("notifyme".to_string(), message.to_string(), None, None)?;
+        self.deliver(message).await
+    }
+
+    async fn send_report(&self, report: &RunReport) -> Result<(), Box<dyn Error>> {
+        let (text, html) = match &self.body {
+            Some(template) => (report.render(template), None),
+            None => (Self::default_body(report), Some(Self::html_body(report))),
+        };
+        let message = self.message(
+            report.render(&self.subject),
+            text,
+            html,
+            self.log_attachment(report)?,
+        )?;
+        self.deliver(message).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, BufReader};
+    use tokio::net::TcpListener;
+
+    fn config(transport: &str) -> EmailConfig {
+        EmailConfig {
+            to: vec!["Ops <ops@example.org>".to_string()],
+            from: "notifyme@example.org".to_string(),
+            transport: Some(transport.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn report() -> RunReport {
+        let mut report = RunReport::new("make", vec!["test".to_string()]);
+        report.host = "build-1".to_string();
+        report.duration = std::time::Duration::from_secs(3);
+        report.exit_code = Some(2);
+        report.success = false;
+        report.output = Some("boom\n".to_string());
+        report
+    }
+
+    fn write_sendmail(name: &str, script: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("notifyme-sendmail-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{}", script)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    /// Accepts one SMTP session and records its commands and data.
+    async fn smtp_server() -> (u16, Arc<Mutex<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let transcript = Arc::new(Mutex::new(String::new()));
+        let recorded = transcript.clone();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = socket.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            writer.write_all(b"220 localhost ready\r\n").await.unwrap();
+            let mut in_data = false;
+            while let Ok(Some(line)) = lines.next_line().await {
+                recorded.lock().unwrap().push_str(&format!("{}\n", line));
+                let reply: &[u8] = if in_data {
+                    if line != "." {
+                        continue;
+                    }
+                    in_data = false;
+                    b"250 queued\r\n"
+                } else if line == "DATA" {
+                    in_data = true;
+                    b"354 go ahead\r\n"
+                } else if line == "QUIT" {
+                    b"221 bye\r\n"
+                } else {
+                    b"250 ok\r\n"
+                };
+                writer.write_all(reply).await.unwrap();
+            }
+        });
+        (port, transcript)
+    }
+
+    #[test]
+    fn test_render_templates() {
+        let report = report();
+        assert_eq!(report.render(DEFAULT_SUBJECT), "❌ make failed on build-1");
+        assert_eq!(
+            report.render("{command_line}: {exit_code} after {duration}"),
+            "make test: 2 after 3s"
+        );
+        assert!(EmailNotifier::default_body(&report)
+            .ends_with("\nDuration: 3s, exit code 2\n\nOutput:\nboom"));
+
+        assert!(EmailNotifier::from_config(&config("smtp")).is_err());
+        assert!(EmailNotifier::from_config(&config("carrier-pigeon")).is_err());
+        let mut invalid = config("sendmail");
+        invalid.cc = vec!["not an address".to_string()];
+        assert!(EmailNotifier::from_config(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_log_attachment() {
+        let mut config = config("sendmail");
+        config.compress_threshold = Some(16);
+        let notifier = EmailNotifier::from_config(&config).unwrap();
+
+        let small = String::from_utf8(
+            notifier
+                .log_attachment(&report())
+                .unwrap()
+                .unwrap()
+                .formatted(),
+        )
+        .unwrap();
+        assert!(small.contains("Content-Type: text/plain; charset=utf-8\r\n"));
+        assert!(small.contains("\r\n\r\nboom"));
+
+        let mut report = report();
+        report.output = Some("line\n".repeat(1000));
+        let large = String::from_utf8_lossy(
+            &notifier
+                .log_attachment(&report)
+                .unwrap()
+                .unwrap()
+                .formatted(),
+        )
+        .into_owned();
+        assert!(large.contains("Content-Type: application/gzip\r\n"));
+        assert!(large.contains(&format!("filename=\"{}.gz\"", report.log_file_name())));
+
+        config.attach_log = Some(false);
+        let notifier = EmailNotifier::from_config(&config).unwrap();
+        assert!(notifier.log_attachment(&report).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sendmail() {
+        let out =
+            std::env::temp_dir().join(format!("notifyme-sendmail-out-{}", std::process::id()));
+        let sendmail = write_sendmail(
+            "ok",
+            &format!("echo \"$@\" > {0}.args\ncat > {0}\n", out.display()),
+        );
+        let mut config = config("sendmail");
+        config.sendmail_path = Some(sendmail.display().to_string());
+        config.subject = Some("[{status}] {command}".to_string());
+        config.to.push("dev@example.org".to_string());
+        config.bcc = vec!["audit@example.org".to_string()];
+        config.reply_to = Some("oncall@example.org".to_string());
+        let notifier = EmailNotifier::from_config(&config).unwrap();
+
+        notifier.send_report(&report()).await.unwrap();
+
+        let args = fs::read_to_string(format!("{}.args", out.display())).unwrap();
+        assert_eq!(args.trim(), "-t -i");
+        let message = fs::read_to_string(&out).unwrap();
+        assert!(message.contains("To: Ops <ops@example.org>, dev@example.org\r\n"));
+        assert!(message.contains("Bcc: audit@example.org\r\n"));
+        assert!(message.contains("Reply-To: oncall@example.org\r\n"));
+        assert!(message.contains("From: notifyme@example.org\r\n"));
+        assert!(message.contains("Subject: [failed] make\r\n"));
+        assert!(message.contains("Content-Type: multipart/mixed;"));
+        assert!(message.contains("Content-Type: multipart/alternative;"));
+        assert!(message.contains("Content-Type: text/html; charset=utf-8\r\n"));
+        assert!(message.contains("Command: make test\r\n"));
+        assert!(message.contains(&format!(
+            "Content-Disposition: attachment; filename=\"{}\"",
+            report().log_file_name()
+        )));
+        fs::remove_file(format!("{}.args", out.display())).unwrap();
+        fs::remove_file(&out).unwrap();
+
+        let failing = write_sendmail("failing", "cat > /dev/null\necho 'no MTA' >&2\nexit 75\n");
+        config.sendmail_path = Some(failing.display().to_string());
+        let err = EmailNotifier::from_config(&config)
+            .unwrap()
+            .send("hello")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no MTA"));
+    }
+
+    #[tokio::test]
+    async fn test_smtp() {
+        let (port, transcript) = smtp_server().await;
+        let mut config = config("smtp");
+        config.smtp = SmtpConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            encryption: Some("none".to_string()),
+            ..Default::default()
+        };
+        config.cc = vec!["dev@example.org".to_string()];
+        config.bcc = vec!["audit@example.org".to_string()];
+        let notifier = EmailNotifier::from_config(&config).unwrap();
+
+        notifier.send_report(&report()).await.unwrap();
+
+        let transcript = transcript.lock().unwrap().clone();
+        assert!(transcript.contains("MAIL FROM:<notifyme@example.org>"));
+        for recipient in ["ops", "dev", "audit"] {
+            assert!(transcript.contains(&format!("RCPT TO:<{}@example.org>", recipient)));
+        }
+        assert!(transcript.contains("Cc: dev@example.org"));
+        assert!(!transcript.contains("Bcc:"));
+        assert!(transcript.contains("Subject: =?utf-8?b?"));
+        assert!(transcript.contains("Host: build-1"));
     }
 }
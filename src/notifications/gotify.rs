@@ -0,0 +1,234 @@
+use crate::config::{GotifyConfig, NotificationConfigType};
+use crate::notifications::registry::{FieldSpec, FieldType, ProviderDescriptor};
+use crate::notifications::{config_mismatch, NotificationSender};
+use crate::report::{format_duration, RunReport};
+use log::{error, info};
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::error::Error;
+
+pub const PROVIDER: ProviderDescriptor = ProviderDescriptor {
+    name: "gotify",
+    display_name: "Gotify",
+    fields: &[
+        FieldSpec::new("server_url", "Server URL", FieldType::String).required(),
+        FieldSpec::new("token", "Application Token", FieldType::String)
+            .required()
+            .secret(),
+        FieldSpec::new(
+            "success_priority",
+            "Success Priority (0-10)",
+            FieldType::Integer,
+        ),
+        FieldSpec::new(
+            "failure_priority",
+            "Failure Priority (0-10)",
+            FieldType::Integer,
+        ),
+        FieldSpec::new("click", "Click URL", FieldType::String),
+    ],
+    default_config: || NotificationConfigType::Gotify(GotifyConfig::default()),
+    build: |config| match config {
+        NotificationConfigType::Gotify(config) => {
+            Ok(Box::new(GotifyNotifier::from_config(config)?))
+        }
+        other => Err(config_mismatch(&PROVIDER, other)),
+    },
+};
+
+/// Characters of command output shown in a message.
+const OUTPUT_LIMIT: usize = 3000;
+/// Characters of the error shown in a message.
+const ERROR_LIMIT: usize = 1000;
+
+const DEFAULT_SUCCESS_PRIORITY: u8 = 4;
+/// High enough for the Android app to alert.
+const DEFAULT_FAILURE_PRIORITY: u8 = 8;
+
+pub struct GotifyNotifier {
+    server_url: String,
+    token: String,
+    success_priority: u8,
+    failure_priority: u8,
+    click: Option<String>,
+    client: Client,
+}
+
+impl GotifyNotifier {
+    pub fn from_config(config: &GotifyConfig) -> Result<Self, Box<dyn Error>> {
+        let priority = |value: Option<u8>, default: u8| match value.unwrap_or(default) {
+            priority @ 0..=10 => Ok(priority),
+            other => Err(format!(
+                "Gotify priority must be between 0 and 10, got {}",
+                other
+            )),
+        };
+
+        Ok(Self {
+            server_url: config.server_url.trim_end_matches('/').to_string(),
+            token: config.token.clone(),
+            success_priority: priority(config.success_priority, DEFAULT_SUCCESS_PRIORITY)?,
+            failure_priority: priority(config.failure_priority, DEFAULT_FAILURE_PRIORITY)?,
+            click: config.click.clone().filter(|url| !url.is_empty()),
+            client: Client::new(),
+        })
+    }
+
+    fn format_report(report: &RunReport) -> String {
+        let exit_code = report
+            .exit_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let mut message = format!(
+            "**Command:** `{}`  \n**Host:** {}  \n**Duration:** {}, exit code {}",
+            report.command_line(),
+            report.host,
+            format_duration(report.duration),
+            exit_code
+        );
+        if report.error.is_some() {
+            message.push_str(&format!(
+                "  \n**Error:** {}",
+                report.error_head(ERROR_LIMIT)
+            ));
+        }
+
+        let output = report.output_tail(OUTPUT_LIMIT).trim_end();
+        if !output.is_empty() {
+            // A zero width space keeps fences in the output from closing the block
+            let output = output.replace("```", "`\u{200b}``");
+            message.push_str(&format!("\n\n```\n{}\n```", output));
+        }
+        message
+    }
+
+    fn report_payload(&self, report: &RunReport) -> Value {
+        let (priority, icon) = if report.success {
+            (self.success_priority, "✅")
+        } else {
+            (self.failure_priority, "❌")
+        };
+        let mut extras = json!({"client::display": {"contentType": "text/markdown"}});
+        if let Some(click) = &self.click {
+            extras["client::notification"] = json!({"click": {"url": click}});
+        }
+
+        json!({
+            "title": format!("{} {} {}", icon, report.command, report.status_text()),
+            "message": Self::format_report(report),
+            "priority": priority,
+            "extras": extras,
+        })
+    }
+
+    async fn post(&self, payload: Value) -> Result<(), Box<dyn Error>> {
+        let response = self
+            .client
+            .post(format!("{}/message", self.server_url))
+            .header("X-Gotify-Key", &self.token)
+            .json(&payload)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if status.is_success() {
+            info!("Gotify message sent successfully");
+            Ok(())
+        } else {
+            let text = response.text().await?;
+            error!("Failed to send Gotify message: {} - {}", status, text);
+            Err(format!("Failed to send Gotify message: {} - {}", status, text).into())
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl NotificationSender for GotifyNotifier {
+    async fn send(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.post(json!({"message": message})).await
+    }
+
+    async fn send_report(&self, report: &RunReport) -> Result<(), Box<dyn Error>> {
+        self.post(self.report_payload(report)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::test_server::{MockResponse, MockServer};
+
+    fn config(server_url: &str) -> GotifyConfig {
+        GotifyConfig {
+            server_url: server_url.to_string(),
+            token: "AbCdEf".to_string(),
+            success_priority: None,
+            failure_priority: Some(10),
+            click: Some("https://ci.example.com/1".to_string()),
+        }
+    }
+
+    fn report(success: bool) -> RunReport {
+        let mut report = RunReport::new("make", vec!["test".to_string()]);
+        report.host = "build-1".to_string();
+        report.duration = std::time::Duration::from_secs(3);
+        report.exit_code = Some(if success { 0 } else { 2 });
+        report.success = success;
+        report.output = Some("one\ntwo\n".to_string());
+        report
+    }
+
+    #[test]
+    fn test_report_payload() {
+        let notifier = GotifyNotifier::from_config(&config("https://push.example.com/")).unwrap();
+
+        assert_eq!(
+            notifier.report_payload(&report(true)),
+            json!({
+                "title": "✅ make succeeded",
+                "message": "**Command:** `make test`  \n**Host:** build-1  \n**Duration:** 3s, exit code 0\n\n```\none\ntwo\n```",
+                "priority": 4,
+                "extras": {
+                    "client::display": {"contentType": "text/markdown"},
+                    "client::notification": {"click": {"url": "https://ci.example.com/1"}}
+                }
+            })
+        );
+        assert_eq!(notifier.report_payload(&report(false))["priority"], 10);
+
+        let mut failed = report(false);
+        failed.output = Some("```\n**done**\n".to_string());
+        failed.error = Some("e".repeat(5000));
+        let message = GotifyNotifier::format_report(&failed);
+        assert!(message.contains(&format!("**Error:** {}\n", "e".repeat(ERROR_LIMIT))));
+        assert!(message.ends_with("\n\n```\n`\u{200b}``\n**done**\n```"));
+
+        let mut invalid = config("https://push.example.com");
+        invalid.success_priority = Some(11);
+        assert!(GotifyNotifier::from_config(&invalid).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_post_message() {
+        let server = MockServer::start(vec![MockResponse::json(json!({"id": 1}))]).await;
+        let notifier = GotifyNotifier::from_config(&config(&format!("{}/", server.url))).unwrap();
+        notifier.send_report(&report(false)).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/message");
+        assert_eq!(requests[0].header("x-gotify-key"), Some("AbCdEf"));
+        assert_eq!(requests[0].json()["title"], "❌ make failed");
+
+        let server = MockServer::start(vec![MockResponse::new(
+            401,
+            r#"{"error":"Unauthorized","errorCode":401,"errorDescription":"you need to provide a valid access token"}"#,
+        )])
+        .await;
+        let err = GotifyNotifier::from_config(&config(&server.url))
+            .unwrap()
+            .send("hello")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("valid access token"));
+    }
+}
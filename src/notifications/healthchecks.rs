@@ -0,0 +1,202 @@
+use crate::config::{HealthchecksConfig, NotificationConfigType};
+use crate::notifications::registry::{FieldSpec, FieldType, ProviderDescriptor};
+use crate::notifications::{config_mismatch, NotificationSender};
+use crate::report::{RunContext, RunReport};
+use log::{error, info};
+use reqwest::Client;
+use std::error::Error;
+use std::time::Duration;
+
+pub const DEFAULT_BASE_URL: &str = "https://hc-ping.com";
+const DEFAULT_TIMEOUT_SECS: u32 = 10;
+/// Characters of command output sent with a failure ping, well below the
+/// 100 kB Healthchecks keeps.
+const OUTPUT_LIMIT: usize = 10000;
+/// Characters of the error sent with a failure ping.
+const ERROR_LIMIT: usize = 10000;
+
+pub const PROVIDER: ProviderDescriptor = ProviderDescriptor {
+    name: "healthchecks",
+    display_name: "Healthchecks",
+    fields: &[
+        FieldSpec::new("uuid", "Check UUID", FieldType::String)
+            .required()
+            .secret(),
+        FieldSpec::new("base_url", "Ping URL", FieldType::String).default_value(DEFAULT_BASE_URL),
+        FieldSpec::new("ping_start", "Ping On Start", FieldType::Bool),
+        FieldSpec::new("timeout", "Timeout (s)", FieldType::Integer),
+    ],
+    default_config: || NotificationConfigType::Healthchecks(HealthchecksConfig::default()),
+    build: |config| match config {
+        NotificationConfigType::Healthchecks(config) => {
+            Ok(Box::new(HealthchecksNotifier::from_config(config)?))
+        }
+        other => Err(config_mismatch(&PROVIDER, other)),
+    },
+};
+
+pub struct HealthchecksNotifier {
+    check_url: String,
+    ping_start: bool,
+    client: Client,
+}
+
+impl HealthchecksNotifier {
+    pub fn from_config(config: &HealthchecksConfig) -> Result<Self, Box<dyn Error>> {
+        let base_url = config
+            .base_url
+            .clone()
+            .filter(|url| !url.is_empty())
+            .unwrap_or_else(|| DEFAULT_BASE_URL.to_string());
+        let check = config.uuid.trim_matches('/');
+        if check.is_empty() {
+            return Err("Healthchecks needs the uuid of a check".into());
+        }
+
+        Ok(Self {
+            check_url: format!("{}/{}", base_url.trim_end_matches('/'), check),
+            ping_start: config.ping_start.unwrap_or(true),
+            client: Client::builder()
+                .timeout(Duration::from_secs(
+                    config.timeout.unwrap_or(DEFAULT_TIMEOUT_SECS).into(),
+                ))
+                .build()?,
+        })
+    }
+
+    /// `suffix` is `""` for success or one of `/start`, `/fail` and `/log`.
+    async fn ping(&self, suffix: &str, body: String) -> Result<(), Box<dyn Error>> {
+        let response = self
+            .client
+            .post(format!("{}{}", self.check_url, suffix))
+            .body(body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        let text = response.text().await?;
+        if status.is_success() {
+            // Unknown checks still answer 200 with "OK (not found)"
+            if text.contains("not found") {
+                error!("Healthchecks does not know the check: {}", text);
+                return Err(format!("Healthchecks ping failed: {}", text).into());
+            }
+            info!("Healthchecks ping{} sent", suffix);
+            return Ok(());
+        }
+        error!("Failed to ping Healthchecks: {} - {}", status, text);
+        Err(format!("Failed to ping Healthchecks: {} - {}", status, text).into())
+    }
+}
+
+#[async_trait::async_trait]
+impl NotificationSender for HealthchecksNotifier {
+    /// Plain messages go to the check's event log without changing its state.
+    async fn send(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.ping("/log", message.to_string()).await
+    }
+
+    async fn send_report(&self, report: &RunReport) -> Result<(), Box<dyn Error>> {
+        if report.success {
+            return self.ping("", String::new()).await;
+        }
+        let mut body = report.output_tail(OUTPUT_LIMIT).to_string();
+        if report.error.is_some() {
+            if !body.is_empty() && !body.ends_with('\n') {
+                body.push('\n');
+            }
+            body.push_str(report.error_head(ERROR_LIMIT));
+        }
+        self.ping("/fail", body).await
+    }
+
+    /// Marks the check as running so Healthchecks can measure the run and
+    /// alert on a run that never finishes.
+    async fn on_start(&self, _context: &RunContext) -> Result<(), Box<dyn Error>> {
+        if !self.ping_start {
+            return Ok(());
+        }
+        self.ping("/start", String::new()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::test_server::{MockResponse, MockServer};
+
+    const UUID: &str = "5bf66975-d4c7-4bf5-bcc8-b8d8a82ea278";
+
+    fn config(base_url: &str) -> HealthchecksConfig {
+        HealthchecksConfig {
+            uuid: UUID.to_string(),
+            base_url: Some(format!("{}/ping/", base_url)),
+            ..Default::default()
+        }
+    }
+
+    fn report(success: bool) -> RunReport {
+        let mut report = RunReport::new("backup.sh", Vec::new());
+        report.exit_code = Some(if success { 0 } else { 1 });
+        report.success = success;
+        report.output = Some("dumping...\ndisk full".to_string());
+        if !success {
+            report.error = Some("Command exited with status 1".to_string());
+        }
+        report
+    }
+
+    #[tokio::test]
+    async fn test_start_and_fail() {
+        let server = MockServer::start(vec![MockResponse::new(200, "OK")]).await;
+        let notifier = HealthchecksNotifier::from_config(&config(&server.url)).unwrap();
+
+        let failure = report(false);
+        let context = RunContext::new("nightly", &failure);
+        notifier.on_start(&context).await.unwrap();
+        notifier.on_finish(&context, &failure).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, format!("/ping/{}/start", UUID));
+        assert_eq!(requests[1].method, "POST");
+        assert_eq!(requests[1].path, format!("/ping/{}/fail", UUID));
+        assert_eq!(
+            requests[1].body_text(),
+            "dumping...\ndisk full\nCommand exited with status 1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_success_without_start() {
+        let server = MockServer::start(vec![MockResponse::new(200, "OK")]).await;
+        let mut config = config(&server.url);
+        config.uuid = "fqOOd6-F4MMNuCEnzTU01w/nightly-backup".to_string();
+        config.ping_start = Some(false);
+        let notifier = HealthchecksNotifier::from_config(&config).unwrap();
+
+        let success = report(true);
+        let context = RunContext::new("nightly", &success);
+        notifier.on_start(&context).await.unwrap();
+        notifier.on_finish(&context, &success).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(
+            requests[0].path,
+            "/ping/fqOOd6-F4MMNuCEnzTU01w/nightly-backup"
+        );
+        assert!(requests[0].body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_check() {
+        let server = MockServer::start(vec![MockResponse::new(200, "OK (not found)")]).await;
+        let notifier = HealthchecksNotifier::from_config(&config(&server.url)).unwrap();
+        assert!(notifier.send_report(&report(true)).await.is_err());
+
+        let server = MockServer::start(vec![MockResponse::new(404, "not found")]).await;
+        let notifier = HealthchecksNotifier::from_config(&config(&server.url)).unwrap();
+        let err = notifier.send_report(&report(true)).await.unwrap_err();
+        assert!(err.to_string().contains("404"));
+    }
+}
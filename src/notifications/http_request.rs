@@ -1 +1,480 @@
+use crate::config::{HttpConfig, NotificationConfigType};
+use crate::notifications::registry::{FieldSpec, FieldType, ProviderDescriptor};
+use crate::notifications::{config_mismatch, NotificationSender};
+use crate::report::{fill_template, RunReport};
+use hmac::{Hmac, Mac};
+use log::{error, info, warn};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::{Certificate, Client, Identity, Method, Proxy, RequestBuilder, Url};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::error::Error;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+pub const DEFAULT_SIGNATURE_HEADER: &str = "X-Notifyme-Signature";
+pub const DEFAULT_TIMESTAMP_HEADER: &str = "X-Notifyme-Timestamp";
+
+pub const PROVIDER: ProviderDescriptor = ProviderDescriptor {
+    name: "http",
+    display_name: "HTTP Webhook",
+    fields: &[
+        FieldSpec::new("url", "URL", FieldType::String).required(),
+        FieldSpec::new("method", "Method", FieldType::String).default_value("POST"),
+        FieldSpec::new(
+            "headers",
+            "Headers (name:value)",
+            FieldType::Records(&["key", "value"]),
+        ),
+        FieldSpec::new("body", "Body Template", FieldType::String),
+        FieldSpec::new("timeout", "Timeout (s)", FieldType::Integer),
+        FieldSpec::new("retry", "Retries", FieldType::Integer),
+        FieldSpec::new("retry_delay", "Retry Delay (s)", FieldType::Integer),
+        FieldSpec::new("signing_secret", "HMAC Signing Secret", FieldType::String).secret(),
+        FieldSpec::new("signature_header", "Signature Header", FieldType::String),
+        FieldSpec::new("timestamp_header", "Timestamp Header", FieldType::String),
+        FieldSpec::new("bearer_token", "Bearer Token", FieldType::String).secret(),
+        FieldSpec::new("username", "Basic Auth User", FieldType::String),
+        FieldSpec::new("password", "Basic Auth Password", FieldType::String).secret(),
+        FieldSpec::new("client_cert", "Client Certificate (PEM)", FieldType::String),
+        FieldSpec::new("client_key", "Client Key (PEM)", FieldType::String),
+        FieldSpec::new("ca_cert", "CA Bundle (PEM)", FieldType::String),
+        FieldSpec::new("proxy", "Proxy URL", FieldType::String),
+        FieldSpec::new("no_proxy", "No Proxy Hosts", FieldType::String),
+    ],
+    default_config: || NotificationConfigType::Http(HttpConfig::default()),
+    build: |config| match config {
+        NotificationConfigType::Http(config) => Ok(Box::new(HttpNotifier::from_config(config)?)),
+        other => Err(config_mismatch(&PROVIDER, other)),
+    },
+};
+
+const DEFAULT_TIMEOUT_SECS: u32 = 30;
+const DEFAULT_RETRY_DELAY_SECS: u32 = 5;
+
+enum Auth {
+    None,
+    Bearer(String),
+    Basic(String, Option<String>),
+}
+
+/// HMAC signature of every request body.
+struct Signing {
+    secret: String,
+    signature_header: String,
+    timestamp_header: String,
+}
+
+pub struct HttpNotifier {
+    url: Url,
+    method: Method,
+    headers: HeaderMap,
+    body: Option<String>,
+    retry: u32,
+    retry_delay: Duration,
+    signing: Option<Signing>,
+    auth: Auth,
+    client: Client,
+}
+
+impl HttpNotifier {
+    pub fn from_config(config: &HttpConfig) -> Result<Self, Box<dyn Error>> {
+        let non_empty = |value: &Option<String>| value.clone().filter(|v| !v.is_empty());
+        let url =
+            Url::parse(&config.url).map_err(|e| format!("Invalid URL '{}': {}", config.url, e))?;
+        let method = match config.method.trim() {
+            "" => Method::POST,
+            method => Method::from_bytes(method.to_uppercase().as_bytes())
+                .map_err(|_| format!("Invalid HTTP method '{}'", method))?,
+        };
+
+        let mut headers = HeaderMap::new();
+        for header in config.headers.iter().flatten() {
+            let name = HeaderName::from_bytes(header.key.trim().as_bytes())
+                .map_err(|_| format!("Invalid HTTP header name '{}'", header.key))?;
+            let value = HeaderValue::from_str(header.value.trim())
+                .map_err(|_| format!("Invalid value for HTTP header '{}'", header.key))?;
+            headers.append(name, value);
+        }
+
+        let auth = match (non_empty(&config.bearer_token), non_empty(&config.username)) {
+            (Some(_), Some(_)) => {
+                return Err("Set either a bearer token or a basic auth user, not both".into())
+            }
+            (Some(token), None) => Auth::Bearer(token),
+            (None, Some(username)) => Auth::Basic(username, non_empty(&config.password)),
+            (None, None) => Auth::None,
+        };
+        let signing = non_empty(&config.signing_secret).map(|secret| Signing {
+            secret,
+            signature_header: non_empty(&config.signature_header)
+                .unwrap_or_else(|| DEFAULT_SIGNATURE_HEADER.to_string()),
+            timestamp_header: non_empty(&config.timestamp_header)
+                .unwrap_or_else(|| DEFAULT_TIMESTAMP_HEADER.to_string()),
+        });
+
+        Ok(Self {
+            url,
+            method,
+            headers,
+            body: non_empty(&config.body),
+            retry: config.retry.unwrap_or(0),
+            retry_delay: Duration::from_secs(
+                config
+                    .retry_delay
+                    .unwrap_or(DEFAULT_RETRY_DELAY_SECS)
+                    .into(),
+            ),
+            signing,
+            auth,
+            client: Self::client(config)?,
+        })
+    }
+
+    fn client(config: &HttpConfig) -> Result<Client, Box<dyn Error>> {
+        let non_empty = |value: &Option<String>| value.clone().filter(|v| !v.is_empty());
+        let read = |kind: &str, path: &str| {
+            std::fs::read(path).map_err(|e| format!("Failed to read {} '{}': {}", kind, path, e))
+        };
+
+        let mut builder = Client::builder()
+            .use_rustls_tls()
+            .timeout(Duration::from_secs(
+                config.timeout.unwrap_or(DEFAULT_TIMEOUT_SECS).into(),
+            ));
+        if let Some(path) = non_empty(&config.ca_cert) {
+            for certificate in Certificate::from_pem_bundle(&read("CA bundle", &path)?)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        if let Some(path) = non_empty(&config.client_cert) {
+            // rustls expects the key and the certificate chain in one buffer
+            let mut pem = match non_empty(&config.client_key) {
+                Some(key) => read("client key", &key)?,
+                None => Vec::new(),
+            };
+            pem.push(b'\n');
+            pem.extend(read("client certificate", &path)?);
+            builder = builder.identity(
+                Identity::from_pem(&pem)
+                    .map_err(|e| format!("Invalid client certificate '{}': {}", path, e))?,
+            );
+        }
+        if let Some(url) = non_empty(&config.proxy) {
+            let proxy = Proxy::all(&url).map_err(|e| format!("Invalid proxy '{}': {}", url, e))?;
+            let no_proxy =
+                non_empty(&config.no_proxy).and_then(|hosts| reqwest::NoProxy::from_string(&hosts));
+            builder = builder.proxy(proxy.no_proxy(no_proxy));
+        }
+        Ok(builder.build()?)
+    }
+
+    /// Whether the configured `Content-Type` asks for a JSON body.
+    fn is_json(&self) -> bool {
+        self.headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_none_or(|value| value.to_lowercase().contains("json"))
+    }
+
+    /// `template` with `values` substituted, escaped for a JSON string
+    /// literal when the body is JSON.
+    fn render(&self, template: &str, values: &[(&str, String)]) -> String {
+        let json = self.is_json();
+        fill_template(template, values, |value| {
+            if json {
+                let quoted = Value::String(value.to_string()).to_string();
+                quoted[1..quoted.len() - 1].to_string()
+            } else {
+                value.to_string()
+            }
+        })
+    }
+
+    fn sign(&self, request: RequestBuilder, body: &str) -> RequestBuilder {
+        let Some(signing) = &self.signing else {
+            return request;
+        };
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let signature = signature(&signing.secret, timestamp, body);
+        request
+            .header(signing.timestamp_header.as_str(), timestamp.to_string())
+            .header(
+                signing.signature_header.as_str(),
+                format!("sha256={}", signature),
+            )
+    }
+
+    fn request(&self, body: &str) -> RequestBuilder {
+        let mut request = self
+            .client
+            .request(self.method.clone(), self.url.clone())
+            .headers(self.headers.clone());
+        if !self.headers.contains_key(CONTENT_TYPE) {
+            request = request.header(CONTENT_TYPE, "application/json");
+        }
+        request = match &self.auth {
+            Auth::None => request,
+            Auth::Bearer(token) => request.bearer_auth(token),
+            Auth::Basic(username, password) => request.basic_auth(username, password.as_ref()),
+        };
+        self.sign(request, body).body(body.to_string())
+    }
+
+    /// Sends `body`, retrying connection errors and server errors.
+    async fn deliver(&self, body: String) -> Result<(), Box<dyn Error>> {
+        let mut attempt = 0;
+        loop {
+            let failure = match self.request(&body).send().await {
+                Ok(response) if response.status().is_success() => {
+                    info!("HTTP webhook delivered: {}", response.status());
+                    return Ok(());
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let text = response.text().await.unwrap_or_default();
+                    let failure = format!("HTTP webhook failed: {} - {}", status, text);
+                    if !status.is_server_error() {
+                        error!("{}", failure);
+                        return Err(failure.into());
+                    }
+                    failure
+                }
+                Err(e) => format!("HTTP webhook request failed: {}", e),
+            };
+
+            if attempt >= self.retry {
+                error!("{}", failure);
+                return Err(failure.into());
+            }
+            attempt += 1;
+            warn!("{}, retry {} of {}", failure, attempt, self.retry);
+            tokio::time::sleep(self.retry_delay).await;
+        }
+    }
+}
+
+/// Hex encoded HMAC-SHA256 of `<timestamp>.<body>`.
+fn signature(secret: &str, timestamp: u64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl NotificationSender for HttpNotifier {
+    async fn send(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        let body = match &self.body {
+            Some(template) => self.render(template, &[("{message}", message.to_string())]),
+            None => json!({ "message": message }).to_string(),
+        };
+        self.deliver(body).await
+    }
+
+    async fn send_report(&self, report: &RunReport) -> Result<(), Box<dyn Error>> {
+        let body = match &self.body {
+            Some(template) => {
+                let mut values = report.placeholders();
+                values.push(("{message}", report.to_message()));
+                self.render(template, &values)
+            }
+            None => json!({
+                "message": report.to_message(),
+                "report": {
+                    "command": report.command,
+                    "args": report.args,
+                    "command_line": report.command_line(),
+                    "host": report.host,
+                    "started_at": report.started_at.to_rfc3339(),
+                    "duration_secs": report.duration.as_secs_f64(),
+                    "exit_code": report.exit_code,
+                    "success": report.success,
+                    "output": report.output,
+                    "error": report.error,
+                }
+            })
+            .to_string(),
+        };
+        self.deliver(body).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::HttpHeader;
+    use crate::notifications::test_server::{MockResponse, MockServer};
+
+    fn config(url: &str) -> HttpConfig {
+        HttpConfig {
+            url: format!("{}/hooks/alerts", url),
+            retry_delay: Some(0),
+            ..Default::default()
+        }
+    }
+
+    fn report() -> RunReport {
+        let mut report = RunReport::new("make", vec!["test".to_string()]);
+        report.host = "build-1".to_string();
+        report.exit_code = Some(2);
+        report.success = false;
+        report.output = Some("said \"no\"\n".to_string());
+        report
+    }
+
+    #[tokio::test]
+    async fn test_signed_report() {
+        let server = MockServer::start(vec![MockResponse::new(204, "")]).await;
+        let mut config = config(&server.url);
+        config.method = "put".to_string();
+        config.headers = Some(vec![HttpHeader {
+            key: "X-Env".to_string(),
+            value: "prod".to_string(),
+        }]);
+        config.signing_secret = Some("s3cret".to_string());
+        config.bearer_token = Some("token-1".to_string());
+        let notifier = HttpNotifier::from_config(&config).unwrap();
+
+        notifier.send_report(&report()).await.unwrap();
+
+        let requests = server.requests();
+        let request = &requests[0];
+        assert_eq!(request.method, "PUT");
+        assert_eq!(request.path, "/hooks/alerts");
+        assert_eq!(request.header("x-env"), Some("prod"));
+        assert_eq!(request.header("content-type"), Some("application/json"));
+        assert_eq!(request.header("authorization"), Some("Bearer token-1"));
+
+        let timestamp: u64 = request
+            .header(DEFAULT_TIMESTAMP_HEADER)
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(
+            request.header(DEFAULT_SIGNATURE_HEADER),
+            Some(
+                format!(
+                    "sha256={}",
+                    signature("s3cret", timestamp, &request.body_text())
+                )
+                .as_str()
+            )
+        );
+        let body = request.json();
+        assert_eq!(body["report"]["command_line"], "make test");
+        assert_eq!(body["report"]["exit_code"], 2);
+        assert_eq!(body["report"]["success"], false);
+    }
+
+    #[tokio::test]
+    async fn test_template_and_basic_auth() {
+        let server = MockServer::start(vec![MockResponse::new(200, "ok")]).await;
+        let mut config = config(&server.url);
+        config.body = Some(r#"{"text":"{icon} {command_line} {status}: {output}"}"#.to_string());
+        config.username = Some("user".to_string());
+        config.password = Some("pass".to_string());
+        config.signature_header = Some("X-Signature".to_string());
+        let notifier = HttpNotifier::from_config(&config).unwrap();
+
+        notifier.send_report(&report()).await.unwrap();
+
+        let request = &server.requests()[0];
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.header("authorization"), Some("Basic dXNlcjpwYXNz"));
+        // Signing is off without a secret
+        assert_eq!(request.header("X-Signature"), None);
+        assert_eq!(request.json()["text"], "❌ make test failed: said \"no\"\n");
+    }
+
+    #[tokio::test]
+    async fn test_template_values_not_rescanned() {
+        let server = MockServer::start(vec![MockResponse::new(200, "ok")]).await;
+        let mut config = config(&server.url);
+        config.body = Some(r#"{"error":"{error}","output":"{output}"}"#.to_string());
+        let notifier = HttpNotifier::from_config(&config).unwrap();
+
+        let mut report = report();
+        report.error = Some("missing {output} and {message}".to_string());
+        report.output = Some(r#"{host}","admin":"1"#.to_string());
+        notifier.send_report(&report).await.unwrap();
+
+        let body = server.requests()[0].json();
+        assert_eq!(body["error"], "missing {output} and {message}");
+        assert_eq!(body["output"], r#"{host}","admin":"1"#);
+        assert_eq!(body.as_object().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let server = MockServer::start(vec![
+            MockResponse::new(503, "busy"),
+            MockResponse::new(200, "ok"),
+        ])
+        .await;
+        let mut config = config(&server.url);
+        config.retry = Some(2);
+        HttpNotifier::from_config(&config)
+            .unwrap()
+            .send("hello")
+            .await
+            .unwrap();
+        assert_eq!(server.requests().len(), 2);
+        assert_eq!(server.requests()[1].json()["message"], "hello");
+
+        // Client errors are not retried
+        let server = MockServer::start(vec![MockResponse::new(401, "unsigned")]).await;
+        let err = HttpNotifier::from_config(&self::config(&server.url))
+            .unwrap()
+            .send("hello")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("unsigned"));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_proxy() {
+        let proxy = MockServer::start(vec![MockResponse::new(200, "ok")]).await;
+        let mut config = config("http://alerts.example.invalid");
+        config.proxy = Some(proxy.url.clone());
+        HttpNotifier::from_config(&config)
+            .unwrap()
+            .send("hello")
+            .await
+            .unwrap();
+        assert_eq!(
+            proxy.requests()[0].path,
+            "http://alerts.example.invalid/hooks/alerts"
+        );
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let invalid = |update: fn(&mut HttpConfig)| {
+            let mut config = config("https://example.com");
+            update(&mut config);
+            HttpNotifier::from_config(&config).is_err()
+        };
+        assert!(invalid(|config| config.method = "NOT A METHOD".to_string()));
+        assert!(invalid(|config| {
+            config.bearer_token = Some("token".to_string());
+            config.username = Some("user".to_string());
+        }));
+        assert!(invalid(
+            |config| config.ca_cert = Some("/nonexistent/ca.pem".to_string())
+        ));
+        assert!(invalid(|config| {
+            config.client_cert = Some("/nonexistent/client.pem".to_string())
+        }));
+        assert!(!invalid(
+            |config| config.proxy = Some("http://proxy:3128".to_string())
+        ));
+    }
+}
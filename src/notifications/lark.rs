@@ -1,75 +1,279 @@
-use crate::notifications::NotificationSender;
+use crate::config::{LarkConfig, LarkMentionConfig, NotificationConfigType};
+use crate::notifications::registry::{FieldSpec, FieldType, ProviderDescriptor};
+use crate::notifications::{config_mismatch, MentionWhen, NotificationSender};
+use crate::report::{format_duration, RunReport};
 use base64::{engine::general_purpose::STANDARD, Engine};
 use hmac::{Hmac, Mac};
-use log::{debug, error, info};
+use log::{error, info};
 use reqwest::Client;
 use serde::Deserialize;
-use serde_json::json;
+use serde_json::{json, Value};
 use sha2::Sha256;
 use std::error::Error;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+pub const PROVIDER: ProviderDescriptor = ProviderDescriptor {
+    name: "lark",
+    display_name: "Lark",
+    fields: &[
+        FieldSpec::new("webhook_url", "Webhook URL", FieldType::String).required(),
+        FieldSpec::new("sign_key", "Sign Key", FieldType::String).secret(),
+        FieldSpec::new("at", "At", FieldType::String),
+        FieldSpec::new(
+            "mention",
+            "Mentions (type:id[:when])",
+            FieldType::Records(&["@type", "@id", "@when"]),
+        ),
+        FieldSpec::new(
+            "message_type",
+            "Message Type (text/card)",
+            FieldType::String,
+        ),
+        FieldSpec::new("log_url", "Log URL", FieldType::String),
+    ],
+    default_config: || NotificationConfigType::Lark(LarkConfig::default()),
+    build: |config| match config {
+        NotificationConfigType::Lark(config) => Ok(Box::new(LarkNotifier::from_config(config)?)),
+        other => Err(config_mismatch(&PROVIDER, other)),
+    },
+};
+
+/// Characters of command output shown in a card, Lark rejects cards over 30KB.
+const CARD_OUTPUT_LIMIT: usize = 4000;
+/// Characters of the error shown in a card.
+const CARD_ERROR_LIMIT: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LarkMessageType {
+    Text,
+    Card,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MentionTarget {
+    UserId(String),
+    OpenId(String),
+    Email(String),
+    All,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LarkMention {
+    pub target: MentionTarget,
+    pub when: MentionWhen,
+}
+
+impl LarkMention {
+    pub fn from_config(config: &LarkMentionConfig) -> Result<Self, Box<dyn Error>> {
+        let id = config.id.clone();
+        let target = match config.id_type.as_deref().unwrap_or_default() {
+            "" | "user_id" => MentionTarget::UserId(id),
+            "open_id" => MentionTarget::OpenId(id),
+            "email" => MentionTarget::Email(id),
+            "all" => MentionTarget::All,
+            other => return Err(format!("Unknown Lark mention type '{}'", other).into()),
+        };
+        if target != MentionTarget::All && config.id.is_empty() {
+            return Err("Lark mention is missing an id".into());
+        }
+
+        let when = config.when.as_deref().unwrap_or_default();
+        let when = MentionWhen::parse(when)
+            .ok_or_else(|| format!("Unknown Lark mention condition '{}'", when))?;
+        Ok(Self { target, when })
+    }
+
+    fn text_tag(&self) -> String {
+        match &self.target {
+            MentionTarget::UserId(id) | MentionTarget::OpenId(id) => {
+                format!("<at user_id=\"{}\"></at>", id)
+            }
+            MentionTarget::Email(email) => format!("<at email=\"{}\"></at>", email),
+            MentionTarget::All => "<at user_id=\"all\"></at>".to_string(),
+        }
+    }
+
+    fn card_tag(&self) -> String {
+        match &self.target {
+            MentionTarget::UserId(id) | MentionTarget::OpenId(id) => format!("<at id={}></at>", id),
+            MentionTarget::Email(email) => format!("<at email={}></at>", email),
+            MentionTarget::All => "<at id=all></at>".to_string(),
+        }
+    }
+}
+
+/// `text` as a fenced code block, so markdown and `<at>` tags in it are
+/// shown as typed. A zero width space keeps fences in the text from closing
+/// the block.
+fn code_block(text: &str) -> String {
+    format!("```\n{}\n```", text.replace("```", "`\u{200b}``"))
+}
+
 pub struct LarkNotifier {
     webhook_url: String,
     sign_key: String,
-    at_user_id: Option<String>,
+    mentions: Vec<LarkMention>,
+    message_type: LarkMessageType,
+    log_url: Option<String>,
     client: Client,
 }
 
 impl LarkNotifier {
     pub fn new(webhook_url: String, sign_key: String, at_user_id: Option<String>) -> Self {
+        let mentions = at_user_id
+            .map(|id| LarkMention {
+                target: MentionTarget::UserId(id),
+                when: MentionWhen::Always,
+            })
+            .into_iter()
+            .collect();
+
         Self {
             webhook_url,
             sign_key,
-            at_user_id,
+            mentions,
+            message_type: LarkMessageType::Text,
+            log_url: None,
             client: Client::new(),
         }
     }
 
+    pub fn from_config(config: &LarkConfig) -> Result<Self, Box<dyn Error>> {
+        let message_type = match config.message_type.as_deref().unwrap_or_default() {
+            "" | "text" => LarkMessageType::Text,
+            "card" => LarkMessageType::Card,
+            other => return Err(format!("Unknown Lark message type '{}'", other).into()),
+        };
+
+        let mut notifier = Self::new(
+            config.webhook_url.clone(),
+            config.sign_key.clone(),
+            config.at.clone().filter(|at| !at.is_empty()),
+        );
+        notifier.message_type = message_type;
+        notifier.log_url = config.log_url.clone().filter(|url| !url.is_empty());
+        for mention in &config.mentions {
+            notifier.mentions.push(LarkMention::from_config(mention)?);
+        }
+        Ok(notifier)
+    }
+
     fn generate_sign(&self, timestamp: u64) -> String {
         let string_to_sign = format!("{}\n{}", timestamp, self.sign_key);
-        let mut mac = Hmac::<Sha256>::new_from_slice(string_to_sign.as_bytes())
+        let mac = Hmac::<Sha256>::new_from_slice(string_to_sign.as_bytes())
             .expect("HMAC can take key of any size");
         let result = mac.finalize();
         STANDARD.encode(result.into_bytes())
     }
 
     fn format_message(&self, message: &str) -> String {
-        if let Some(user_id) = &self.at_user_id {
-            format!("<at user_id=\"{}\"></at>\n{}", user_id, message)
-        } else {
+        self.format_message_for(message, None)
+    }
+
+    fn format_message_for(&self, message: &str, success: Option<bool>) -> String {
+        let tags = self.mention_tags(success, LarkMention::text_tag);
+        if tags.is_empty() {
             message.to_string()
+        } else {
+            format!("{}\n{}", tags, message)
         }
     }
-}
 
-#[derive(Deserialize, Debug)]
-struct LarkResponse {
-    code: i32,
-    msg: String,
-}
+    fn mention_tags(&self, success: Option<bool>, tag: fn(&LarkMention) -> String) -> String {
+        self.mentions
+            .iter()
+            .filter(|mention| mention.when.applies(success))
+            .map(tag)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
 
-#[async_trait::async_trait]
-impl NotificationSender for LarkNotifier {
-    async fn send(&self, message: &str) -> Result<(), Box<dyn Error>> {
-        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    fn build_card(&self, report: &RunReport) -> Value {
+        let (template, icon) = if report.success {
+            ("green", "✅")
+        } else {
+            ("red", "❌")
+        };
+        let exit_code = report
+            .exit_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let field = |name: &str, value: &str| {
+            json!({
+                "is_short": true,
+                "text": {"tag": "lark_md", "content": format!("**{}**\n{}", name, value)}
+            })
+        };
 
-        let sign = self.generate_sign(timestamp);
-        let formatted_message = self.format_message(message);
+        let mut elements = Vec::new();
+        let mentions = self.mention_tags(Some(report.success), LarkMention::card_tag);
+        if !mentions.is_empty() {
+            elements.push(json!({"tag": "markdown", "content": mentions}));
+        }
+        elements.push(json!({
+            "tag": "div",
+            "fields": [
+                field("Command", &report.command_line()),
+                field("Host", &report.host),
+                field("Duration", &format_duration(report.duration)),
+                field("Exit Code", &exit_code),
+            ]
+        }));
+        if report.error.is_some() {
+            elements.push(json!({
+                "tag": "markdown",
+                "content": format!("**Error**\n{}", code_block(report.error_head(CARD_ERROR_LIMIT)))
+            }));
+        }
 
-        let body = json!({
-            "timestamp": timestamp,
-            "sign": sign,
-            "msg_type": "text",
-            "content": {
-                "text": formatted_message
+        let output = report.output_tail(CARD_OUTPUT_LIMIT);
+        if !output.is_empty() {
+            elements.push(json!({
+                "tag": "collapsible_panel",
+                "expanded": false,
+                "header": {"title": {"tag": "plain_text", "content": "Output"}},
+                "elements": [{"tag": "markdown", "content": code_block(output)}]
+            }));
+        }
+
+        if let Some(log_url) = &self.log_url {
+            elements.push(json!({
+                "tag": "action",
+                "actions": [{
+                    "tag": "button",
+                    "text": {"tag": "plain_text", "content": "View log"},
+                    "type": "primary",
+                    "url": log_url
+                }]
+            }));
+        }
+
+        json!({
+            "msg_type": "interactive",
+            "card": {
+                "config": {"wide_screen_mode": true},
+                "header": {
+                    "template": template,
+                    "title": {
+                        "tag": "plain_text",
+                        "content": format!("{} {} {}", icon, report.command, report.status_text())
+                    }
+                },
+                "elements": elements
             }
-        });
+        })
+    }
+
+    /// Signs `payload` and posts it to the webhook.
+    async fn post(&self, mut payload: Value) -> Result<(), Box<dyn Error>> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        payload["timestamp"] = json!(timestamp);
+        payload["sign"] = json!(self.generate_sign(timestamp));
 
         let response = self
             .client
             .post(&self.webhook_url)
-            .json(&body)
+            .json(&payload)
             .send()
             .await?;
 
@@ -100,6 +304,39 @@ impl NotificationSender for LarkNotifier {
     }
 }
 
+#[derive(Deserialize, Debug)]
+struct LarkResponse {
+    code: i32,
+    msg: String,
+}
+
+// Custom bot webhooks neither return the id of a posted message nor accept
+// replies, so runs use the default lifecycle: an optional plain start
+// announcement followed by the report.
+#[async_trait::async_trait]
+impl NotificationSender for LarkNotifier {
+    async fn send(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.post(json!({
+            "msg_type": "text",
+            "content": {
+                "text": self.format_message(message)
+            }
+        }))
+        .await
+    }
+
+    async fn send_report(&self, report: &RunReport) -> Result<(), Box<dyn Error>> {
+        match self.message_type {
+            LarkMessageType::Text => {
+                let text = self.format_message_for(&report.to_message(), Some(report.success));
+                self.post(json!({"msg_type": "text", "content": {"text": text}}))
+                    .await
+            }
+            LarkMessageType::Card => self.post(self.build_card(report)).await,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -221,4 +458,180 @@ mod tests {
         let formatted = notifier_without_at.format_message("Hello world");
         assert_eq!(formatted, "Hello world");
     }
+
+    fn card_notifier(log_url: Option<&str>) -> LarkNotifier {
+        LarkNotifier::from_config(&LarkConfig {
+            webhook_url: "dummy_url".to_string(),
+            sign_key: "dummy_key".to_string(),
+            at: None,
+            message_type: Some("card".to_string()),
+            log_url: log_url.map(str::to_string),
+            mentions: Vec::new(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_build_card_success() {
+        let notifier = card_notifier(Some("https://ci.example.com/runs/1"));
+        let mut report = RunReport::new("make", vec!["release".to_string()]);
+        report.host = "build-1".to_string();
+        report.duration = std::time::Duration::from_secs(125);
+        report.exit_code = Some(0);
+        report.output = Some("done".to_string());
+
+        let card = notifier.build_card(&report);
+        assert_eq!(card["msg_type"], "interactive");
+        assert_eq!(card["card"]["header"]["template"], "green");
+        assert_eq!(
+            card["card"]["header"]["title"]["content"],
+            "✅ make succeeded"
+        );
+
+        let elements = card["card"]["elements"].as_array().unwrap();
+        let fields = elements[0]["fields"].as_array().unwrap();
+        let contents: Vec<&str> = fields
+            .iter()
+            .map(|f| f["text"]["content"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            contents,
+            vec![
+                "**Command**\nmake release",
+                "**Host**\nbuild-1",
+                "**Duration**\n2m 5s",
+                "**Exit Code**\n0"
+            ]
+        );
+
+        assert_eq!(elements[1]["tag"], "collapsible_panel");
+        assert_eq!(elements[1]["expanded"], false);
+        assert_eq!(elements[1]["elements"][0]["content"], "```\ndone\n```");
+
+        assert_eq!(elements[2]["tag"], "action");
+        assert_eq!(
+            elements[2]["actions"][0]["url"],
+            "https://ci.example.com/runs/1"
+        );
+    }
+
+    #[test]
+    fn test_build_card_failure() {
+        let notifier = card_notifier(None);
+        let mut report = RunReport::new("make", vec![]);
+        report.success = false;
+        report.error = Some("Command failed: oops".to_string());
+
+        let card = notifier.build_card(&report);
+        assert_eq!(card["card"]["header"]["template"], "red");
+        assert_eq!(card["card"]["header"]["title"]["content"], "❌ make failed");
+
+        let elements = card["card"]["elements"].as_array().unwrap();
+        assert_eq!(
+            elements[0]["fields"][3]["text"]["content"],
+            "**Exit Code**\n-"
+        );
+        assert_eq!(
+            elements[1]["content"],
+            "**Error**\n```\nCommand failed: oops\n```"
+        );
+        // No output panel and no log button
+        assert_eq!(elements.len(), 2);
+    }
+
+    #[test]
+    fn test_build_card_long_error() {
+        let notifier = card_notifier(None);
+        let mut report = RunReport::new("make", vec![]);
+        report.success = false;
+        report.error = Some(format!("first line\n{}", "错误\n".repeat(20000)));
+        report.output = Some("输出\n".repeat(20000));
+
+        let card = notifier.build_card(&report);
+        let error = card["card"]["elements"][1]["content"].as_str().unwrap();
+        assert!(error.starts_with("**Error**\n```\nfirst line\n"));
+        assert!(error.chars().count() <= CARD_ERROR_LIMIT + "**Error**\n```\n\n```".len());
+        assert!(card.to_string().len() < 30 * 1024);
+
+        // Mentions and markdown in stderr are shown as typed
+        report.error = Some("<at id=all></at> **bold** ```".to_string());
+        let card = notifier.build_card(&report);
+        assert_eq!(
+            card["card"]["elements"][1]["content"],
+            "**Error**\n```\n<at id=all></at> **bold** `\u{200b}``\n```"
+        );
+    }
+
+    #[test]
+    fn test_unknown_message_type() {
+        let config = LarkConfig {
+            message_type: Some("post".to_string()),
+            ..Default::default()
+        };
+        assert!(LarkNotifier::from_config(&config).is_err());
+    }
+
+    fn mention(id_type: &str, id: &str, when: &str) -> LarkMentionConfig {
+        LarkMentionConfig {
+            id_type: Some(id_type.to_string()),
+            id: id.to_string(),
+            when: Some(when.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_conditional_mentions() {
+        let notifier = LarkNotifier::from_config(&LarkConfig {
+            webhook_url: "dummy_url".to_string(),
+            at: Some("u_legacy".to_string()),
+            mentions: vec![
+                mention("open_id", "ou_1", "always"),
+                mention("email", "oncall@example.com", "failure"),
+                mention("all", "", "failure"),
+                mention("user_id", "u_2", "success"),
+            ],
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            notifier.format_message("hi"),
+            "<at user_id=\"u_legacy\"></at> <at user_id=\"ou_1\"></at>\nhi"
+        );
+        assert_eq!(
+            notifier.format_message_for("hi", Some(true)),
+            "<at user_id=\"u_legacy\"></at> <at user_id=\"ou_1\"></at> <at user_id=\"u_2\"></at>\nhi"
+        );
+        assert_eq!(
+            notifier.format_message_for("hi", Some(false)),
+            "<at user_id=\"u_legacy\"></at> <at user_id=\"ou_1\"></at> \
+             <at email=\"oncall@example.com\"></at> <at user_id=\"all\"></at>\nhi"
+        );
+
+        let mut report = RunReport::new("make", vec![]);
+        report.success = false;
+        let card = notifier.build_card(&report);
+        assert_eq!(
+            card["card"]["elements"][0]["content"],
+            "<at id=u_legacy></at> <at id=ou_1></at> <at email=oncall@example.com></at> <at id=all></at>"
+        );
+    }
+
+    #[test]
+    fn test_invalid_mentions() {
+        for invalid in [
+            mention("phone", "123", "always"),
+            mention("open_id", "", "always"),
+            mention("open_id", "ou_1", "sometimes"),
+        ] {
+            assert!(LarkMention::from_config(&invalid).is_err());
+        }
+        assert_eq!(
+            LarkMention::from_config(&mention("all", "", "")).unwrap(),
+            LarkMention {
+                target: MentionTarget::All,
+                when: MentionWhen::Always
+            }
+        );
+    }
 }
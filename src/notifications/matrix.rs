@@ -0,0 +1,400 @@
+use crate::config::{MatrixConfig, NotificationConfigType};
+use crate::notifications::registry::{FieldSpec, FieldType, ProviderDescriptor};
+use crate::notifications::{config_mismatch, NotificationSender};
+use crate::report::{format_duration, RunContext, RunReport};
+use log::{error, info};
+use reqwest::{Client, RequestBuilder, Url};
+use serde_json::{json, Value};
+use std::error::Error;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+pub const PROVIDER: ProviderDescriptor = ProviderDescriptor {
+    name: "matrix",
+    display_name: "Matrix",
+    fields: &[
+        FieldSpec::new("homeserver_url", "Homeserver URL", FieldType::String).required(),
+        FieldSpec::new("access_token", "Access Token", FieldType::String)
+            .required()
+            .secret(),
+        FieldSpec::new("room_id", "Room ID", FieldType::String).required(),
+        FieldSpec::new("thread", "Reply In Thread", FieldType::Bool),
+        FieldSpec::new("upload_log", "Upload Log", FieldType::Bool),
+    ],
+    default_config: || NotificationConfigType::Matrix(MatrixConfig::default()),
+    build: |config| match config {
+        NotificationConfigType::Matrix(config) => Ok(Box::new(MatrixNotifier::new(config))),
+        other => Err(config_mismatch(&PROVIDER, other)),
+    },
+};
+
+/// Characters of command output shown in a report.
+const OUTPUT_LIMIT: usize = 4000;
+/// Characters of the error shown in a report. Body and formatted body
+/// both carry it, and Matrix rejects events over 65 KB.
+const ERROR_LIMIT: usize = 4000;
+
+pub struct MatrixNotifier {
+    homeserver_url: String,
+    access_token: String,
+    room_id: String,
+    thread: bool,
+    upload_log: bool,
+    /// Event id of the start message results are posted under.
+    start_event: Mutex<Option<String>>,
+    /// Makes transaction ids unique within the process.
+    transaction: AtomicU64,
+    client: Client,
+}
+
+impl MatrixNotifier {
+    pub fn new(config: &MatrixConfig) -> Self {
+        Self {
+            homeserver_url: config.homeserver_url.trim_end_matches('/').to_string(),
+            access_token: config.access_token.clone(),
+            room_id: config.room_id.clone(),
+            thread: config.thread.unwrap_or(false),
+            upload_log: config.upload_log.unwrap_or(false),
+            start_event: Mutex::new(None),
+            transaction: AtomicU64::new(0),
+            client: Client::new(),
+        }
+    }
+
+    /// `path` below the homeserver URL, each segment percent-encoded.
+    fn url(&self, path: &[&str]) -> Result<Url, Box<dyn Error>> {
+        let mut url = Url::parse(&self.homeserver_url)?;
+        url.path_segments_mut()
+            .map_err(|_| format!("Invalid Matrix homeserver URL '{}'", self.homeserver_url))?
+            .pop_if_empty()
+            .extend(path);
+        Ok(url)
+    }
+
+    fn transaction_id(&self) -> String {
+        format!(
+            "notifyme-{}-{}-{}",
+            std::process::id(),
+            chrono::Utc::now().timestamp_millis(),
+            self.transaction.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+
+    fn format_report(report: &RunReport) -> (String, String) {
+        let icon = if report.success { "✅" } else { "❌" };
+        let title = format!("{} {} {}", icon, report.command, report.status_text());
+        let exit_code = report
+            .exit_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let duration = format!(
+            "{}, exit code {}",
+            format_duration(report.duration),
+            exit_code
+        );
+
+        let mut body = format!(
+            "{}\nCommand: {}\nHost: {}\nDuration: {}",
+            title,
+            report.command_line(),
+            report.host,
+            duration
+        );
+        let mut html = format!(
+            "<h4>{}</h4>\n<p><b>Command:</b> <code>{}</code><br>\n<b>Host:</b> {}<br>\n<b>Duration:</b> {}</p>",
+            escape_html(&title),
+            escape_html(&report.command_line()),
+            escape_html(&report.host),
+            duration
+        );
+        if report.error.is_some() {
+            let error = report.error_head(ERROR_LIMIT);
+            body.push_str(&format!("\nError: {}", error));
+            html.push_str(&format!("\n<p><b>Error:</b> {}</p>", escape_html(error)));
+        }
+
+        let output = report.output_tail(OUTPUT_LIMIT).trim_end();
+        if !output.is_empty() {
+            body.push_str(&format!("\n\n{}", output));
+            html.push_str(&format!(
+                "\n<pre><code>{}</code></pre>",
+                escape_html(output)
+            ));
+        }
+        (body, html)
+    }
+
+    /// `content` relating to the start message, as a thread reply or a
+    /// plain reply.
+    fn relate(&self, mut content: Value, event_id: Option<&str>) -> Value {
+        let Some(event_id) = event_id else {
+            return content;
+        };
+        content["m.relates_to"] = if self.thread {
+            // Clients without thread support show it as a reply
+            json!({
+                "rel_type": "m.thread",
+                "event_id": event_id,
+                "is_falling_back": true,
+                "m.in_reply_to": {"event_id": event_id}
+            })
+        } else {
+            json!({"m.in_reply_to": {"event_id": event_id}})
+        };
+        content
+    }
+
+    async fn execute(&self, request: RequestBuilder) -> Result<Value, Box<dyn Error>> {
+        let response = request.bearer_auth(&self.access_token).send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            error!("Matrix request failed: {} - {}", status, text);
+            return Err(format!("Matrix request failed: {} - {}", status, text).into());
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Sends an `m.room.message` event, returning its id.
+    async fn send_event(&self, content: Value) -> Result<String, Box<dyn Error>> {
+        let txn_id = self.transaction_id();
+        let url = self.url(&[
+            "_matrix",
+            "client",
+            "v3",
+            "rooms",
+            &self.room_id,
+            "send",
+            "m.room.message",
+            &txn_id,
+        ])?;
+        let body = self.execute(self.client.put(url).json(&content)).await?;
+        let event_id = body["event_id"]
+            .as_str()
+            .ok_or("Matrix did not return an event id")?
+            .to_string();
+        info!("Matrix event {} sent", event_id);
+        Ok(event_id)
+    }
+
+    /// Uploads `content` to the media repository, returning its `mxc://` URI.
+    async fn upload(&self, file_name: &str, content: String) -> Result<String, Box<dyn Error>> {
+        let mut url = self.url(&["_matrix", "media", "v3", "upload"])?;
+        url.query_pairs_mut().append_pair("filename", file_name);
+        let request = self
+            .client
+            .post(url)
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .body(content);
+        let body = self.execute(request).await?;
+        Ok(body["content_uri"]
+            .as_str()
+            .ok_or("Matrix did not return a content URI")?
+            .to_string())
+    }
+
+    /// Sends the report, and the log as an `m.file` when enabled, related to
+    /// `event_id`.
+    async fn deliver_report(
+        &self,
+        report: &RunReport,
+        event_id: Option<&str>,
+    ) -> Result<(), Box<dyn Error>> {
+        let (body, html) = Self::format_report(report);
+        let content = json!({
+            "msgtype": "m.text",
+            "body": body,
+            "format": "org.matrix.custom.html",
+            "formatted_body": html
+        });
+        self.send_event(self.relate(content, event_id)).await?;
+
+        let output = report.output.as_deref().unwrap_or_default();
+        if !self.upload_log || output.is_empty() {
+            return Ok(());
+        }
+        let file_name = report.log_file_name();
+        let uri = self.upload(&file_name, output.to_string()).await?;
+        let content = json!({
+            "msgtype": "m.file",
+            "body": file_name,
+            "filename": file_name,
+            "url": uri,
+            "info": {"mimetype": "text/plain", "size": output.len()}
+        });
+        self.send_event(self.relate(content, event_id)).await?;
+        Ok(())
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[async_trait::async_trait]
+impl NotificationSender for MatrixNotifier {
+    async fn send(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.send_event(json!({"msgtype": "m.text", "body": message}))
+            .await
+            .map(|_| ())
+    }
+
+    async fn send_report(&self, report: &RunReport) -> Result<(), Box<dyn Error>> {
+        self.deliver_report(report, None).await
+    }
+
+    async fn on_start(&self, context: &RunContext) -> Result<(), Box<dyn Error>> {
+        if !context.notify_on_start {
+            return Ok(());
+        }
+        let event_id = self
+            .send_event(json!({"msgtype": "m.text", "body": context.start_message()}))
+            .await?;
+        *self.start_event.lock().unwrap() = Some(event_id);
+        Ok(())
+    }
+
+    async fn on_finish(
+        &self,
+        _context: &RunContext,
+        report: &RunReport,
+    ) -> Result<(), Box<dyn Error>> {
+        let start_event = self.start_event.lock().unwrap().take();
+        self.deliver_report(report, start_event.as_deref()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::test_server::{MockResponse, MockServer};
+
+    fn config(homeserver_url: &str, thread: bool) -> MatrixConfig {
+        MatrixConfig {
+            homeserver_url: homeserver_url.to_string(),
+            access_token: "syt_token".to_string(),
+            room_id: "!room:example.org".to_string(),
+            thread: Some(thread),
+            upload_log: Some(true),
+        }
+    }
+
+    fn report(success: bool) -> RunReport {
+        let mut report = RunReport::new("make", vec!["<all>".to_string()]);
+        report.host = "build-1".to_string();
+        report.duration = std::time::Duration::from_secs(3);
+        report.exit_code = Some(if success { 0 } else { 2 });
+        report.success = success;
+        report.output = Some("a < b\n".to_string());
+        report
+    }
+
+    #[test]
+    fn test_format_report() {
+        let (body, html) = MatrixNotifier::format_report(&report(true));
+        assert_eq!(
+            body,
+            "✅ make succeeded\nCommand: make <all>\nHost: build-1\nDuration: 3s, exit code 0\n\na < b"
+        );
+        assert_eq!(
+            html,
+            "<h4>✅ make succeeded</h4>\n<p><b>Command:</b> <code>make &lt;all&gt;</code><br>\n<b>Host:</b> build-1<br>\n<b>Duration:</b> 3s, exit code 0</p>\n<pre><code>a &lt; b</code></pre>"
+        );
+
+        let mut failed = report(false);
+        failed.error = Some("<".repeat(100_000));
+        let (body, html) = MatrixNotifier::format_report(&failed);
+        assert!(body.contains(&format!("\nError: {}\n", "<".repeat(ERROR_LIMIT))));
+        assert!(body.len() + html.len() < 65 * 1024);
+    }
+
+    #[tokio::test]
+    async fn test_thread_under_start_message() {
+        let server = MockServer::start(vec![MockResponse::json(
+            json!({"event_id": "$start", "content_uri": "mxc://example.org/log"}),
+        )])
+        .await;
+        let notifier = MatrixNotifier::new(&config(&format!("{}/", server.url), true));
+
+        let report = report(false);
+        let mut context = RunContext::new("default", &report);
+        context.notify_on_start = true;
+        notifier.on_start(&context).await.unwrap();
+        notifier.on_finish(&context, &report).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 4);
+        assert_eq!(requests[0].method, "PUT");
+        assert!(requests[0].path.starts_with(
+            "/_matrix/client/v3/rooms/!room:example.org/send/m.room.message/notifyme-"
+        ));
+        assert_eq!(
+            requests[0].header("authorization"),
+            Some("Bearer syt_token")
+        );
+        assert_ne!(requests[0].path, requests[1].path);
+
+        let result = requests[1].json();
+        assert_eq!(result["format"], "org.matrix.custom.html");
+        assert_eq!(
+            result["m.relates_to"],
+            json!({
+                "rel_type": "m.thread",
+                "event_id": "$start",
+                "is_falling_back": true,
+                "m.in_reply_to": {"event_id": "$start"}
+            })
+        );
+
+        assert_eq!(requests[2].method, "POST");
+        assert_eq!(
+            requests[2].path,
+            format!(
+                "/_matrix/media/v3/upload?filename={}",
+                report.log_file_name()
+            )
+        );
+        assert_eq!(requests[2].body_text(), "a < b\n");
+
+        let file = requests[3].json();
+        assert_eq!(file["msgtype"], "m.file");
+        assert_eq!(file["url"], "mxc://example.org/log");
+        assert_eq!(file["m.relates_to"]["rel_type"], "m.thread");
+    }
+
+    #[tokio::test]
+    async fn test_reply_and_errors() {
+        let server =
+            MockServer::start(vec![MockResponse::json(json!({"event_id": "$start"}))]).await;
+        let mut config = config(&server.url, false);
+        config.upload_log = None;
+        let notifier = MatrixNotifier::new(&config);
+
+        let report = report(true);
+        let mut context = RunContext::new("default", &report);
+        context.notify_on_start = true;
+        notifier.on_start(&context).await.unwrap();
+        notifier.on_finish(&context, &report).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[1].json()["m.relates_to"],
+            json!({"m.in_reply_to": {"event_id": "$start"}})
+        );
+
+        let server = MockServer::start(vec![MockResponse::new(
+            403,
+            r#"{"errcode":"M_FORBIDDEN","error":"User not in room"}"#,
+        )])
+        .await;
+        let err = MatrixNotifier::new(&self::config(&server.url, false))
+            .send("hello")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("M_FORBIDDEN"));
+    }
+}
@@ -0,0 +1,340 @@
+use crate::config::{MetricsConfig, NotificationConfigType};
+use crate::notifications::registry::{FieldSpec, FieldType, ProviderDescriptor};
+use crate::notifications::{config_mismatch, NotificationSender};
+use crate::report::{RunContext, RunReport};
+use base64::{engine::general_purpose::URL_SAFE, Engine};
+use log::{debug, error, info};
+use reqwest::Client;
+use std::error::Error;
+use std::path::Path;
+use tokio::net::UdpSocket;
+
+pub const DEFAULT_STATSD_ADDRESS: &str = "127.0.0.1:8125";
+
+pub const PROVIDER: ProviderDescriptor = ProviderDescriptor {
+    name: "metrics",
+    display_name: "Metrics (Pushgateway/StatsD)",
+    fields: &[
+        FieldSpec::new("sink", "Sink (pushgateway/statsd)", FieldType::String)
+            .default_value("pushgateway"),
+        FieldSpec::new("job", "Job Name", FieldType::String),
+        FieldSpec::new("pushgateway_url", "Pushgateway URL", FieldType::String),
+        FieldSpec::new("statsd_address", "StatsD Address", FieldType::String)
+            .default_value(DEFAULT_STATSD_ADDRESS),
+    ],
+    default_config: || NotificationConfigType::Metrics(MetricsConfig::default()),
+    build: |config| match config {
+        NotificationConfigType::Metrics(config) => {
+            Ok(Box::new(MetricsNotifier::from_config(config)?))
+        }
+        other => Err(config_mismatch(&PROVIDER, other)),
+    },
+};
+
+/// Reported as the exit code when the command never exited on its own.
+const NO_EXIT_CODE: i32 = -1;
+
+enum Sink {
+    Pushgateway { url: String, client: Client },
+    Statsd { address: String },
+}
+
+pub struct MetricsNotifier {
+    sink: Sink,
+    job: Option<String>,
+}
+
+/// One run as metric samples, labelled by job, host and config set.
+struct Sample {
+    labels: Vec<(&'static str, String)>,
+    duration_seconds: f64,
+    exit_code: i32,
+    /// Unix time of the run when it succeeded.
+    last_success: Option<i64>,
+}
+
+impl MetricsNotifier {
+    pub fn from_config(config: &MetricsConfig) -> Result<Self, Box<dyn Error>> {
+        let non_empty = |value: &Option<String>| value.clone().filter(|v| !v.is_empty());
+        let sink = match non_empty(&config.sink).as_deref().unwrap_or("pushgateway") {
+            "pushgateway" => Sink::Pushgateway {
+                url: non_empty(&config.pushgateway_url)
+                    .ok_or("The pushgateway sink needs a pushgateway_url")?
+                    .trim_end_matches('/')
+                    .to_string(),
+                client: Client::new(),
+            },
+            "statsd" => Sink::Statsd {
+                address: non_empty(&config.statsd_address)
+                    .unwrap_or_else(|| DEFAULT_STATSD_ADDRESS.to_string()),
+            },
+            other => {
+                return Err(format!(
+                    "Unknown metrics sink '{}', expected pushgateway or statsd",
+                    other
+                )
+                .into())
+            }
+        };
+
+        Ok(Self {
+            sink,
+            job: non_empty(&config.job),
+        })
+    }
+
+    fn sample(&self, config_set: Option<&str>, report: &RunReport) -> Sample {
+        let job = self.job.clone().unwrap_or_else(|| {
+            Path::new(&report.command)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| report.command.clone())
+        });
+        let mut labels = vec![("job", job), ("host", report.host.clone())];
+        if let Some(config_set) = config_set {
+            labels.push(("config_set", config_set.to_string()));
+        }
+
+        Sample {
+            labels,
+            duration_seconds: report.duration.as_secs_f64(),
+            exit_code: report.exit_code.unwrap_or(NO_EXIT_CODE),
+            last_success: report.success.then(|| report.started_at.timestamp()),
+        }
+    }
+
+    async fn emit(&self, sample: &Sample) -> Result<(), Box<dyn Error>> {
+        match &self.sink {
+            Sink::Pushgateway { url, client } => push(client, url, sample).await,
+            Sink::Statsd { address } => emit_statsd(address, sample).await,
+        }
+    }
+}
+
+/// Grouping key path, with `@base64` values unless the plain value is safe
+/// as a path segment. Empty values are written as `=`, the Pushgateway
+/// cannot route an empty segment.
+fn grouping_path(labels: &[(&str, String)]) -> String {
+    let mut path = String::from("/metrics");
+    for (name, value) in labels {
+        let plain = !value.is_empty()
+            && value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+        if plain {
+            path.push_str(&format!("/{}/{}", name, value));
+        } else if value.is_empty() {
+            path.push_str(&format!("/{}@base64/=", name));
+        } else {
+            path.push_str(&format!("/{}@base64/{}", name, URL_SAFE.encode(value)));
+        }
+    }
+    path
+}
+
+/// Text exposition format, the grouping key provides the labels.
+fn exposition(sample: &Sample) -> String {
+    let mut body = String::new();
+    let mut gauge = |name: &str, help: &str, value: String| {
+        body.push_str(&format!(
+            "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}\n"
+        ));
+    };
+    gauge(
+        "notifyme_job_duration_seconds",
+        "Duration of the last run.",
+        sample.duration_seconds.to_string(),
+    );
+    gauge(
+        "notifyme_job_exit_code",
+        "Exit code of the last run, -1 when it did not exit.",
+        sample.exit_code.to_string(),
+    );
+    if let Some(timestamp) = sample.last_success {
+        gauge(
+            "notifyme_job_last_success_timestamp",
+            "Unix time of the last successful run.",
+            timestamp.to_string(),
+        );
+    }
+    body
+}
+
+/// POST only replaces the pushed metric names, so a failed run keeps the
+/// last success timestamp of the group.
+async fn push(client: &Client, url: &str, sample: &Sample) -> Result<(), Box<dyn Error>> {
+    let response = client
+        .post(format!("{}{}", url, grouping_path(&sample.labels)))
+        .header("Content-Type", "text/plain; version=0.0.4")
+        .body(exposition(sample))
+        .send()
+        .await?;
+
+    let status = response.status();
+    if status.is_success() {
+        info!("Job metrics pushed to the Pushgateway");
+        return Ok(());
+    }
+    let text = response.text().await?;
+    error!("Failed to push metrics: {} - {}", status, text);
+    Err(format!("Failed to push metrics: {} - {}", status, text).into())
+}
+
+/// Gauges with DogStatsD tags, which the statsd_exporter, Telegraf and the
+/// Datadog agent all map to labels.
+fn statsd_lines(sample: &Sample) -> String {
+    let tags = sample
+        .labels
+        .iter()
+        .map(|(name, value)| format!("{}:{}", name, value.replace([',', '|', '#', '\n'], "_")))
+        .collect::<Vec<_>>()
+        .join(",");
+    let mut lines = vec![
+        format!(
+            "notifyme_job_duration_seconds:{}|g|#{}",
+            sample.duration_seconds, tags
+        ),
+        format!("notifyme_job_exit_code:{}|g|#{}", sample.exit_code, tags),
+    ];
+    if let Some(timestamp) = sample.last_success {
+        lines.push(format!(
+            "notifyme_job_last_success_timestamp:{}|g|#{}",
+            timestamp, tags
+        ));
+    }
+    lines.join("\n")
+}
+
+async fn emit_statsd(address: &str, sample: &Sample) -> Result<(), Box<dyn Error>> {
+    let target = tokio::net::lookup_host(address)
+        .await?
+        .next()
+        .ok_or_else(|| format!("StatsD address '{}' did not resolve", address))?;
+    let local = if target.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket
+        .send_to(statsd_lines(sample).as_bytes(), target)
+        .await?;
+    info!("Job metrics sent to StatsD at {}", target);
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl NotificationSender for MetricsNotifier {
+    /// Metrics have no place for free-form messages.
+    async fn send(&self, _message: &str) -> Result<(), Box<dyn Error>> {
+        debug!("Metrics sink ignores plain messages");
+        Ok(())
+    }
+
+    /// Without a run context the samples carry no `config_set` label,
+    /// `notify` and `run` go through `on_finish` instead.
+    async fn send_report(&self, report: &RunReport) -> Result<(), Box<dyn Error>> {
+        self.emit(&self.sample(None, report)).await
+    }
+
+    async fn on_start(&self, _context: &RunContext) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    async fn on_finish(
+        &self,
+        context: &RunContext,
+        report: &RunReport,
+    ) -> Result<(), Box<dyn Error>> {
+        self.emit(&self.sample(Some(&context.config_set), report))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::test_server::{MockResponse, MockServer};
+    use std::time::Duration;
+
+    fn report(success: bool) -> RunReport {
+        let mut report = RunReport::new("/usr/local/bin/backup.sh", vec!["--full".to_string()]);
+        report.host = "db-1".to_string();
+        report.duration = Duration::from_millis(12500);
+        report.exit_code = Some(if success { 0 } else { 2 });
+        report.success = success;
+        report
+    }
+
+    #[tokio::test]
+    async fn test_pushgateway() {
+        let server = MockServer::start(vec![MockResponse::new(200, "")]).await;
+        let notifier = MetricsNotifier::from_config(&MetricsConfig {
+            pushgateway_url: Some(format!("{}/", server.url)),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let success = report(true);
+        let context = RunContext::new("nightly", &success);
+        notifier.on_finish(&context, &success).await.unwrap();
+        notifier.on_finish(&context, &report(false)).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(
+            requests[0].path,
+            "/metrics/job/backup.sh/host/db-1/config_set/nightly"
+        );
+        let body = requests[0].body_text();
+        assert!(body.contains("# TYPE notifyme_job_duration_seconds gauge\n"));
+        assert!(body.contains("\nnotifyme_job_duration_seconds 12.5\n"));
+        assert!(body.contains("\nnotifyme_job_exit_code 0\n"));
+        assert!(body.contains(&format!(
+            "\nnotifyme_job_last_success_timestamp {}\n",
+            success.started_at.timestamp()
+        )));
+
+        let body = requests[1].body_text();
+        assert!(body.contains("\nnotifyme_job_exit_code 2\n"));
+        assert!(!body.contains("notifyme_job_last_success_timestamp"));
+    }
+
+    #[test]
+    fn test_grouping_path() {
+        let labels = vec![
+            ("job", "nightly/backup".to_string()),
+            ("host", "db-1".to_string()),
+            ("config_set", String::new()),
+        ];
+        assert_eq!(
+            grouping_path(&labels),
+            "/metrics/job@base64/bmlnaHRseS9iYWNrdXA=/host/db-1/config_set@base64/="
+        );
+        assert!(MetricsNotifier::from_config(&MetricsConfig::default()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_statsd() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let notifier = MetricsNotifier::from_config(&MetricsConfig {
+            sink: Some("statsd".to_string()),
+            job: Some("backup".to_string()),
+            statsd_address: Some(receiver.local_addr().unwrap().to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let failure = report(false);
+        let context = RunContext::new("nightly", &failure);
+        notifier.on_finish(&context, &failure).await.unwrap();
+
+        let mut buffer = [0u8; 1024];
+        let (len, _) = receiver.recv_from(&mut buffer).await.unwrap();
+        assert_eq!(
+            std::str::from_utf8(&buffer[..len]).unwrap(),
+            "notifyme_job_duration_seconds:12.5|g|#job:backup,host:db-1,config_set:nightly\n\
+             notifyme_job_exit_code:2|g|#job:backup,host:db-1,config_set:nightly"
+        );
+    }
+}
@@ -1,41 +1,319 @@
-use crate::config::{
-    CommandConfig, EmailConfig, HttpConfig, NotificationConfigType, PhoneCallConfig,
-    TelegramConfig, TwilioSmsConfig,
-};
-use std::collections::HashMap;
+use crate::config::NotificationConfigType;
+use crate::error::NotificationError;
+use crate::report::{RunContext, RunProgress, RunReport};
+use registry::ProviderDescriptor;
 
+#[cfg(feature = "bark")]
+pub mod bark;
+#[cfg(feature = "desktop")]
+pub mod desktop;
+#[cfg(feature = "dingtalk")]
+pub mod dingtalk;
+#[cfg(feature = "discord")]
+pub mod discord;
+#[cfg(feature = "email")]
 pub mod email;
+#[cfg(feature = "gotify")]
+pub mod gotify;
+#[cfg(feature = "healthchecks")]
+pub mod healthchecks;
+#[cfg(feature = "http")]
 pub mod http_request;
+#[cfg(feature = "lark")]
 pub mod lark;
+#[cfg(feature = "matrix")]
+pub mod matrix;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "ntfy")]
+pub mod ntfy;
+#[cfg(feature = "opsgenie")]
+pub mod opsgenie;
+#[cfg(feature = "pagerduty")]
+pub mod pagerduty;
 pub mod phone_call_twilio;
+#[cfg(feature = "plugin")]
+pub mod plugin;
+#[cfg(feature = "pushover")]
+pub mod pushover;
+pub mod registry;
+#[cfg(feature = "slack")]
+pub mod slack;
 pub mod sms_twilio;
+#[cfg(feature = "teams")]
+pub mod teams;
+#[cfg(feature = "telegram")]
 pub mod telegram;
+#[cfg(feature = "terminal")]
+pub mod terminal;
+#[cfg(feature = "wecom")]
+pub mod wecom;
+// Unused when every provider relying on it is compiled out
+#[cfg(test)]
+#[allow(dead_code)]
+pub(crate) mod test_server;
 
 #[async_trait::async_trait]
 pub trait NotificationSender: Send + Sync {
     async fn send(&self, message: &str) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Sends a structured run report. Senders without a richer format
+    /// fall back to the plain text rendering.
+    async fn send_report(&self, report: &RunReport) -> Result<(), Box<dyn std::error::Error>> {
+        self.send(&report.to_message()).await
+    }
+
+    /// Called once before the command starts. Announces the run through
+    /// `send` when the config set asks for it, senders that keep a live
+    /// status message or thread their replies post it here.
+    async fn on_start(&self, context: &RunContext) -> Result<(), Box<dyn std::error::Error>> {
+        if context.notify_on_start {
+            self.send(&context.start_message()).await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Called periodically while the command runs. Senders decide for
+    /// themselves how often they actually publish an update.
+    async fn on_progress(
+        &self,
+        _context: &RunContext,
+        _progress: &RunProgress,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    /// Called once the command finished, sends the report by default.
+    async fn on_finish(
+        &self,
+        _context: &RunContext,
+        report: &RunReport,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.send_report(report).await
+    }
+}
+
+/// Run outcome a mention is restricted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MentionWhen {
+    Always,
+    Success,
+    Failure,
+}
+
+impl MentionWhen {
+    /// Parses a `when` attribute, empty meaning `always`.
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "" | "always" => Some(MentionWhen::Always),
+            "success" => Some(MentionWhen::Success),
+            "failure" => Some(MentionWhen::Failure),
+            _ => None,
+        }
+    }
+
+    /// Whether to mention for a run with the given outcome, `None` being a
+    /// plain message outside of any run.
+    pub fn applies(&self, success: Option<bool>) -> bool {
+        match (self, success) {
+            (MentionWhen::Always, _) => true,
+            (MentionWhen::Success, Some(success)) => success,
+            (MentionWhen::Failure, Some(success)) => !success,
+            (_, None) => false,
+        }
+    }
+}
+
+/// Providers compiled into this build.
+pub const PROVIDERS: &[&ProviderDescriptor] = &[
+    #[cfg(feature = "telegram")]
+    &telegram::PROVIDER,
+    #[cfg(feature = "lark")]
+    &lark::PROVIDER,
+    #[cfg(feature = "dingtalk")]
+    &dingtalk::PROVIDER,
+    #[cfg(feature = "wecom")]
+    &wecom::PROVIDER,
+    #[cfg(feature = "teams")]
+    &teams::PROVIDER,
+    #[cfg(feature = "slack")]
+    &slack::PROVIDER,
+    #[cfg(feature = "discord")]
+    &discord::PROVIDER,
+    #[cfg(feature = "matrix")]
+    &matrix::PROVIDER,
+    #[cfg(feature = "ntfy")]
+    &ntfy::PROVIDER,
+    #[cfg(feature = "gotify")]
+    &gotify::PROVIDER,
+    #[cfg(feature = "pushover")]
+    &pushover::PROVIDER,
+    #[cfg(feature = "bark")]
+    &bark::PROVIDER,
+    #[cfg(feature = "desktop")]
+    &desktop::PROVIDER,
+    #[cfg(feature = "terminal")]
+    &terminal::PROVIDER,
+    #[cfg(feature = "email")]
+    &email::PROVIDER,
+    #[cfg(feature = "http")]
+    &http_request::PROVIDER,
+    #[cfg(feature = "pagerduty")]
+    &pagerduty::PROVIDER,
+    #[cfg(feature = "opsgenie")]
+    &opsgenie::PROVIDER,
+    #[cfg(feature = "metrics")]
+    &metrics::PROVIDER,
+    #[cfg(feature = "healthchecks")]
+    &healthchecks::PROVIDER,
+    #[cfg(feature = "plugin")]
+    &plugin::PROVIDER,
+];
+
+/// Config entries that can be written but have no sender yet.
+const UNIMPLEMENTED: &[&str] = &["cmd", "sms-twilio", "phone-call"];
+
+pub fn find_provider(name: &str) -> Option<&'static ProviderDescriptor> {
+    PROVIDERS
+        .iter()
+        .copied()
+        .find(|provider| provider.name == name)
 }
 
 pub fn create_notification_sender(
     config: &NotificationConfigType,
 ) -> Result<Box<dyn NotificationSender>, Box<dyn std::error::Error>> {
-    match config {
-        NotificationConfigType::Telegram(TelegramConfig { token, chat_id, .. }) => Ok(Box::new(
-            telegram::TelegramNotifier::new(token.clone(), chat_id.clone()),
-        )),
-        NotificationConfigType::Email(_) => Err("Email notification not implemented yet".into()),
-        NotificationConfigType::Http(_) => Err("HTTP notification not implemented yet".into()),
-        NotificationConfigType::Cmd(_) => Err("Command notification not implemented yet".into()),
-        NotificationConfigType::TwilioSms(_) => {
-            Err("Twilio SMS notification not implemented yet".into())
+    let kind = config.kind();
+    let provider = match find_provider(kind) {
+        Some(provider) => provider,
+        None if UNIMPLEMENTED.contains(&kind) => {
+            return Err(format!("{} notification not implemented yet", kind).into())
         }
-        NotificationConfigType::PhoneCall(_) => {
-            Err("Phone call notification not implemented yet".into())
+        None => {
+            return Err(Box::new(NotificationError::ProviderNotCompiledIn(
+                kind.to_string(),
+            )))
         }
-        NotificationConfigType::Lark(config) => Ok(Box::new(lark::LarkNotifier::new(
-            config.webhook_url.clone(),
-            config.sign_key.clone(),
-            config.at.clone(),
-        ))),
+    };
+
+    let errors = provider.validate(config);
+    if !errors.is_empty() {
+        return Err(Box::new(NotificationError::ConfigError(errors.join(", "))));
+    }
+    (provider.build)(config)
+}
+
+/// Error for a `build` function handed a config of another provider.
+pub fn config_mismatch(
+    provider: &ProviderDescriptor,
+    config: &NotificationConfigType,
+) -> Box<dyn std::error::Error> {
+    Box::new(NotificationError::ConfigError(format!(
+        "{} provider cannot be built from a '{}' entry",
+        provider.display_name,
+        config.kind()
+    )))
+}
+
+/// The longest prefix of `text` that fits in `max_bytes`.
+pub fn head_within_bytes(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// The longest suffix of `text` that fits in `max_bytes`.
+pub fn tail_within_bytes(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut start = text.len() - max_bytes;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    &text[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{LarkConfig, TelegramConfig};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingSender {
+        sent: Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl NotificationSender for RecordingSender {
+        async fn send(&self, message: &str) -> Result<(), Box<dyn std::error::Error>> {
+            self.sent.lock().unwrap().push(message.to_string());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_default_lifecycle_falls_back_to_send() {
+        let sender = RecordingSender::default();
+        let mut report = RunReport::new("make", vec![]);
+        report.host = "build-1".to_string();
+        report.output = Some("done".to_string());
+        let mut context = RunContext::new("ci", &report);
+
+        sender.on_start(&context).await.unwrap();
+        sender
+            .on_progress(&context, &RunProgress::default())
+            .await
+            .unwrap();
+        sender.on_finish(&context, &report).await.unwrap();
+        assert_eq!(*sender.sent.lock().unwrap(), vec!["Command output:\ndone"]);
+
+        context.notify_on_start = true;
+        sender.on_start(&context).await.unwrap();
+        assert_eq!(
+            sender.sent.lock().unwrap().last().unwrap(),
+            "⏳ make started on host build-1"
+        );
+    }
+
+    #[test]
+    fn test_create_notification_sender() {
+        let telegram = NotificationConfigType::Telegram(TelegramConfig {
+            token: "token".to_string(),
+            chat_id: "42".to_string(),
+            ..Default::default()
+        });
+        let lark = NotificationConfigType::Lark(LarkConfig {
+            webhook_url: "https://example.com/hook".to_string(),
+            ..Default::default()
+        });
+
+        for (config, provider, enabled) in [
+            (telegram, "telegram", cfg!(feature = "telegram")),
+            (lark, "lark", cfg!(feature = "lark")),
+        ] {
+            match create_notification_sender(&config) {
+                Ok(_) => assert!(enabled, "{} should not be compiled in", provider),
+                Err(e) => {
+                    assert!(!enabled, "{} failed: {}", provider, e);
+                    assert!(e.to_string().contains("not compiled in"));
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "telegram")]
+    fn test_create_notification_sender_validates() {
+        let telegram = NotificationConfigType::Telegram(TelegramConfig::default());
+        let err = create_notification_sender(&telegram).err().unwrap();
+        assert!(err.to_string().contains("Token is required"), "{}", err);
     }
 }
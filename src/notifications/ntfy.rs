@@ -0,0 +1,379 @@
+use crate::config::{NotificationConfigType, NtfyConfig};
+use crate::notifications::registry::{FieldSpec, FieldType, ProviderDescriptor};
+use crate::notifications::{
+    config_mismatch, head_within_bytes, tail_within_bytes, NotificationSender,
+};
+use crate::report::{format_duration, RunReport};
+use log::{error, info};
+use reqwest::{Client, RequestBuilder, Url};
+use serde_json::{json, Value};
+use std::error::Error;
+
+pub const DEFAULT_SERVER_URL: &str = "https://ntfy.sh";
+
+pub const PROVIDER: ProviderDescriptor = ProviderDescriptor {
+    name: "ntfy",
+    display_name: "ntfy",
+    fields: &[
+        FieldSpec::new("server_url", "Server URL", FieldType::String)
+            .default_value(DEFAULT_SERVER_URL),
+        FieldSpec::new("topic", "Topic", FieldType::String).required(),
+        FieldSpec::new("token", "Access Token", FieldType::String).secret(),
+        FieldSpec::new(
+            "success_priority",
+            "Success Priority (1-5)",
+            FieldType::Integer,
+        ),
+        FieldSpec::new(
+            "failure_priority",
+            "Failure Priority (1-5)",
+            FieldType::Integer,
+        ),
+        FieldSpec::new("tag", "Tags", FieldType::List),
+        FieldSpec::new("click", "Click URL", FieldType::String),
+        FieldSpec::new(
+            "attachment_threshold",
+            "Attach Output Above (chars)",
+            FieldType::Integer,
+        ),
+    ],
+    default_config: || NotificationConfigType::Ntfy(NtfyConfig::default()),
+    build: |config| match config {
+        NotificationConfigType::Ntfy(config) => Ok(Box::new(NtfyNotifier::from_config(config)?)),
+        other => Err(config_mismatch(&PROVIDER, other)),
+    },
+};
+
+/// ntfy turns messages over this many bytes into attachments.
+const MESSAGE_LIMIT: usize = 4096;
+/// Characters of command output above which it is uploaded instead of
+/// shown in the message, unless `attachment_threshold` says otherwise.
+const OUTPUT_LIMIT: usize = 3000;
+/// Characters of the error shown in a notification.
+const ERROR_LIMIT: usize = 1000;
+/// Characters of the summary passed in the query string of an upload, long
+/// URLs are rejected by ntfy and the proxies in front of it.
+const URL_MESSAGE_LIMIT: usize = 500;
+
+const DEFAULT_SUCCESS_PRIORITY: u8 = 3;
+const DEFAULT_FAILURE_PRIORITY: u8 = 5;
+
+pub struct NtfyNotifier {
+    server_url: String,
+    topic: String,
+    token: Option<String>,
+    success_priority: u8,
+    failure_priority: u8,
+    tags: Vec<String>,
+    click: Option<String>,
+    attachment_threshold: usize,
+    client: Client,
+}
+
+impl NtfyNotifier {
+    pub fn from_config(config: &NtfyConfig) -> Result<Self, Box<dyn Error>> {
+        let priority = |value: Option<u8>, default: u8| match value.unwrap_or(default) {
+            priority @ 1..=5 => Ok(priority),
+            other => Err(format!(
+                "ntfy priority must be between 1 and 5, got {}",
+                other
+            )),
+        };
+        let non_empty = |value: &Option<String>| value.clone().filter(|v| !v.is_empty());
+
+        Ok(Self {
+            server_url: non_empty(&config.server_url)
+                .unwrap_or_else(|| DEFAULT_SERVER_URL.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            topic: config.topic.clone(),
+            token: non_empty(&config.token),
+            success_priority: priority(config.success_priority, DEFAULT_SUCCESS_PRIORITY)?,
+            failure_priority: priority(config.failure_priority, DEFAULT_FAILURE_PRIORITY)?,
+            tags: config.tags.clone(),
+            click: non_empty(&config.click),
+            attachment_threshold: config
+                .attachment_threshold
+                .map(|t| t as usize)
+                .unwrap_or(OUTPUT_LIMIT),
+            client: Client::new(),
+        })
+    }
+
+    /// Tags of a report, led by the emoji ntfy shows for the outcome.
+    fn report_tags(&self, success: bool) -> Vec<String> {
+        let emoji = if success {
+            "white_check_mark"
+        } else {
+            "rotating_light"
+        };
+        std::iter::once(emoji.to_string())
+            .chain(self.tags.iter().cloned())
+            .collect()
+    }
+
+    fn summary(report: &RunReport) -> String {
+        let exit_code = report
+            .exit_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let mut summary = format!(
+            "Command: {}\nHost: {}\nDuration: {}, exit code {}",
+            report.command_line(),
+            report.host,
+            format_duration(report.duration),
+            exit_code
+        );
+        if report.error.is_some() {
+            summary.push_str(&format!("\nError: {}", report.error_head(ERROR_LIMIT)));
+        }
+        summary
+    }
+
+    /// JSON publish request, see https://docs.ntfy.sh/publish/#publish-as-json
+    fn report_payload(&self, report: &RunReport) -> Value {
+        let summary = Self::summary(report);
+        let mut message = head_within_bytes(&summary, MESSAGE_LIMIT).to_string();
+        let budget = MESSAGE_LIMIT.saturating_sub(message.len() + 2);
+        let output = tail_within_bytes(report.output_tail(OUTPUT_LIMIT).trim_end(), budget);
+        if !output.is_empty() {
+            message.push_str("\n\n");
+            message.push_str(output);
+        }
+
+        let mut payload = json!({
+            "topic": self.topic,
+            "title": format!("{} {}", report.command, report.status_text()),
+            "message": message,
+            "priority": self.priority(report.success),
+            "tags": self.report_tags(report.success),
+        });
+        if let Some(click) = &self.click {
+            payload["click"] = json!(click);
+        }
+        payload
+    }
+
+    fn priority(&self, success: bool) -> u8 {
+        if success {
+            self.success_priority
+        } else {
+            self.failure_priority
+        }
+    }
+
+    /// Upload of the full output, with the message fields passed as query
+    /// parameters since the body holds the file.
+    fn attachment_url(&self, report: &RunReport) -> Result<Url, Box<dyn Error>> {
+        let mut url = Url::parse(&format!("{}/{}", self.server_url, self.topic))?;
+        {
+            let mut query = url.query_pairs_mut();
+            query
+                .append_pair(
+                    "title",
+                    &format!("{} {}", report.command, report.status_text()),
+                )
+                .append_pair(
+                    "message",
+                    &truncate(&Self::summary(report), URL_MESSAGE_LIMIT),
+                )
+                .append_pair("priority", &self.priority(report.success).to_string())
+                .append_pair("tags", &self.report_tags(report.success).join(","))
+                .append_pair("filename", &report.log_file_name());
+            if let Some(click) = &self.click {
+                query.append_pair("click", click);
+            }
+        }
+        Ok(url)
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn execute(&self, request: RequestBuilder) -> Result<(), Box<dyn Error>> {
+        let response = self.authorize(request).send().await?;
+        let status = response.status();
+        if status.is_success() {
+            info!("ntfy message published successfully");
+            Ok(())
+        } else {
+            let text = response.text().await?;
+            error!("Failed to publish ntfy message: {} - {}", status, text);
+            Err(format!("Failed to publish ntfy message: {} - {}", status, text).into())
+        }
+    }
+}
+
+/// `text` cut to `limit` characters, marked with an ellipsis when cut.
+fn truncate(text: &str, limit: usize) -> String {
+    if text.chars().count() <= limit {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(limit - 1).collect();
+    cut.push('…');
+    cut
+}
+
+#[async_trait::async_trait]
+impl NotificationSender for NtfyNotifier {
+    async fn send(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        let mut payload = json!({"topic": self.topic, "message": message});
+        if !self.tags.is_empty() {
+            payload["tags"] = json!(self.tags);
+        }
+        self.execute(self.client.post(&self.server_url).json(&payload))
+            .await
+    }
+
+    async fn send_report(&self, report: &RunReport) -> Result<(), Box<dyn Error>> {
+        let output = report.output.as_deref().unwrap_or_default();
+        if output.chars().count() <= self.attachment_threshold {
+            let payload = self.report_payload(report);
+            return self
+                .execute(self.client.post(&self.server_url).json(&payload))
+                .await;
+        }
+
+        let url = self.attachment_url(report)?;
+        self.execute(self.client.put(url).body(output.to_string()))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::test_server::{MockResponse, MockServer};
+
+    fn config(server_url: &str) -> NtfyConfig {
+        NtfyConfig {
+            server_url: Some(server_url.to_string()),
+            topic: "builds".to_string(),
+            token: Some("tk_secret".to_string()),
+            success_priority: None,
+            failure_priority: None,
+            tags: vec!["ci".to_string()],
+            click: Some("https://ci.example.com/1".to_string()),
+            attachment_threshold: Some(20),
+        }
+    }
+
+    fn report(success: bool, output: &str) -> RunReport {
+        let mut report = RunReport::new("make", vec!["test".to_string()]);
+        report.host = "build-1".to_string();
+        report.duration = std::time::Duration::from_secs(3);
+        report.exit_code = Some(if success { 0 } else { 2 });
+        report.success = success;
+        report.output = Some(output.to_string());
+        report
+    }
+
+    #[test]
+    fn test_report_payload() {
+        let notifier = NtfyNotifier::from_config(&config("https://ntfy.example.com/")).unwrap();
+        assert_eq!(notifier.server_url, "https://ntfy.example.com");
+
+        assert_eq!(
+            notifier.report_payload(&report(true, "ok\n")),
+            json!({
+                "topic": "builds",
+                "title": "make succeeded",
+                "message": "Command: make test\nHost: build-1\nDuration: 3s, exit code 0\n\nok",
+                "priority": 3,
+                "tags": ["white_check_mark", "ci"],
+                "click": "https://ci.example.com/1"
+            })
+        );
+
+        let failure = notifier.report_payload(&report(false, ""));
+        assert_eq!(failure["priority"], 5);
+        assert_eq!(failure["tags"][0], "rotating_light");
+
+        let mut long = report(false, &"输出\n".repeat(2000));
+        long.error = Some("错误\n".repeat(5000));
+        let message = notifier.report_payload(&long)["message"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        assert!(message.len() <= MESSAGE_LIMIT);
+        assert!(message.starts_with("Command: make test\n"));
+        assert!(message.ends_with("\n输出"));
+
+        let mut invalid = config("https://ntfy.example.com");
+        invalid.failure_priority = Some(6);
+        assert!(NtfyNotifier::from_config(&invalid).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send_report() {
+        let server = MockServer::start(vec![MockResponse::json(json!({"id": "abc"}))]).await;
+        let notifier = NtfyNotifier::from_config(&config(&server.url)).unwrap();
+        notifier.send_report(&report(true, "ok")).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/");
+        assert_eq!(
+            requests[0].header("authorization"),
+            Some("Bearer tk_secret")
+        );
+        assert_eq!(requests[0].json()["topic"], "builds");
+    }
+
+    #[tokio::test]
+    async fn test_long_output_is_attached() {
+        let server = MockServer::start(vec![MockResponse::json(json!({"id": "abc"}))]).await;
+        let notifier = NtfyNotifier::from_config(&config(&server.url)).unwrap();
+
+        let report = report(false, "line one\nline two\nline three\n");
+        notifier.send_report(&report).await.unwrap();
+
+        let request = &server.requests()[0];
+        assert_eq!(request.method, "PUT");
+        let url = Url::parse(&format!("{}{}", server.url, request.path)).unwrap();
+        assert_eq!(url.path(), "/builds");
+        let query: Vec<_> = url.query_pairs().into_owned().collect();
+        assert!(query.contains(&("title".to_string(), "make failed".to_string())));
+        assert!(query.contains(&("priority".to_string(), "5".to_string())));
+        assert!(query.contains(&("tags".to_string(), "rotating_light,ci".to_string())));
+        assert!(query.contains(&("filename".to_string(), report.log_file_name())));
+        assert_eq!(request.body_text(), "line one\nline two\nline three\n");
+    }
+
+    #[tokio::test]
+    async fn test_long_error_in_url() {
+        let server = MockServer::start(vec![MockResponse::json(json!({"id": "abc"}))]).await;
+        let notifier = NtfyNotifier::from_config(&config(&server.url)).unwrap();
+
+        let mut report = report(false, "line one\nline two\nline three\n");
+        report.error = Some("stderr line\n".repeat(5000));
+        notifier.send_report(&report).await.unwrap();
+
+        let request = &server.requests()[0];
+        assert!(request.path.len() < 4096);
+        let url = Url::parse(&format!("{}{}", server.url, request.path)).unwrap();
+        let (_, message) = url.query_pairs().find(|(key, _)| key == "message").unwrap();
+        assert!(message.starts_with("Command: make test\n"));
+        assert!(message.ends_with('…'));
+        assert_eq!(message.chars().count(), URL_MESSAGE_LIMIT);
+    }
+
+    #[tokio::test]
+    async fn test_publish_error() {
+        let server = MockServer::start(vec![MockResponse::new(
+            403,
+            r#"{"code":40301,"http":403,"error":"forbidden"}"#,
+        )])
+        .await;
+        let err = NtfyNotifier::from_config(&config(&server.url))
+            .unwrap()
+            .send("hello")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("forbidden"));
+    }
+}
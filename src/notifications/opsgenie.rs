@@ -0,0 +1,318 @@
+use crate::config::{NotificationConfigType, OpsgenieConfig};
+use crate::notifications::registry::{FieldSpec, FieldType, ProviderDescriptor};
+use crate::notifications::{config_mismatch, NotificationSender};
+use crate::report::{format_duration, hostname, RunContext, RunReport};
+use log::{error, info};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::error::Error;
+
+pub const DEFAULT_API_URL: &str = "https://api.opsgenie.com";
+
+pub const PROVIDER: ProviderDescriptor = ProviderDescriptor {
+    name: "opsgenie",
+    display_name: "Opsgenie",
+    fields: &[
+        FieldSpec::new("api_key", "API Key", FieldType::String)
+            .required()
+            .secret(),
+        FieldSpec::new("priority", "Priority (P1-P5)", FieldType::String),
+        FieldSpec::new("alias", "Alias", FieldType::String),
+        FieldSpec::new("tag", "Tags", FieldType::List),
+        FieldSpec::new("close_on_success", "Close On Success", FieldType::Bool),
+        FieldSpec::new("api_url", "API URL", FieldType::String).default_value(DEFAULT_API_URL),
+    ],
+    default_config: || NotificationConfigType::Opsgenie(OpsgenieConfig::default()),
+    build: |config| match config {
+        NotificationConfigType::Opsgenie(config) => {
+            Ok(Box::new(OpsgenieNotifier::from_config(config)?))
+        }
+        other => Err(config_mismatch(&PROVIDER, other)),
+    },
+};
+
+const PRIORITIES: &[&str] = &["P1", "P2", "P3", "P4", "P5"];
+/// Opsgenie rejects longer alert messages.
+const MESSAGE_LIMIT: usize = 130;
+/// Opsgenie truncates longer descriptions.
+const DESCRIPTION_LIMIT: usize = 15000;
+/// Characters of command output included in the description.
+const OUTPUT_LIMIT: usize = 10000;
+/// Characters of the error included in the description, leaving room for
+/// the output.
+const ERROR_LIMIT: usize = 4000;
+
+pub struct OpsgenieNotifier {
+    api_url: String,
+    api_key: String,
+    priority: String,
+    alias: Option<String>,
+    tags: Vec<String>,
+    close_on_success: bool,
+    client: Client,
+}
+
+impl OpsgenieNotifier {
+    pub fn from_config(config: &OpsgenieConfig) -> Result<Self, Box<dyn Error>> {
+        let non_empty = |value: &Option<String>| value.clone().filter(|v| !v.is_empty());
+        let priority = non_empty(&config.priority)
+            .map(|priority| priority.to_uppercase())
+            .unwrap_or_else(|| "P3".to_string());
+        if !PRIORITIES.contains(&priority.as_str()) {
+            return Err(format!(
+                "Unknown Opsgenie priority '{}', expected one of {}",
+                priority,
+                PRIORITIES.join(", ")
+            )
+            .into());
+        }
+
+        Ok(Self {
+            api_url: non_empty(&config.api_url)
+                .unwrap_or_else(|| DEFAULT_API_URL.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            api_key: config.api_key.clone(),
+            priority,
+            alias: non_empty(&config.alias),
+            tags: config.tags.clone(),
+            close_on_success: config.close_on_success.unwrap_or(true),
+            client: Client::new(),
+        })
+    }
+
+    /// `path` below the API URL, each segment percent-encoded.
+    fn url(&self, path: &[&str]) -> Result<Url, Box<dyn Error>> {
+        let mut url = Url::parse(&self.api_url)?;
+        url.path_segments_mut()
+            .map_err(|_| format!("Invalid Opsgenie API URL '{}'", self.api_url))?
+            .pop_if_empty()
+            .extend(path);
+        Ok(url)
+    }
+
+    fn alias(&self, report: &RunReport) -> String {
+        self.alias.clone().unwrap_or_else(|| report.incident_key())
+    }
+
+    fn alert(&self, report: &RunReport) -> Value {
+        let exit_code = report
+            .exit_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let message = format!("{} failed on {}", report.command_line(), report.host);
+        let mut description = format!(
+            "Command: {}\nHost: {}\nExit code: {}\nDuration: {}\n",
+            report.command_line(),
+            report.host,
+            exit_code,
+            format_duration(report.duration)
+        );
+        if report.error.is_some() {
+            description.push_str(&format!("Error: {}\n", report.error_head(ERROR_LIMIT)));
+        }
+        let output = report.output_tail(OUTPUT_LIMIT);
+        if !output.is_empty() {
+            description.push_str(&format!("\n{}", output));
+        }
+
+        json!({
+            "message": message.chars().take(MESSAGE_LIMIT).collect::<String>(),
+            "alias": self.alias(report),
+            "description": description.chars().take(DESCRIPTION_LIMIT).collect::<String>(),
+            "source": "notifyme",
+            "priority": self.priority,
+            "tags": self.tags,
+            "entity": report.host,
+            "details": {
+                "command": report.command,
+                "exit_code": exit_code,
+                "started_at": report.started_at.to_rfc3339(),
+            }
+        })
+    }
+
+    async fn post(&self, url: Url, body: Value) -> Result<(), Box<dyn Error>> {
+        let response = self
+            .client
+            .post(url)
+            .header("Authorization", format!("GenieKey {}", self.api_key))
+            .json(&body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        let text = response.text().await?;
+        if status.is_success() {
+            // Requests are processed asynchronously, `requestId` tracks them
+            let request_id = serde_json::from_str::<OpsgenieResponse>(&text)
+                .map(|body| body.request_id)
+                .unwrap_or_default();
+            info!("Opsgenie request {} accepted", request_id);
+            return Ok(());
+        }
+        let detail = serde_json::from_str::<OpsgenieResponse>(&text)
+            .map(|body| body.message)
+            .unwrap_or(text);
+        error!("Failed to send Opsgenie request: {} - {}", status, detail);
+        Err(format!("Failed to send Opsgenie request: {} - {}", status, detail).into())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct OpsgenieResponse {
+    #[serde(default)]
+    message: String,
+    #[serde(rename = "requestId", default)]
+    request_id: String,
+}
+
+#[async_trait::async_trait]
+impl NotificationSender for OpsgenieNotifier {
+    /// Plain messages open an alert of their own.
+    async fn send(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        let url = self.url(&["v2", "alerts"])?;
+        self.post(
+            url,
+            json!({
+                "message": message.chars().take(MESSAGE_LIMIT).collect::<String>(),
+                "description": message.chars().take(DESCRIPTION_LIMIT).collect::<String>(),
+                "source": "notifyme",
+                "priority": self.priority,
+                "tags": self.tags,
+                "entity": hostname(),
+            }),
+        )
+        .await
+    }
+
+    async fn send_report(&self, report: &RunReport) -> Result<(), Box<dyn Error>> {
+        if !report.success {
+            let url = self.url(&["v2", "alerts"])?;
+            return self.post(url, self.alert(report)).await;
+        }
+        if !self.close_on_success {
+            return Ok(());
+        }
+        let mut url = self.url(&["v2", "alerts", &self.alias(report), "close"])?;
+        url.query_pairs_mut().append_pair("identifierType", "alias");
+        self.post(
+            url,
+            json!({
+                "source": "notifyme",
+                "note": format!(
+                    "{} succeeded on {} after {}",
+                    report.command_line(),
+                    report.host,
+                    format_duration(report.duration)
+                ),
+            }),
+        )
+        .await
+    }
+
+    /// Starting a run is not an alert.
+    async fn on_start(&self, _context: &RunContext) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::test_server::{MockResponse, MockServer};
+
+    const ACCEPTED: &str =
+        r#"{"result":"Request will be processed","took":0.302,"requestId":"43a29c5c"}"#;
+
+    fn config(api_url: &str) -> OpsgenieConfig {
+        OpsgenieConfig {
+            api_key: "eb243592-faa2-4ba2-a551q-1afdf565c889".to_string(),
+            priority: Some("p2".to_string()),
+            tags: vec!["backup".to_string(), "prod".to_string()],
+            api_url: Some(api_url.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn report(success: bool) -> RunReport {
+        let mut report = RunReport::new("backup.sh", vec!["--full".to_string()]);
+        report.host = "db-1".to_string();
+        report.exit_code = Some(if success { 0 } else { 1 });
+        report.success = success;
+        report.output = Some("disk full\n".to_string());
+        report
+    }
+
+    #[tokio::test]
+    async fn test_create_then_close() {
+        let server = MockServer::start(vec![MockResponse::new(202, ACCEPTED)]).await;
+        let notifier = OpsgenieNotifier::from_config(&config(&server.url)).unwrap();
+
+        notifier.send_report(&report(false)).await.unwrap();
+        notifier.send_report(&report(true)).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/v2/alerts");
+        assert_eq!(
+            requests[0].header("authorization"),
+            Some("GenieKey eb243592-faa2-4ba2-a551q-1afdf565c889")
+        );
+        let alert = requests[0].json();
+        assert_eq!(alert["message"], "backup.sh --full failed on db-1");
+        assert_eq!(alert["priority"], "P2");
+        assert_eq!(alert["tags"], json!(["backup", "prod"]));
+        assert_eq!(alert["entity"], "db-1");
+        assert!(alert["description"]
+            .as_str()
+            .unwrap()
+            .ends_with("\ndisk full\n"));
+
+        let alias = alert["alias"].as_str().unwrap();
+        assert!(alias.starts_with("notifyme-"));
+        assert_eq!(
+            requests[1].path,
+            format!("/v2/alerts/{}/close?identifierType=alias", alias)
+        );
+        assert_eq!(requests[1].json()["source"], "notifyme");
+    }
+
+    #[tokio::test]
+    async fn test_alias_and_close_off() {
+        let server = MockServer::start(vec![MockResponse::new(202, ACCEPTED)]).await;
+        let mut config = config(&server.url);
+        config.alias = Some("nightly backup".to_string());
+        let notifier = OpsgenieNotifier::from_config(&config).unwrap();
+        notifier.send_report(&report(true)).await.unwrap();
+
+        config.close_on_success = Some(false);
+        let notifier = OpsgenieNotifier::from_config(&config).unwrap();
+        notifier.send_report(&report(true)).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(
+            requests[0].path,
+            "/v2/alerts/nightly%20backup/close?identifierType=alias"
+        );
+
+        config.priority = Some("P9".to_string());
+        assert!(OpsgenieNotifier::from_config(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_key() {
+        let server = MockServer::start(vec![MockResponse::new(
+            422,
+            r#"{"message":"Key format is not valid!","took":0.001,"requestId":"b2c1"}"#,
+        )])
+        .await;
+        let err = OpsgenieNotifier::from_config(&config(&server.url))
+            .unwrap()
+            .send_report(&report(false))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Key format is not valid!"));
+    }
+}
@@ -0,0 +1,301 @@
+use crate::config::{NotificationConfigType, PagerDutyConfig};
+use crate::notifications::registry::{FieldSpec, FieldType, ProviderDescriptor};
+use crate::notifications::{config_mismatch, NotificationSender};
+use crate::report::{format_duration, hostname, RunContext, RunReport};
+use log::{error, info};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::error::Error;
+
+pub const DEFAULT_EVENTS_URL: &str = "https://events.pagerduty.com";
+
+pub const PROVIDER: ProviderDescriptor = ProviderDescriptor {
+    name: "pagerduty",
+    display_name: "PagerDuty",
+    fields: &[
+        FieldSpec::new("routing_key", "Integration Key", FieldType::String)
+            .required()
+            .secret(),
+        FieldSpec::new(
+            "severity",
+            "Severity (critical/error/warning/info)",
+            FieldType::String,
+        ),
+        FieldSpec::new("dedup_key", "Dedup Key", FieldType::String),
+        FieldSpec::new("resolve_on_success", "Resolve On Success", FieldType::Bool),
+        FieldSpec::new("events_url", "Events API URL", FieldType::String)
+            .default_value(DEFAULT_EVENTS_URL),
+    ],
+    default_config: || NotificationConfigType::PagerDuty(PagerDutyConfig::default()),
+    build: |config| match config {
+        NotificationConfigType::PagerDuty(config) => {
+            Ok(Box::new(PagerDutyNotifier::from_config(config)?))
+        }
+        other => Err(config_mismatch(&PROVIDER, other)),
+    },
+};
+
+const SEVERITIES: &[&str] = &["critical", "error", "warning", "info"];
+/// PagerDuty truncates summaries longer than this.
+const SUMMARY_LIMIT: usize = 1024;
+/// Characters of command output sent as a custom detail.
+const OUTPUT_LIMIT: usize = 4000;
+/// Characters of the error sent as a custom detail, PagerDuty rejects
+/// events over 512 KB.
+const ERROR_LIMIT: usize = 4000;
+
+pub struct PagerDutyNotifier {
+    events_url: String,
+    routing_key: String,
+    severity: String,
+    dedup_key: Option<String>,
+    resolve_on_success: bool,
+    client: Client,
+}
+
+impl PagerDutyNotifier {
+    pub fn from_config(config: &PagerDutyConfig) -> Result<Self, Box<dyn Error>> {
+        let non_empty = |value: &Option<String>| value.clone().filter(|v| !v.is_empty());
+        let severity = non_empty(&config.severity).unwrap_or_else(|| "error".to_string());
+        if !SEVERITIES.contains(&severity.as_str()) {
+            return Err(format!(
+                "Unknown PagerDuty severity '{}', expected one of {}",
+                severity,
+                SEVERITIES.join(", ")
+            )
+            .into());
+        }
+
+        Ok(Self {
+            events_url: non_empty(&config.events_url)
+                .unwrap_or_else(|| DEFAULT_EVENTS_URL.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            routing_key: config.routing_key.clone(),
+            severity,
+            dedup_key: non_empty(&config.dedup_key),
+            resolve_on_success: config.resolve_on_success.unwrap_or(true),
+            client: Client::new(),
+        })
+    }
+
+    fn dedup_key(&self, report: &RunReport) -> String {
+        self.dedup_key
+            .clone()
+            .unwrap_or_else(|| report.incident_key())
+    }
+
+    fn trigger_event(&self, report: &RunReport) -> Value {
+        let exit_code = report
+            .exit_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let summary = format!(
+            "{} failed on {} with exit code {}",
+            report.command_line(),
+            report.host,
+            exit_code
+        );
+        json!({
+            "routing_key": self.routing_key,
+            "event_action": "trigger",
+            "dedup_key": self.dedup_key(report),
+            "client": "notifyme",
+            "payload": {
+                "summary": summary.chars().take(SUMMARY_LIMIT).collect::<String>(),
+                "source": report.host,
+                "severity": self.severity,
+                "timestamp": report.started_at.to_rfc3339(),
+                "component": report.command,
+                "custom_details": {
+                    "command_line": report.command_line(),
+                    "duration": format_duration(report.duration),
+                    "exit_code": report.exit_code,
+                    "error": report.error.as_ref().map(|_| report.error_head(ERROR_LIMIT)),
+                    "output": report.output_tail(OUTPUT_LIMIT),
+                }
+            }
+        })
+    }
+
+    async fn enqueue(&self, event: Value) -> Result<(), Box<dyn Error>> {
+        let response = self
+            .client
+            .post(format!("{}/v2/enqueue", self.events_url))
+            .json(&event)
+            .send()
+            .await?;
+
+        let status = response.status();
+        let text = response.text().await?;
+        if status.is_success() {
+            info!("PagerDuty {} event accepted", event["event_action"]);
+            return Ok(());
+        }
+        let detail = match serde_json::from_str::<PagerDutyResponse>(&text) {
+            Ok(body) if !body.errors.is_empty() => {
+                format!("{}: {}", body.message, body.errors.join(", "))
+            }
+            Ok(body) => body.message,
+            Err(_) => text,
+        };
+        error!("Failed to send PagerDuty event: {} - {}", status, detail);
+        Err(format!("Failed to send PagerDuty event: {} - {}", status, detail).into())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct PagerDutyResponse {
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    errors: Vec<String>,
+}
+
+#[async_trait::async_trait]
+impl NotificationSender for PagerDutyNotifier {
+    /// Plain messages open an incident of their own.
+    async fn send(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.enqueue(json!({
+            "routing_key": self.routing_key,
+            "event_action": "trigger",
+            "client": "notifyme",
+            "payload": {
+                "summary": message.chars().take(SUMMARY_LIMIT).collect::<String>(),
+                "source": hostname(),
+                "severity": self.severity,
+            }
+        }))
+        .await
+    }
+
+    async fn send_report(&self, report: &RunReport) -> Result<(), Box<dyn Error>> {
+        if !report.success {
+            return self.enqueue(self.trigger_event(report)).await;
+        }
+        if !self.resolve_on_success {
+            return Ok(());
+        }
+        // Resolving a key without an open incident is a no-op
+        self.enqueue(json!({
+            "routing_key": self.routing_key,
+            "event_action": "resolve",
+            "dedup_key": self.dedup_key(report),
+        }))
+        .await
+    }
+
+    /// Starting a run is not an incident.
+    async fn on_start(&self, _context: &RunContext) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::test_server::{MockResponse, MockServer};
+
+    fn config(events_url: &str) -> PagerDutyConfig {
+        PagerDutyConfig {
+            routing_key: "R0UT1NG".to_string(),
+            severity: Some("critical".to_string()),
+            events_url: Some(events_url.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn report(success: bool) -> RunReport {
+        let mut report = RunReport::new("backup.sh", vec!["--full".to_string()]);
+        report.host = "db-1".to_string();
+        report.exit_code = Some(if success { 0 } else { 1 });
+        report.success = success;
+        report.output = Some("disk full\n".to_string());
+        report
+    }
+
+    #[tokio::test]
+    async fn test_trigger_then_resolve() {
+        let server = MockServer::start(vec![MockResponse::new(
+            202,
+            r#"{"status":"success","message":"Event processed","dedup_key":"k"}"#,
+        )])
+        .await;
+        let notifier = PagerDutyNotifier::from_config(&config(&server.url)).unwrap();
+
+        notifier.send_report(&report(false)).await.unwrap();
+        notifier.send_report(&report(true)).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/v2/enqueue");
+        let trigger = requests[0].json();
+        assert_eq!(trigger["routing_key"], "R0UT1NG");
+        assert_eq!(trigger["event_action"], "trigger");
+        assert_eq!(
+            trigger["payload"]["summary"],
+            "backup.sh --full failed on db-1 with exit code 1"
+        );
+        assert_eq!(trigger["payload"]["source"], "db-1");
+        assert_eq!(trigger["payload"]["severity"], "critical");
+        assert_eq!(
+            trigger["payload"]["custom_details"]["output"],
+            "disk full\n"
+        );
+
+        let resolve = requests[1].json();
+        assert_eq!(resolve["event_action"], "resolve");
+        assert_eq!(resolve["dedup_key"], trigger["dedup_key"]);
+        assert!(resolve["dedup_key"]
+            .as_str()
+            .unwrap()
+            .starts_with("notifyme-"));
+    }
+
+    #[test]
+    fn test_long_error() {
+        let notifier =
+            PagerDutyNotifier::from_config(&config("https://events.example.com")).unwrap();
+        let mut failed = report(false);
+        failed.error = Some("e".repeat(1_000_000));
+        let details = &notifier.trigger_event(&failed)["payload"]["custom_details"];
+        assert_eq!(details["error"].as_str().unwrap().len(), ERROR_LIMIT);
+        assert!(
+            notifier.trigger_event(&report(false))["payload"]["custom_details"]["error"].is_null()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dedup_key_and_resolve_off() {
+        let server = MockServer::start(vec![MockResponse::new(202, "{}")]).await;
+        let mut config = config(&server.url);
+        config.dedup_key = Some("nightly-backup".to_string());
+        config.resolve_on_success = Some(false);
+        let notifier = PagerDutyNotifier::from_config(&config).unwrap();
+
+        notifier.send_report(&report(true)).await.unwrap();
+        notifier.send_report(&report(false)).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].json()["dedup_key"], "nightly-backup");
+
+        config.severity = Some("fatal".to_string());
+        assert!(PagerDutyNotifier::from_config(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_event() {
+        let server = MockServer::start(vec![MockResponse::new(
+            400,
+            r#"{"status":"invalid event","message":"Event object is invalid","errors":["Length of 'routing_key' is incorrect (should be 32 characters)"]}"#,
+        )])
+        .await;
+        let err = PagerDutyNotifier::from_config(&config(&server.url))
+            .unwrap()
+            .send_report(&report(false))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Length of 'routing_key'"));
+    }
+}
@@ -0,0 +1,360 @@
+//! External notification providers.
+//!
+//! A `<plugin name="foo">` entry runs the executable `notifyme-provider-foo`
+//! (looked up in `PATH`, or the entry's `path` attribute) once per
+//! notification. notifyme writes a single JSON [`PluginRequest`] to the
+//! plugin's stdin and closes it; the plugin answers with a single JSON
+//! [`PluginResponse`] on stdout and exits:
+//!
+//! ```text
+//! -> {"version":1,"params":{"channel":"ops"},"message":"...","report":{...}}
+//! <- {"version":1,"ok":true}
+//! <- {"version":1,"ok":false,"error":"channel not found"}
+//! ```
+//!
+//! `report` is `null` when only a plain message is sent. Plugins must reject
+//! requests whose `version` they do not understand.
+
+use crate::config::{NotificationConfigType, PluginConfig};
+use crate::notifications::registry::{FieldSpec, FieldType, ProviderDescriptor};
+use crate::notifications::{config_mismatch, NotificationSender};
+use crate::report::RunReport;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command as TokioCommand;
+
+pub const PLUGIN_PROTOCOL_VERSION: u32 = 1;
+const PLUGIN_PREFIX: &str = "notifyme-provider-";
+const DEFAULT_TIMEOUT_SECS: u32 = 30;
+
+pub const PROVIDER: ProviderDescriptor = ProviderDescriptor {
+    name: "plugin",
+    display_name: "Plugin",
+    fields: &[
+        FieldSpec::new("@name", "Name", FieldType::String).required(),
+        FieldSpec::new("@path", "Path", FieldType::String),
+        FieldSpec::new("@timeout", "Timeout (s)", FieldType::Integer),
+        FieldSpec::new("param", "Params", FieldType::Params),
+    ],
+    default_config: || NotificationConfigType::Plugin(PluginConfig::default()),
+    build: |config| match config {
+        NotificationConfigType::Plugin(config) => Ok(Box::new(PluginNotifier::new(config))),
+        other => Err(config_mismatch(&PROVIDER, other)),
+    },
+};
+
+#[derive(Serialize, Debug)]
+pub struct PluginRequest<'a> {
+    pub version: u32,
+    pub params: &'a HashMap<String, String>,
+    pub message: String,
+    pub report: Option<PluginReport<'a>>,
+}
+
+/// Wire form of [`RunReport`].
+#[derive(Serialize, Debug)]
+pub struct PluginReport<'a> {
+    pub command: &'a str,
+    pub args: &'a [String],
+    pub command_line: String,
+    pub host: &'a str,
+    pub started_at: String,
+    pub duration_secs: f64,
+    pub exit_code: Option<i32>,
+    pub success: bool,
+    pub output: Option<&'a str>,
+    pub error: Option<&'a str>,
+}
+
+impl<'a> From<&'a RunReport> for PluginReport<'a> {
+    fn from(report: &'a RunReport) -> Self {
+        Self {
+            command: &report.command,
+            args: &report.args,
+            command_line: report.command_line(),
+            host: &report.host,
+            started_at: report.started_at.to_rfc3339(),
+            duration_secs: report.duration.as_secs_f64(),
+            exit_code: report.exit_code,
+            success: report.success,
+            output: report.output.as_deref(),
+            error: report.error.as_deref(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PluginResponse {
+    pub version: u32,
+    pub ok: bool,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+pub struct PluginNotifier {
+    name: String,
+    program: String,
+    params: HashMap<String, String>,
+    timeout: Duration,
+}
+
+impl PluginNotifier {
+    pub fn new(config: &PluginConfig) -> Self {
+        let program = config
+            .path
+            .clone()
+            .filter(|path| !path.is_empty())
+            .unwrap_or_else(|| format!("{}{}", PLUGIN_PREFIX, config.name));
+        let params = config
+            .params
+            .iter()
+            .map(|p| (p.key.clone(), p.value.clone()))
+            .collect();
+
+        Self {
+            name: config.name.clone(),
+            program,
+            params,
+            timeout: Duration::from_secs(config.timeout.unwrap_or(DEFAULT_TIMEOUT_SECS) as u64),
+        }
+    }
+
+    async fn invoke(&self, request: &PluginRequest<'_>) -> Result<(), Box<dyn Error>> {
+        let payload = serde_json::to_vec(request)?;
+
+        let mut child = TokioCommand::new(&self.program)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Failed to start plugin '{}': {}", self.program, e))?;
+
+        let mut stdin = child.stdin.take().ok_or("Failed to open plugin stdin")?;
+        // Input is fed while the output is drained, both under the timeout,
+        // so a plugin that leaves a large payload unread (or fills its stderr
+        // first) cannot block notifyme on a full pipe.
+        let write = async move {
+            let result = stdin.write_all(&payload).await;
+            drop(stdin);
+            result
+        };
+        let (written, output) = match tokio::time::timeout(self.timeout, async {
+            tokio::join!(write, child.wait_with_output())
+        })
+        .await
+        {
+            Ok(result) => result,
+            Err(_) => {
+                return Err(format!(
+                    "Plugin '{}' timed out after {}s",
+                    self.name,
+                    self.timeout.as_secs()
+                )
+                .into())
+            }
+        };
+        let output = output?;
+        // A plugin that exits without reading its input is reported through
+        // its exit status below rather than as a broken pipe.
+        if let Err(e) = written {
+            if e.kind() != std::io::ErrorKind::BrokenPipe {
+                return Err(e.into());
+            }
+        }
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            error!("Plugin '{}' failed: {}", self.name, stderr);
+            return Err(format!(
+                "Plugin '{}' exited with {}: {}",
+                self.name,
+                output.status,
+                stderr.trim()
+            )
+            .into());
+        }
+
+        let response: PluginResponse = serde_json::from_slice(&output.stdout).map_err(|e| {
+            format!(
+                "Invalid response from plugin '{}': {}, body: {}",
+                self.name,
+                e,
+                String::from_utf8_lossy(&output.stdout)
+            )
+        })?;
+
+        if response.version != PLUGIN_PROTOCOL_VERSION {
+            return Err(format!(
+                "Plugin '{}' answered with protocol version {}, expected {}",
+                self.name, response.version, PLUGIN_PROTOCOL_VERSION
+            )
+            .into());
+        }
+
+        if response.ok {
+            info!("Plugin '{}' notification sent successfully", self.name);
+            Ok(())
+        } else {
+            Err(format!(
+                "Plugin '{}' failed to send notification: {}",
+                self.name,
+                response
+                    .error
+                    .unwrap_or_else(|| "unknown error".to_string())
+            )
+            .into())
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl NotificationSender for PluginNotifier {
+    async fn send(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.invoke(&PluginRequest {
+            version: PLUGIN_PROTOCOL_VERSION,
+            params: &self.params,
+            message: message.to_string(),
+            report: None,
+        })
+        .await
+    }
+
+    async fn send_report(&self, report: &RunReport) -> Result<(), Box<dyn Error>> {
+        self.invoke(&PluginRequest {
+            version: PLUGIN_PROTOCOL_VERSION,
+            params: &self.params,
+            message: report.to_message(),
+            report: Some(report.into()),
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PluginParam;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::{Path, PathBuf};
+
+    fn write_plugin(name: &str, script: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("notifyme-plugin-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{}", script)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn config(path: &Path) -> PluginConfig {
+        PluginConfig {
+            name: "test".to_string(),
+            path: Some(path.display().to_string()),
+            timeout: Some(5),
+            params: vec![PluginParam {
+                key: "channel".to_string(),
+                value: "ops".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_default_program_name() {
+        let notifier = PluginNotifier::new(&PluginConfig {
+            name: "foo".to_string(),
+            ..Default::default()
+        });
+        assert_eq!(notifier.program, "notifyme-provider-foo");
+    }
+
+    #[tokio::test]
+    async fn test_send_report() {
+        let request_path =
+            std::env::temp_dir().join(format!("notifyme-plugin-request-{}", std::process::id()));
+        let plugin = write_plugin(
+            "ok",
+            &format!(
+                "cat > {}\necho '{{\"version\":1,\"ok\":true}}'\n",
+                request_path.display()
+            ),
+        );
+
+        let mut report = RunReport::new("make", vec!["test".to_string()]);
+        report.exit_code = Some(2);
+        report.success = false;
+        report.output = Some("boom".to_string());
+
+        let notifier = PluginNotifier::new(&config(&plugin));
+        notifier.send_report(&report).await.unwrap();
+
+        let request: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&request_path).unwrap()).unwrap();
+        assert_eq!(request["version"], 1);
+        assert_eq!(request["params"]["channel"], "ops");
+        assert_eq!(request["report"]["command_line"], "make test");
+        assert_eq!(request["report"]["exit_code"], 2);
+        assert_eq!(request["report"]["success"], false);
+        assert_eq!(request["report"]["output"], "boom");
+        fs::remove_file(request_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_plugin_errors() {
+        let failed = write_plugin(
+            "failed",
+            "cat > /dev/null\necho '{\"version\":1,\"ok\":false,\"error\":\"no such channel\"}'\n",
+        );
+        let err = PluginNotifier::new(&config(&failed))
+            .send("hello")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no such channel"));
+
+        let version = write_plugin(
+            "version",
+            "cat > /dev/null\necho '{\"version\":99,\"ok\":true}'\n",
+        );
+        let err = PluginNotifier::new(&config(&version))
+            .send("hello")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("protocol version 99"));
+
+        let crashed = write_plugin("crashed", "echo 'bad token' >&2\nexit 3\n");
+        let err = PluginNotifier::new(&config(&crashed))
+            .send("hello")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("bad token"));
+    }
+
+    #[tokio::test]
+    async fn test_unread_payload() {
+        let mut report = RunReport::new("make", vec!["test".to_string()]);
+        report.output = Some("x".repeat(256 * 1024));
+
+        let ignores = write_plugin("ignores", "sleep 1\necho '{\"version\":1,\"ok\":true}'\n");
+        PluginNotifier::new(&config(&ignores))
+            .send_report(&report)
+            .await
+            .unwrap();
+
+        let stuck = write_plugin("stuck", "sleep 30\n");
+        let mut config = config(&stuck);
+        config.timeout = Some(1);
+        let started = std::time::Instant::now();
+        let err = PluginNotifier::new(&config)
+            .send_report(&report)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("timed out"));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,370 @@
+use crate::config::{NotificationConfigType, PushoverConfig};
+use crate::notifications::registry::{FieldSpec, FieldType, ProviderDescriptor};
+use crate::notifications::{config_mismatch, NotificationSender};
+use crate::report::{format_duration, RunReport};
+use log::{error, info};
+use reqwest::Client;
+use serde::Deserialize;
+use std::error::Error;
+
+pub const DEFAULT_API_URL: &str = "https://api.pushover.net/1";
+
+pub const PROVIDER: ProviderDescriptor = ProviderDescriptor {
+    name: "pushover",
+    display_name: "Pushover",
+    fields: &[
+        FieldSpec::new("token", "API Token", FieldType::String)
+            .required()
+            .secret(),
+        FieldSpec::new("user", "User Key", FieldType::String).required(),
+        FieldSpec::new("device", "Device", FieldType::String),
+        FieldSpec::new(
+            "success_priority",
+            "Success Priority (-2..2)",
+            FieldType::Integer,
+        ),
+        FieldSpec::new(
+            "failure_priority",
+            "Failure Priority (-2..2)",
+            FieldType::Integer,
+        ),
+        FieldSpec::new("success_sound", "Success Sound", FieldType::String),
+        FieldSpec::new("failure_sound", "Failure Sound", FieldType::String),
+        FieldSpec::new("retry", "Emergency Retry (s)", FieldType::Integer),
+        FieldSpec::new("expire", "Emergency Expire (s)", FieldType::Integer),
+        FieldSpec::new("url", "URL", FieldType::String),
+        FieldSpec::new("api_url", "API URL", FieldType::String).default_value(DEFAULT_API_URL),
+    ],
+    default_config: || NotificationConfigType::Pushover(PushoverConfig::default()),
+    build: |config| match config {
+        NotificationConfigType::Pushover(config) => {
+            Ok(Box::new(PushoverNotifier::from_config(config)?))
+        }
+        other => Err(config_mismatch(&PROVIDER, other)),
+    },
+};
+
+/// Pushover rejects messages longer than this many characters.
+const MESSAGE_LIMIT: usize = 1024;
+/// Characters of the error shown in a message, leaving room for output.
+const ERROR_LIMIT: usize = 500;
+/// Priority repeating the alert until it is acknowledged.
+const EMERGENCY_PRIORITY: i8 = 2;
+
+const DEFAULT_SUCCESS_PRIORITY: i8 = 0;
+const DEFAULT_FAILURE_PRIORITY: i8 = 1;
+const DEFAULT_RETRY: u32 = 60;
+const DEFAULT_EXPIRE: u32 = 3600;
+
+/// Priority and sound of the notification for one outcome.
+struct Alert {
+    priority: i8,
+    sound: Option<String>,
+}
+
+pub struct PushoverNotifier {
+    api_url: String,
+    token: String,
+    user: String,
+    device: Option<String>,
+    success: Alert,
+    failure: Alert,
+    retry: u32,
+    expire: u32,
+    url: Option<String>,
+    client: Client,
+}
+
+impl PushoverNotifier {
+    pub fn from_config(config: &PushoverConfig) -> Result<Self, Box<dyn Error>> {
+        let non_empty = |value: &Option<String>| value.clone().filter(|v| !v.is_empty());
+        let alert = |priority: Option<i8>, default: i8, sound: &Option<String>| match priority
+            .unwrap_or(default)
+        {
+            priority @ -2..=2 => Ok(Alert {
+                priority,
+                sound: non_empty(sound),
+            }),
+            other => Err(format!(
+                "Pushover priority must be between -2 and 2, got {}",
+                other
+            )),
+        };
+
+        let success = alert(
+            config.success_priority,
+            DEFAULT_SUCCESS_PRIORITY,
+            &config.success_sound,
+        )?;
+        let failure = alert(
+            config.failure_priority,
+            DEFAULT_FAILURE_PRIORITY,
+            &config.failure_sound,
+        )?;
+
+        // Pushover only looks at retry and expire for emergency alerts
+        let retry = config.retry.unwrap_or(DEFAULT_RETRY);
+        let expire = config.expire.unwrap_or(DEFAULT_EXPIRE);
+        if success.priority == EMERGENCY_PRIORITY || failure.priority == EMERGENCY_PRIORITY {
+            if retry < 30 {
+                return Err(
+                    format!("Pushover retry must be at least 30 seconds, got {}", retry).into(),
+                );
+            }
+            if expire > 10800 {
+                return Err(format!(
+                    "Pushover expire must be at most 10800 seconds, got {}",
+                    expire
+                )
+                .into());
+            }
+        }
+
+        Ok(Self {
+            api_url: non_empty(&config.api_url)
+                .unwrap_or_else(|| DEFAULT_API_URL.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            token: config.token.clone(),
+            user: config.user.clone(),
+            device: non_empty(&config.device),
+            success,
+            failure,
+            retry,
+            expire,
+            url: non_empty(&config.url),
+            client: Client::new(),
+        })
+    }
+
+    fn format_report(report: &RunReport) -> String {
+        let exit_code = report
+            .exit_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let mut message = format!(
+            "Command: {}\nHost: {}\nDuration: {}, exit code {}",
+            report.command_line(),
+            report.host,
+            format_duration(report.duration),
+            exit_code
+        );
+        if report.error.is_some() {
+            message.push_str(&format!("\nError: {}", report.error_head(ERROR_LIMIT)));
+        }
+        let message: String = message.chars().take(MESSAGE_LIMIT).collect();
+
+        let budget = MESSAGE_LIMIT.saturating_sub(message.chars().count() + 2);
+        let output = report.output_tail(budget).trim_end();
+        if output.is_empty() {
+            message
+        } else {
+            format!("{}\n\n{}", message, output)
+        }
+    }
+
+    /// Form fields of a message, `alert` being the outcome's settings for
+    /// reports.
+    fn params(
+        &self,
+        title: Option<String>,
+        message: String,
+        alert: Option<&Alert>,
+    ) -> Vec<(&'static str, String)> {
+        let mut params = vec![
+            ("token", self.token.clone()),
+            ("user", self.user.clone()),
+            ("message", message),
+        ];
+        if let Some(title) = title {
+            params.push(("title", title));
+        }
+        if let Some(device) = &self.device {
+            params.push(("device", device.clone()));
+        }
+        if let Some(url) = &self.url {
+            params.push(("url", url.clone()));
+        }
+        if let Some(alert) = alert {
+            params.push(("priority", alert.priority.to_string()));
+            if alert.priority == EMERGENCY_PRIORITY {
+                params.push(("retry", self.retry.to_string()));
+                params.push(("expire", self.expire.to_string()));
+            }
+            if let Some(sound) = &alert.sound {
+                params.push(("sound", sound.clone()));
+            }
+        }
+        params
+    }
+
+    async fn post(&self, params: Vec<(&'static str, String)>) -> Result<(), Box<dyn Error>> {
+        let response = self
+            .client
+            .post(format!("{}/messages.json", self.api_url))
+            .form(&params)
+            .send()
+            .await?;
+
+        let status = response.status();
+        let text = response.text().await?;
+        let body: PushoverResponse = serde_json::from_str(&text).map_err(|e| {
+            error!("Failed to parse Pushover response: {}, body: {}", e, text);
+            format!("Invalid Pushover response: {}, body: {}", e, text)
+        })?;
+
+        if status.is_success() && body.status == 1 {
+            info!("Pushover message sent successfully");
+            Ok(())
+        } else {
+            let errors = body.errors.join(", ");
+            error!("Failed to send Pushover message: {} - {}", status, errors);
+            Err(format!("Failed to send Pushover message: {} - {}", status, errors).into())
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct PushoverResponse {
+    status: i64,
+    #[serde(default)]
+    errors: Vec<String>,
+}
+
+#[async_trait::async_trait]
+impl NotificationSender for PushoverNotifier {
+    async fn send(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        let message: String = message.chars().take(MESSAGE_LIMIT).collect();
+        self.post(self.params(None, message, None)).await
+    }
+
+    async fn send_report(&self, report: &RunReport) -> Result<(), Box<dyn Error>> {
+        let (alert, icon) = if report.success {
+            (&self.success, "✅")
+        } else {
+            (&self.failure, "❌")
+        };
+        let title = format!("{} {} {}", icon, report.command, report.status_text());
+        self.post(self.params(Some(title), Self::format_report(report), Some(alert)))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::test_server::{MockResponse, MockServer, RecordedRequest};
+
+    fn config(api_url: &str) -> PushoverConfig {
+        PushoverConfig {
+            api_url: Some(api_url.to_string()),
+            token: "app-token".to_string(),
+            user: "user-key".to_string(),
+            device: None,
+            success_priority: Some(-1),
+            failure_priority: Some(2),
+            success_sound: None,
+            failure_sound: Some("siren".to_string()),
+            retry: None,
+            expire: Some(600),
+            url: None,
+        }
+    }
+
+    fn report(success: bool, output: &str) -> RunReport {
+        let mut report = RunReport::new("make", vec!["test".to_string()]);
+        report.host = "build-1".to_string();
+        report.duration = std::time::Duration::from_secs(3);
+        report.exit_code = Some(if success { 0 } else { 2 });
+        report.success = success;
+        report.output = Some(output.to_string());
+        report
+    }
+
+    fn form(request: &RecordedRequest) -> Vec<(String, String)> {
+        reqwest::Url::parse(&format!("http://localhost/?{}", request.body_text()))
+            .unwrap()
+            .query_pairs()
+            .into_owned()
+            .collect()
+    }
+
+    #[test]
+    fn test_format_report() {
+        assert_eq!(
+            PushoverNotifier::format_report(&report(true, "ok\n")),
+            "Command: make test\nHost: build-1\nDuration: 3s, exit code 0\n\nok"
+        );
+        let long = PushoverNotifier::format_report(&report(false, &"x".repeat(5000)));
+        assert_eq!(long.chars().count(), MESSAGE_LIMIT);
+
+        let mut invalid = config("https://example.com");
+        invalid.retry = Some(10);
+        assert!(PushoverNotifier::from_config(&invalid).is_err());
+        invalid.retry = None;
+        invalid.expire = Some(20000);
+        assert!(PushoverNotifier::from_config(&invalid).is_err());
+        invalid.expire = None;
+        invalid.failure_priority = Some(3);
+        assert!(PushoverNotifier::from_config(&invalid).is_err());
+
+        // Without an emergency priority retry and expire are not used
+        let mut normal = config("https://example.com");
+        normal.failure_priority = Some(1);
+        normal.retry = Some(10);
+        normal.expire = Some(20000);
+        assert!(PushoverNotifier::from_config(&normal).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_emergency_failure() {
+        let server = MockServer::start(vec![MockResponse::json(
+            serde_json::json!({"status": 1, "request": "r-1", "receipt": "rc-1"}),
+        )])
+        .await;
+        let notifier = PushoverNotifier::from_config(&config(&server.url)).unwrap();
+
+        notifier.send_report(&report(false, "boom")).await.unwrap();
+        notifier.send_report(&report(true, "ok")).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/messages.json");
+        let failure = form(&requests[0]);
+        for field in [
+            ("token", "app-token"),
+            ("user", "user-key"),
+            ("title", "❌ make failed"),
+            ("priority", "2"),
+            ("retry", "60"),
+            ("expire", "600"),
+            ("sound", "siren"),
+        ] {
+            assert!(
+                failure.contains(&(field.0.to_string(), field.1.to_string())),
+                "missing {:?}",
+                field
+            );
+        }
+
+        let success = form(&requests[1]);
+        assert!(success.contains(&("priority".to_string(), "-1".to_string())));
+        assert!(!success
+            .iter()
+            .any(|(key, _)| key == "retry" || key == "sound"));
+    }
+
+    #[tokio::test]
+    async fn test_api_error() {
+        let server = MockServer::start(vec![MockResponse::new(
+            400,
+            r#"{"user":"invalid","errors":["user identifier is invalid"],"status":0,"request":"r-1"}"#,
+        )])
+        .await;
+        let err = PushoverNotifier::from_config(&config(&server.url))
+            .unwrap()
+            .send("hello")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("user identifier is invalid"));
+    }
+}
@@ -0,0 +1,372 @@
+//! Provider metadata shared by the sender factory, the editor and config
+//! validation.
+//!
+//! Each provider module exposes a `PROVIDER` descriptor listing its config
+//! fields. Field keys are the serde names of the provider's config struct,
+//! nested structs are addressed with dots (`smtp.host`), so values can be
+//! read and written generically through `serde_json`.
+
+use crate::config::NotificationConfigType;
+use crate::notifications::NotificationSender;
+use serde_json::{Map, Value};
+use std::error::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    String,
+    Bool,
+    Integer,
+    /// Comma separated list of strings.
+    List,
+    /// Comma separated `key=value` pairs, stored as `<param key value/>`.
+    Params,
+    /// Comma separated entries whose attributes, named by the slice, are
+    /// separated by `:`. Trailing attributes may be left out.
+    Records(&'static [&'static str]),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FieldSpec {
+    pub key: &'static str,
+    pub label: &'static str,
+    pub field_type: FieldType,
+    pub required: bool,
+    pub secret: bool,
+    pub default: Option<&'static str>,
+}
+
+impl FieldSpec {
+    pub const fn new(key: &'static str, label: &'static str, field_type: FieldType) -> Self {
+        Self {
+            key,
+            label,
+            field_type,
+            required: false,
+            secret: false,
+            default: None,
+        }
+    }
+
+    pub const fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    pub const fn secret(mut self) -> Self {
+        self.secret = true;
+        self
+    }
+
+    pub const fn default_value(mut self, value: &'static str) -> Self {
+        self.default = Some(value);
+        self
+    }
+}
+
+type BuildFn = fn(&NotificationConfigType) -> Result<Box<dyn NotificationSender>, Box<dyn Error>>;
+
+pub struct ProviderDescriptor {
+    /// Element name in the config file, also the cargo feature name.
+    pub name: &'static str,
+    pub display_name: &'static str,
+    pub fields: &'static [FieldSpec],
+    pub default_config: fn() -> NotificationConfigType,
+    pub build: BuildFn,
+}
+
+impl ProviderDescriptor {
+    /// A new config entry with every field default applied.
+    pub fn new_config(&self) -> NotificationConfigType {
+        let mut config = (self.default_config)();
+        for field in self.fields {
+            if let Some(default) = field.default {
+                if let Err(e) = self.set_field(&mut config, field, default) {
+                    panic!("invalid default for {}.{}: {}", self.name, field.key, e);
+                }
+            }
+        }
+        config
+    }
+
+    /// Current value of `field` rendered as editable text.
+    pub fn get_field(&self, config: &NotificationConfigType, field: &FieldSpec) -> String {
+        let value = serde_json::to_value(config).unwrap_or(Value::Null);
+        let mut current = &value[self.name];
+        for part in field.key.split('.') {
+            current = &current[part];
+        }
+        value_to_text(current, field.field_type)
+    }
+
+    /// Like `get_field`, but hides secrets.
+    pub fn display_field(&self, config: &NotificationConfigType, field: &FieldSpec) -> String {
+        let value = self.get_field(config, field);
+        if field.secret && !value.is_empty() {
+            "********".to_string()
+        } else {
+            value
+        }
+    }
+
+    pub fn set_field(
+        &self,
+        config: &mut NotificationConfigType,
+        field: &FieldSpec,
+        text: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let new_value = text_to_value(text.trim(), field.field_type)
+            .map_err(|e| format!("{}: {}", field.label, e))?;
+
+        match self.set_field_value(config, field, new_value.clone()) {
+            // Empty input maps to `None`, which non optional strings reject
+            Err(_) if new_value.is_null() && field.field_type == FieldType::String => {
+                self.set_field_value(config, field, Value::String(String::new()))
+            }
+            result => result,
+        }
+    }
+
+    fn set_field_value(
+        &self,
+        config: &mut NotificationConfigType,
+        field: &FieldSpec,
+        new_value: Value,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut value = serde_json::to_value(&*config)?;
+        let mut current = &mut value[self.name];
+        for part in field.key.split('.') {
+            current = &mut current[part];
+        }
+        *current = new_value;
+        *config = serde_json::from_value(value).map_err(|e| format!("{}: {}", field.label, e))?;
+        Ok(())
+    }
+
+    /// Problems that prevent `config` from being used, one message each.
+    pub fn validate(&self, config: &NotificationConfigType) -> Vec<String> {
+        self.fields
+            .iter()
+            .filter(|field| field.required && self.get_field(config, field).is_empty())
+            .map(|field| format!("{}: {} is required", self.display_name, field.label))
+            .collect()
+    }
+}
+
+fn value_to_text(value: &Value, field_type: FieldType) -> String {
+    match (value, field_type) {
+        (Value::Null, _) => String::new(),
+        (Value::String(s), _) => s.clone(),
+        (Value::Array(items), FieldType::Params) => items
+            .iter()
+            .map(|item| {
+                format!(
+                    "{}={}",
+                    item["@key"].as_str().unwrap_or_default(),
+                    item["@value"].as_str().unwrap_or_default()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+        (Value::Array(items), FieldType::Records(keys)) => items
+            .iter()
+            .map(|item| {
+                keys.iter()
+                    .map(|key| value_to_text(&item[*key], FieldType::String))
+                    .collect::<Vec<_>>()
+                    .join(":")
+                    .trim_end_matches(':')
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+        (Value::Array(items), _) => items
+            .iter()
+            .map(|item| value_to_text(item, FieldType::String))
+            .collect::<Vec<_>>()
+            .join(", "),
+        (other, _) => other.to_string(),
+    }
+}
+
+fn text_to_value(text: &str, field_type: FieldType) -> Result<Value, String> {
+    if text.is_empty() {
+        return Ok(match field_type {
+            FieldType::List | FieldType::Params | FieldType::Records(_) => Value::Array(Vec::new()),
+            _ => Value::Null,
+        });
+    }
+
+    match field_type {
+        FieldType::String => Ok(Value::String(text.to_string())),
+        FieldType::Bool => match text.to_lowercase().as_str() {
+            "true" | "yes" | "1" => Ok(Value::Bool(true)),
+            "false" | "no" | "0" => Ok(Value::Bool(false)),
+            _ => Err(format!("'{}' is not a boolean", text)),
+        },
+        FieldType::Integer => text
+            .parse::<i64>()
+            .map(Value::from)
+            .map_err(|_| format!("'{}' is not an integer", text)),
+        FieldType::List => Ok(Value::Array(
+            split_list(text)
+                .map(|s| Value::String(s.to_string()))
+                .collect(),
+        )),
+        FieldType::Params => split_list(text)
+            .map(|pair| {
+                let (key, value) = pair
+                    .split_once('=')
+                    .ok_or_else(|| format!("'{}' is not a key=value pair", pair))?;
+                let mut param = Map::new();
+                param.insert("@key".to_string(), Value::String(key.trim().to_string()));
+                param.insert(
+                    "@value".to_string(),
+                    Value::String(value.trim().to_string()),
+                );
+                Ok(Value::Object(param))
+            })
+            .collect::<Result<Vec<_>, String>>()
+            .map(Value::Array),
+        FieldType::Records(keys) => Ok(Value::Array(
+            split_list(text)
+                .map(|entry| {
+                    let record = keys
+                        .iter()
+                        .zip(entry.splitn(keys.len(), ':'))
+                        .filter(|(_, value)| !value.trim().is_empty())
+                        .map(|(key, value)| {
+                            (key.to_string(), Value::String(value.trim().to_string()))
+                        })
+                        .collect();
+                    Value::Object(record)
+                })
+                .collect(),
+        )),
+    }
+}
+
+fn split_list(text: &str) -> impl Iterator<Item = &str> {
+    text.split(',').map(str::trim).filter(|s| !s.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{EmailConfig, LarkConfig, PluginConfig};
+
+    const EMAIL: ProviderDescriptor = ProviderDescriptor {
+        name: "email",
+        display_name: "Email",
+        fields: &[
+            FieldSpec::new("from", "From", FieldType::String).required(),
+            FieldSpec::new("subject", "Subject", FieldType::String),
+            FieldSpec::new("smtp.port", "SMTP Port", FieldType::Integer).default_value("587"),
+            FieldSpec::new("smtp.password", "SMTP Password", FieldType::String).secret(),
+            FieldSpec::new("smtp.auth", "SMTP Auth", FieldType::Bool),
+        ],
+        default_config: || NotificationConfigType::Email(EmailConfig::default()),
+        build: |_| Err("unused".into()),
+    };
+
+    #[test]
+    fn test_get_and_set_fields() {
+        let mut config = EMAIL.new_config();
+        let [from, subject, port, password, auth] = EMAIL.fields else {
+            unreachable!()
+        };
+        assert_eq!(EMAIL.get_field(&config, port), "587");
+        assert_eq!(EMAIL.validate(&config), vec!["Email: From is required"]);
+
+        EMAIL
+            .set_field(&mut config, from, "ops@example.com")
+            .unwrap();
+        EMAIL.set_field(&mut config, subject, "Build done").unwrap();
+        EMAIL.set_field(&mut config, password, "hunter2").unwrap();
+        EMAIL.set_field(&mut config, auth, "yes").unwrap();
+        assert!(EMAIL.validate(&config).is_empty());
+        assert_eq!(EMAIL.display_field(&config, password), "********");
+        assert_eq!(EMAIL.get_field(&config, password), "hunter2");
+
+        match &config {
+            NotificationConfigType::Email(email) => {
+                assert_eq!(email.from, "ops@example.com");
+                assert_eq!(email.subject.as_deref(), Some("Build done"));
+                assert_eq!(email.smtp.port, 587);
+                assert_eq!(email.smtp.auth, Some(true));
+            }
+            other => panic!("unexpected config: {:?}", other),
+        }
+
+        // Empty input clears optional and non optional strings alike
+        EMAIL.set_field(&mut config, subject, "").unwrap();
+        EMAIL.set_field(&mut config, from, "").unwrap();
+        assert_eq!(EMAIL.get_field(&config, subject), "");
+        assert_eq!(EMAIL.get_field(&config, from), "");
+
+        assert!(EMAIL.set_field(&mut config, port, "many").is_err());
+        assert!(EMAIL.set_field(&mut config, auth, "maybe").is_err());
+    }
+
+    #[test]
+    fn test_params_field() {
+        let field = FieldSpec::new("param", "Params", FieldType::Params);
+        let plugin = ProviderDescriptor {
+            name: "plugin",
+            display_name: "Plugin",
+            fields: &[],
+            default_config: || NotificationConfigType::Plugin(PluginConfig::default()),
+            build: |_| Err("unused".into()),
+        };
+
+        let mut config = plugin.new_config();
+        plugin
+            .set_field(&mut config, &field, "room=ops, token = abc")
+            .unwrap();
+        assert_eq!(plugin.get_field(&config, &field), "room=ops, token=abc");
+        assert!(plugin.set_field(&mut config, &field, "room").is_err());
+    }
+
+    #[test]
+    fn test_optional_and_list_fields() {
+        let field = FieldSpec::new("at", "At", FieldType::String);
+        let lark = ProviderDescriptor {
+            name: "lark",
+            display_name: "Lark",
+            fields: &[],
+            default_config: || NotificationConfigType::Lark(LarkConfig::default()),
+            build: |_| Err("unused".into()),
+        };
+        let mut config = lark.new_config();
+        lark.set_field(&mut config, &field, "ou_123").unwrap();
+        assert_eq!(lark.get_field(&config, &field), "ou_123");
+        lark.set_field(&mut config, &field, "").unwrap();
+        match &config {
+            NotificationConfigType::Lark(lark) => assert_eq!(lark.at, None),
+            other => panic!("unexpected config: {:?}", other),
+        }
+
+        assert_eq!(
+            text_to_value("a, b,,c", FieldType::List).unwrap(),
+            serde_json::json!(["a", "b", "c"])
+        );
+    }
+
+    #[test]
+    fn test_records_field() {
+        let records = FieldType::Records(&["@type", "@id", "@when"]);
+        let value =
+            text_to_value("email:a@example.com, all::failure, open_id:ou_1", records).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!([
+                {"@type": "email", "@id": "a@example.com"},
+                {"@type": "all", "@when": "failure"},
+                {"@type": "open_id", "@id": "ou_1"}
+            ])
+        );
+        assert_eq!(
+            value_to_text(&value, records),
+            "email:a@example.com, all::failure, open_id:ou_1"
+        );
+    }
+}
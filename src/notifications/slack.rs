@@ -0,0 +1,545 @@
+use crate::config::{NotificationConfigType, SlackConfig, SlackMentionConfig};
+use crate::notifications::registry::{FieldSpec, FieldType, ProviderDescriptor};
+use crate::notifications::{config_mismatch, MentionWhen, NotificationSender};
+use crate::report::{format_duration, RunContext, RunReport};
+use log::{error, info};
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::sync::Mutex;
+
+pub const PROVIDER: ProviderDescriptor = ProviderDescriptor {
+    name: "slack",
+    display_name: "Slack",
+    fields: &[
+        FieldSpec::new("webhook_url", "Webhook URL", FieldType::String),
+        FieldSpec::new("token", "Bot Token", FieldType::String).secret(),
+        FieldSpec::new("channel", "Channel", FieldType::String),
+        FieldSpec::new(
+            "mention",
+            "Mentions (type:id[:when])",
+            FieldType::Records(&["@type", "@id", "@when"]),
+        ),
+        FieldSpec::new("api_url", "API URL", FieldType::String).default_value(DEFAULT_API_URL),
+    ],
+    default_config: || NotificationConfigType::Slack(SlackConfig::default()),
+    build: |config| match config {
+        NotificationConfigType::Slack(config) => Ok(Box::new(SlackNotifier::from_config(config)?)),
+        other => Err(config_mismatch(&PROVIDER, other)),
+    },
+};
+
+pub const DEFAULT_API_URL: &str = "https://slack.com/api";
+
+/// Characters of command output shown in the report, Slack rejects section
+/// texts over 3000 characters.
+const OUTPUT_LIMIT: usize = 2800;
+/// Slack truncates header blocks at 150 characters.
+const HEADER_LIMIT: usize = 150;
+
+const SUCCESS_COLOR: &str = "#2eb886";
+const FAILURE_COLOR: &str = "#e01e5a";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MentionTarget {
+    User(String),
+    Group(String),
+    Here,
+    Channel,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlackMention {
+    pub target: MentionTarget,
+    pub when: MentionWhen,
+}
+
+impl SlackMention {
+    pub fn from_config(config: &SlackMentionConfig) -> Result<Self, Box<dyn Error>> {
+        let id = config.id.clone();
+        let target = match config.mention_type.as_deref().unwrap_or_default() {
+            "" | "user" => MentionTarget::User(id),
+            "group" => MentionTarget::Group(id),
+            "here" => MentionTarget::Here,
+            "channel" => MentionTarget::Channel,
+            other => return Err(format!("Unknown Slack mention type '{}'", other).into()),
+        };
+        if matches!(target, MentionTarget::User(_) | MentionTarget::Group(_))
+            && config.id.is_empty()
+        {
+            return Err("Slack mention is missing an id".into());
+        }
+
+        let when = config.when.as_deref().unwrap_or_default();
+        let when = MentionWhen::parse(when)
+            .ok_or_else(|| format!("Unknown Slack mention condition '{}'", when))?;
+        Ok(Self { target, when })
+    }
+
+    fn tag(&self) -> String {
+        match &self.target {
+            MentionTarget::User(id) => format!("<@{}>", id),
+            MentionTarget::Group(id) => format!("<!subteam^{}>", id),
+            MentionTarget::Here => "<!here>".to_string(),
+            MentionTarget::Channel => "<!channel>".to_string(),
+        }
+    }
+}
+
+/// Where messages are posted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Destination {
+    Webhook(String),
+    /// `chat.postMessage` with a bot token, which also allows threading.
+    Api {
+        api_url: String,
+        token: String,
+        channel: String,
+    },
+}
+
+pub struct SlackNotifier {
+    destination: Destination,
+    mentions: Vec<SlackMention>,
+    /// `ts` of the start message results are threaded under.
+    thread_ts: Mutex<Option<String>>,
+    client: Client,
+}
+
+impl SlackNotifier {
+    pub fn from_config(config: &SlackConfig) -> Result<Self, Box<dyn Error>> {
+        let non_empty = |value: &Option<String>| value.clone().filter(|v| !v.is_empty());
+        let destination = match (non_empty(&config.token), non_empty(&config.webhook_url)) {
+            (Some(token), _) => Destination::Api {
+                api_url: non_empty(&config.api_url)
+                    .unwrap_or_else(|| DEFAULT_API_URL.to_string())
+                    .trim_end_matches('/')
+                    .to_string(),
+                token,
+                channel: non_empty(&config.channel)
+                    .ok_or("Slack channel is required with a bot token")?,
+            },
+            (None, Some(webhook_url)) => Destination::Webhook(webhook_url),
+            (None, None) => return Err("Slack needs a webhook URL or a bot token".into()),
+        };
+
+        let mentions = config
+            .mentions
+            .iter()
+            .map(SlackMention::from_config)
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            destination,
+            mentions,
+            thread_ts: Mutex::new(None),
+            client: Client::new(),
+        })
+    }
+
+    fn mention_tags(&self, success: Option<bool>) -> String {
+        self.mentions
+            .iter()
+            .filter(|mention| mention.when.applies(success))
+            .map(SlackMention::tag)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn with_mentions(&self, text: &str, success: Option<bool>) -> String {
+        let tags = self.mention_tags(success);
+        if tags.is_empty() {
+            text.to_string()
+        } else {
+            format!("{} {}", tags, text)
+        }
+    }
+
+    fn message_payload(&self, message: &str) -> Value {
+        json!({"text": self.with_mentions(&escape(message), None)})
+    }
+
+    /// Block Kit rendering of `report`, wrapped in an attachment for the
+    /// status color. The top level text carries the mentions and serves as
+    /// the notification fallback.
+    fn report_payload(&self, report: &RunReport) -> Value {
+        let (color, icon) = if report.success {
+            (SUCCESS_COLOR, "✅")
+        } else {
+            (FAILURE_COLOR, "❌")
+        };
+        let title = format!("{} {} {}", icon, report.command, report.status_text());
+        let exit_code = report
+            .exit_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let field = |name: &str, value: &str| {
+            let text = format!("*{}*\n{}", name, value);
+            json!({"type": "mrkdwn", "text": text})
+        };
+
+        let mut blocks = vec![
+            json!({
+                "type": "header",
+                "text": {
+                    "type": "plain_text",
+                    "text": title.chars().take(HEADER_LIMIT).collect::<String>(),
+                    "emoji": true
+                }
+            }),
+            json!({
+                "type": "section",
+                "fields": [
+                    field("Command", &format!("`{}`", escape(&report.command_line()))),
+                    field("Host", &escape(&report.host)),
+                    field("Duration", &format_duration(report.duration)),
+                    field("Exit Code", &exit_code),
+                ]
+            }),
+        ];
+        if report.error.is_some() {
+            let error = escape_head(report.error_head(OUTPUT_LIMIT), OUTPUT_LIMIT);
+            blocks.push(json!({
+                "type": "section",
+                "text": {"type": "mrkdwn", "text": format!("*Error*\n{}", error)}
+            }));
+        }
+
+        let output = report.output_tail(OUTPUT_LIMIT).trim_end();
+        if !output.is_empty() {
+            // A zero width space keeps fences in the output from closing the block
+            let output = escape(output).replace("```", "`\u{200b}``");
+            blocks.push(json!({
+                "type": "section",
+                "text": {
+                    "type": "mrkdwn",
+                    "text": format!("```\n{}\n```", entity_safe_tail(&output, OUTPUT_LIMIT))
+                }
+            }));
+        }
+
+        json!({
+            "text": self.with_mentions(&escape(&title), Some(report.success)),
+            "attachments": [{"color": color, "blocks": blocks}]
+        })
+    }
+
+    /// Posts `payload`, returning the `ts` of the message when posted
+    /// through the API.
+    async fn post(&self, mut payload: Value) -> Result<Option<String>, Box<dyn Error>> {
+        let request = match &self.destination {
+            Destination::Webhook(url) => self.client.post(url),
+            Destination::Api {
+                api_url,
+                token,
+                channel,
+            } => {
+                payload["channel"] = json!(channel);
+                self.client
+                    .post(format!("{}/chat.postMessage", api_url))
+                    .bearer_auth(token)
+            }
+        };
+
+        let response = request.json(&payload).send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            error!("Failed to send Slack message: {} - {}", status, text);
+            return Err(format!("Failed to send Slack message: {} - {}", status, text).into());
+        }
+
+        // Webhooks answer with a plain `ok`, the API always with JSON
+        if let Destination::Webhook(_) = self.destination {
+            info!("Slack message sent successfully");
+            return Ok(None);
+        }
+        let body: Value = serde_json::from_str(&text)
+            .map_err(|e| format!("Invalid Slack API response: {}, body: {}", e, text))?;
+        if body["ok"] != json!(true) {
+            let error = body["error"].as_str().unwrap_or("unknown error");
+            error!("Failed to send Slack message: {}", error);
+            return Err(format!("Failed to send Slack message: {}", error).into());
+        }
+        info!("Slack message sent successfully");
+        Ok(body["ts"].as_str().map(str::to_string))
+    }
+}
+
+/// Escapes the characters Slack treats as control sequences.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// `text` escaped and cut to its first `limit` characters, escaping can make
+/// it several times longer. Entities are never split.
+fn escape_head(text: &str, limit: usize) -> String {
+    let escaped = escape(text);
+    let end = escaped
+        .char_indices()
+        .nth(limit)
+        .map_or(escaped.len(), |(i, _)| i);
+    let mut head = &escaped[..end];
+    if let Some(amp) = head.rfind('&') {
+        if !head[amp..].contains(';') {
+            head = &head[..amp];
+        }
+    }
+    head.to_string()
+}
+
+/// Last `limit` characters of the already escaped `escaped`, starting after
+/// any entity the cut would split.
+fn entity_safe_tail(escaped: &str, limit: usize) -> &str {
+    let skip = escaped.chars().count().saturating_sub(limit);
+    let start = escaped
+        .char_indices()
+        .nth(skip)
+        .map_or(escaped.len(), |(i, _)| i);
+    let (dropped, tail) = escaped.split_at(start);
+    match dropped.rfind('&') {
+        Some(amp) if !dropped[amp..].contains(';') => {
+            tail.split_once(';').map_or("", |(_, rest)| rest)
+        }
+        _ => tail,
+    }
+}
+
+#[async_trait::async_trait]
+impl NotificationSender for SlackNotifier {
+    async fn send(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.post(self.message_payload(message)).await.map(|_| ())
+    }
+
+    async fn send_report(&self, report: &RunReport) -> Result<(), Box<dyn Error>> {
+        self.post(self.report_payload(report)).await.map(|_| ())
+    }
+
+    async fn on_start(&self, context: &RunContext) -> Result<(), Box<dyn Error>> {
+        if !context.notify_on_start {
+            return Ok(());
+        }
+        let ts = self
+            .post(self.message_payload(&context.start_message()))
+            .await?;
+        *self.thread_ts.lock().unwrap() = ts;
+        Ok(())
+    }
+
+    async fn on_finish(
+        &self,
+        _context: &RunContext,
+        report: &RunReport,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut payload = self.report_payload(report);
+        if let Some(ts) = self.thread_ts.lock().unwrap().take() {
+            payload["thread_ts"] = json!(ts);
+        }
+        self.post(payload).await.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::test_server::{MockResponse, MockServer};
+
+    fn report(success: bool) -> RunReport {
+        let mut report = RunReport::new("make", vec!["test".to_string()]);
+        report.host = "build-1".to_string();
+        report.duration = std::time::Duration::from_secs(3);
+        report.exit_code = Some(if success { 0 } else { 2 });
+        report.success = success;
+        report.output = Some("a < b\n```\n".to_string());
+        if !success {
+            report.error = Some("Command failed: boom".to_string());
+        }
+        report
+    }
+
+    fn mention(mention_type: &str, id: &str, when: &str) -> SlackMentionConfig {
+        SlackMentionConfig {
+            mention_type: Some(mention_type.to_string()),
+            id: id.to_string(),
+            when: Some(when.to_string()),
+        }
+    }
+
+    fn api_config(server: &MockServer) -> SlackConfig {
+        SlackConfig {
+            token: Some("xoxb-1".to_string()),
+            channel: Some("C123".to_string()),
+            api_url: Some(format!("{}/api/", server.url)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_from_config() {
+        assert!(SlackNotifier::from_config(&SlackConfig::default()).is_err());
+        assert!(SlackNotifier::from_config(&SlackConfig {
+            token: Some("xoxb-1".to_string()),
+            ..Default::default()
+        })
+        .is_err());
+
+        let notifier = SlackNotifier::from_config(&SlackConfig {
+            webhook_url: Some("https://hooks.slack.com/services/x".to_string()),
+            token: Some("xoxb-1".to_string()),
+            channel: Some("#ops".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            notifier.destination,
+            Destination::Api {
+                api_url: DEFAULT_API_URL.to_string(),
+                token: "xoxb-1".to_string(),
+                channel: "#ops".to_string(),
+            }
+        );
+
+        let invalid = SlackConfig {
+            webhook_url: Some("https://hooks.slack.com/services/x".to_string()),
+            mentions: vec![mention("group", "", "always")],
+            ..Default::default()
+        };
+        assert!(SlackNotifier::from_config(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_report_payload() {
+        let notifier = SlackNotifier::from_config(&SlackConfig {
+            webhook_url: Some("https://hooks.slack.com/services/x".to_string()),
+            mentions: vec![
+                mention("user", "U1", "always"),
+                mention("group", "S1", "failure"),
+                mention("here", "", "success"),
+            ],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let success = notifier.report_payload(&report(true));
+        assert_eq!(success["text"], "<@U1> <!here> ✅ make succeeded");
+        let attachment = &success["attachments"][0];
+        assert_eq!(attachment["color"], SUCCESS_COLOR);
+        assert_eq!(attachment["blocks"][0]["text"]["text"], "✅ make succeeded");
+        assert_eq!(
+            attachment["blocks"][1]["fields"][0]["text"],
+            "*Command*\n`make test`"
+        );
+        assert_eq!(
+            attachment["blocks"][2]["text"]["text"],
+            "```\na &lt; b\n`\u{200b}``\n```"
+        );
+
+        let failure = notifier.report_payload(&report(false));
+        assert_eq!(failure["text"], "<@U1> <!subteam^S1> ❌ make failed");
+        assert_eq!(failure["attachments"][0]["color"], FAILURE_COLOR);
+        assert_eq!(
+            failure["attachments"][0]["blocks"][2]["text"]["text"],
+            "*Error*\nCommand failed: boom"
+        );
+
+        assert_eq!(notifier.message_payload("hi"), json!({"text": "<@U1> hi"}));
+    }
+
+    #[test]
+    fn test_escaped_limits() {
+        let notifier = SlackNotifier::from_config(&SlackConfig {
+            webhook_url: Some("https://hooks.slack.com/services/x".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        let mut report = report(false);
+        report.error = Some("<".repeat(5000));
+        report.output = Some("a<".repeat(5000));
+
+        let payload = notifier.report_payload(&report);
+        let blocks = payload["attachments"][0]["blocks"].as_array().unwrap();
+        let error = blocks[2]["text"]["text"].as_str().unwrap();
+        let output = blocks[3]["text"]["text"].as_str().unwrap();
+        for text in [error, output] {
+            assert!(text.chars().count() <= 3000);
+        }
+        assert!(error.ends_with("&lt;"));
+        assert!(output.starts_with("```\na&lt;") || output.starts_with("```\n&lt;"));
+        assert!(output.ends_with("a&lt;\n```"));
+
+        assert_eq!(escape_head("a<b", 3), "a");
+        assert_eq!(entity_safe_tail("&lt;b&gt;", 5), "b&gt;");
+        assert_eq!(entity_safe_tail("&lt;b&gt;", 6), "b&gt;");
+    }
+
+    #[tokio::test]
+    async fn test_webhook() {
+        let server = MockServer::start(vec![MockResponse::new(200, "ok")]).await;
+        let notifier = SlackNotifier::from_config(&SlackConfig {
+            webhook_url: Some(format!("{}/services/T/B/x", server.url)),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let report = report(true);
+        let mut context = RunContext::new("ci", &report);
+        context.notify_on_start = true;
+        notifier.on_start(&context).await.unwrap();
+        notifier.on_finish(&context, &report).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].path, "/services/T/B/x");
+        assert!(requests[0].json()["text"]
+            .as_str()
+            .unwrap()
+            .contains("started on host build-1"));
+        // Webhooks cannot thread
+        assert!(requests[1].json().get("thread_ts").is_none());
+        assert!(requests[1].json().get("channel").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_thread_replies() {
+        let server = MockServer::start(vec![MockResponse::json(
+            json!({"ok": true, "channel": "C123", "ts": "1700000000.000100"}),
+        )])
+        .await;
+        let notifier = SlackNotifier::from_config(&api_config(&server)).unwrap();
+
+        let report = report(false);
+        let mut context = RunContext::new("ci", &report);
+        context.notify_on_start = true;
+        notifier.on_start(&context).await.unwrap();
+        notifier.on_finish(&context, &report).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].path, "/api/chat.postMessage");
+        assert_eq!(requests[0].header("authorization"), Some("Bearer xoxb-1"));
+        assert_eq!(requests[0].json()["channel"], "C123");
+        let result = requests[1].json();
+        assert_eq!(result["thread_ts"], "1700000000.000100");
+        assert_eq!(result["attachments"][0]["color"], FAILURE_COLOR);
+
+        // Without a start message the report is posted to the channel
+        notifier.on_finish(&context, &report).await.unwrap();
+        assert!(server.requests()[2].json().get("thread_ts").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_api_error() {
+        let server = MockServer::start(vec![MockResponse::json(
+            json!({"ok": false, "error": "channel_not_found"}),
+        )])
+        .await;
+        let err = SlackNotifier::from_config(&api_config(&server))
+            .unwrap()
+            .send("hello")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("channel_not_found"));
+    }
+}
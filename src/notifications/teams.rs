@@ -0,0 +1,265 @@
+use crate::config::{NotificationConfigType, TeamsConfig};
+use crate::notifications::registry::{FieldSpec, FieldType, ProviderDescriptor};
+use crate::notifications::{config_mismatch, NotificationSender};
+use crate::report::{format_duration, RunReport};
+use log::{error, info};
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::error::Error;
+
+pub const PROVIDER: ProviderDescriptor = ProviderDescriptor {
+    name: "teams",
+    display_name: "Microsoft Teams",
+    fields: &[
+        FieldSpec::new("webhook_url", "Webhook URL", FieldType::String).required(),
+        FieldSpec::new("log_url", "Log URL", FieldType::String),
+    ],
+    default_config: || NotificationConfigType::Teams(TeamsConfig::default()),
+    build: |config| match config {
+        NotificationConfigType::Teams(config) => Ok(Box::new(TeamsNotifier::new(config))),
+        other => Err(config_mismatch(&PROVIDER, other)),
+    },
+};
+
+/// Characters of command output shown in a card, Teams rejects messages
+/// over 28KB.
+const CARD_OUTPUT_LIMIT: usize = 4000;
+/// Characters of the error shown in a card, on top of the output.
+const CARD_ERROR_LIMIT: usize = 2000;
+
+pub struct TeamsNotifier {
+    webhook_url: String,
+    log_url: Option<String>,
+    client: Client,
+}
+
+impl TeamsNotifier {
+    pub fn new(config: &TeamsConfig) -> Self {
+        Self {
+            webhook_url: config.webhook_url.clone(),
+            log_url: config.log_url.clone().filter(|url| !url.is_empty()),
+            client: Client::new(),
+        }
+    }
+
+    /// Wraps Adaptive Card `body` elements and `actions` into a webhook
+    /// message.
+    fn card_message(body: Vec<Value>, actions: Vec<Value>) -> Value {
+        let mut card = json!({
+            "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+            "type": "AdaptiveCard",
+            "version": "1.4",
+            "msteams": {"width": "Full"},
+            "body": body,
+        });
+        if !actions.is_empty() {
+            card["actions"] = json!(actions);
+        }
+
+        json!({
+            "type": "message",
+            "attachments": [{
+                "contentType": "application/vnd.microsoft.card.adaptive",
+                "contentUrl": null,
+                "content": card
+            }]
+        })
+    }
+
+    fn build_card(&self, report: &RunReport) -> Value {
+        let (style, icon) = if report.success {
+            ("good", "✅")
+        } else {
+            ("attention", "❌")
+        };
+        let exit_code = report
+            .exit_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let fact = |title: &str, value: &str| json!({"title": title, "value": value});
+
+        let mut body = vec![
+            json!({
+                "type": "Container",
+                "style": style,
+                "bleed": true,
+                "items": [{
+                    "type": "TextBlock",
+                    "text": format!("{} {} {}", icon, report.command, report.status_text()),
+                    "size": "Large",
+                    "weight": "Bolder",
+                    "wrap": true
+                }]
+            }),
+            json!({
+                "type": "FactSet",
+                "facts": [
+                    fact("Command", &report.command_line()),
+                    fact("Host", &report.host),
+                    fact("Duration", &format_duration(report.duration)),
+                    fact("Exit Code", &exit_code),
+                ]
+            }),
+        ];
+        if report.error.is_some() {
+            body.push(json!({
+                "type": "TextBlock",
+                "text": report.error_head(CARD_ERROR_LIMIT),
+                "color": "Attention",
+                "wrap": true
+            }));
+        }
+
+        let output = report.output_tail(CARD_OUTPUT_LIMIT).trim_end();
+        if !output.is_empty() {
+            body.push(json!({
+                "type": "Container",
+                "separator": true,
+                "items": [
+                    {"type": "TextBlock", "text": "Output", "weight": "Bolder"},
+                    {"type": "TextBlock", "text": output, "fontType": "Monospace", "wrap": true}
+                ]
+            }));
+        }
+
+        let actions = self
+            .log_url
+            .iter()
+            .map(|url| json!({"type": "Action.OpenUrl", "title": "View log", "url": url}))
+            .collect();
+        Self::card_message(body, actions)
+    }
+
+    async fn post(&self, payload: Value) -> Result<(), Box<dyn Error>> {
+        let response = self
+            .client
+            .post(&self.webhook_url)
+            .json(&payload)
+            .send()
+            .await?;
+
+        // Classic webhooks answer 200, Workflows 202
+        let status = response.status();
+        if status.is_success() {
+            info!("Teams message sent successfully");
+            Ok(())
+        } else {
+            let text = response.text().await?;
+            error!("Failed to send Teams message: {} - {}", status, text);
+            Err(format!("Failed to send Teams message: {} - {}", status, text).into())
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl NotificationSender for TeamsNotifier {
+    async fn send(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        let body = vec![json!({"type": "TextBlock", "text": message, "wrap": true})];
+        self.post(Self::card_message(body, Vec::new())).await
+    }
+
+    async fn send_report(&self, report: &RunReport) -> Result<(), Box<dyn Error>> {
+        self.post(self.build_card(report)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::test_server::{MockResponse, MockServer};
+
+    fn report(success: bool) -> RunReport {
+        let mut report = RunReport::new("make", vec!["test".to_string()]);
+        report.host = "build-1".to_string();
+        report.duration = std::time::Duration::from_secs(3);
+        report.exit_code = Some(if success { 0 } else { 2 });
+        report.success = success;
+        report.output = Some("all good\n".to_string());
+        if !success {
+            report.error = Some("Command failed: boom".to_string());
+        }
+        report
+    }
+
+    fn notifier(webhook_url: &str, log_url: Option<&str>) -> TeamsNotifier {
+        TeamsNotifier::new(&TeamsConfig {
+            webhook_url: webhook_url.to_string(),
+            log_url: log_url.map(str::to_string),
+        })
+    }
+
+    #[test]
+    fn test_build_card() {
+        let message = notifier("https://example.com/hook", None).build_card(&report(true));
+        assert_eq!(message["type"], "message");
+        let attachment = &message["attachments"][0];
+        assert_eq!(
+            attachment["contentType"],
+            "application/vnd.microsoft.card.adaptive"
+        );
+
+        let card = &attachment["content"];
+        assert_eq!(card["type"], "AdaptiveCard");
+        assert_eq!(card["body"][0]["style"], "good");
+        assert_eq!(card["body"][0]["items"][0]["text"], "✅ make succeeded");
+        assert_eq!(
+            card["body"][1]["facts"],
+            json!([
+                {"title": "Command", "value": "make test"},
+                {"title": "Host", "value": "build-1"},
+                {"title": "Duration", "value": "3s"},
+                {"title": "Exit Code", "value": "0"}
+            ])
+        );
+        assert_eq!(card["body"][2]["items"][1]["text"], "all good");
+        assert_eq!(card["body"][2]["items"][1]["fontType"], "Monospace");
+        assert!(card.get("actions").is_none());
+    }
+
+    #[test]
+    fn test_build_failure_card() {
+        let message = notifier("https://example.com/hook", Some("https://ci.example.com/1"))
+            .build_card(&report(false));
+        let card = &message["attachments"][0]["content"];
+        assert_eq!(card["body"][0]["style"], "attention");
+        assert_eq!(card["body"][0]["items"][0]["text"], "❌ make failed");
+        assert_eq!(card["body"][2]["text"], "Command failed: boom");
+        assert_eq!(card["body"][2]["color"], "Attention");
+        assert_eq!(
+            card["actions"],
+            json!([{"type": "Action.OpenUrl", "title": "View log", "url": "https://ci.example.com/1"}])
+        );
+
+        let mut long = report(false);
+        long.error = Some("e".repeat(50000));
+        long.output = Some("o".repeat(50000));
+        let message = notifier("https://example.com/hook", None).build_card(&long);
+        let card = &message["attachments"][0]["content"];
+        assert_eq!(
+            card["body"][2]["text"].as_str().unwrap().len(),
+            CARD_ERROR_LIMIT
+        );
+        assert!(message.to_string().len() < 28 * 1024);
+    }
+
+    #[tokio::test]
+    async fn test_post_to_webhook() {
+        let server = MockServer::start(vec![MockResponse::new(202, "")]).await;
+        let notifier = notifier(&format!("{}/workflows/run", server.url), None);
+        notifier.send("hello").await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/workflows/run");
+        assert_eq!(
+            requests[0].json()["attachments"][0]["content"]["body"][0]["text"],
+            "hello"
+        );
+
+        let server = MockServer::start(vec![MockResponse::new(400, "Bad payload")]).await;
+        let err = self::notifier(&server.url, None)
+            .send_report(&report(true))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Bad payload"));
+    }
+}
@@ -1,24 +1,296 @@
-use crate::notifications::NotificationSender;
-use reqwest::Client;
-use serde_json::json;
+use crate::config::{NotificationConfigType, TelegramConfig};
+use crate::notifications::registry::{FieldSpec, FieldType, ProviderDescriptor};
+use crate::notifications::{config_mismatch, NotificationSender};
+use crate::report::{format_duration, RunContext, RunProgress, RunReport};
+use reqwest::multipart::{Form, Part};
+use reqwest::{Client, Response};
+use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::error::Error;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const PROVIDER: ProviderDescriptor = ProviderDescriptor {
+    name: "telegram",
+    display_name: "Telegram",
+    fields: &[
+        FieldSpec::new("token", "Token", FieldType::String)
+            .required()
+            .secret(),
+        FieldSpec::new("chat_id", "Chat ID", FieldType::String).required(),
+        FieldSpec::new(
+            "parse_mode",
+            "Parse Mode (MarkdownV2/HTML)",
+            FieldType::String,
+        ),
+        FieldSpec::new("message_thread_id", "Message Thread ID", FieldType::Integer),
+        FieldSpec::new("disable_notification", "Silent", FieldType::Bool),
+        FieldSpec::new(
+            "disable_notification_on_success",
+            "Silent On Success",
+            FieldType::Bool,
+        ),
+        FieldSpec::new(
+            "disable_web_page_preview",
+            "Disable Link Preview",
+            FieldType::Bool,
+        ),
+        FieldSpec::new(
+            "document_threshold",
+            "Upload Output Above (chars)",
+            FieldType::Integer,
+        ),
+        FieldSpec::new("api_url", "API URL", FieldType::String).default_value(DEFAULT_API_URL),
+        FieldSpec::new("live_updates", "Live Status Message", FieldType::Bool),
+        FieldSpec::new(
+            "live_update_interval",
+            "Live Update Interval (s)",
+            FieldType::Integer,
+        ),
+    ],
+    default_config: || NotificationConfigType::Telegram(TelegramConfig::default()),
+    build: |config| match config {
+        NotificationConfigType::Telegram(config) => {
+            Ok(Box::new(TelegramNotifier::from_config(config)?))
+        }
+        other => Err(config_mismatch(&PROVIDER, other)),
+    },
+};
+
+pub const DEFAULT_API_URL: &str = "https://api.telegram.org";
+
+/// Telegram rejects messages longer than this many characters.
+const MESSAGE_LIMIT: usize = 4096;
+/// Characters of the error shown in a document caption, which Telegram
+/// limits to 1024 characters.
+const CAPTION_ERROR_LIMIT: usize = 600;
+/// Characters of the error shown in a report message, leaving room for the
+/// output within `MESSAGE_LIMIT` even once every character is escaped.
+const MESSAGE_ERROR_LIMIT: usize = 1000;
+/// Characters of the command, command line and host shown in a header, so
+/// that a caption with `CAPTION_ERROR_LIMIT` of error stays within 1024.
+const HEADER_FIELD_LIMIT: usize = 100;
+/// Seconds between edits of the live status message, Telegram throttles
+/// bots editing the same chat too often.
+const DEFAULT_LIVE_UPDATE_INTERVAL: u32 = 10;
+/// Output lines shown in the live status message.
+const LIVE_OUTPUT_LINES: usize = 10;
+/// Characters of output shown in the live status message.
+const LIVE_OUTPUT_LIMIT: usize = 3000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+    Plain,
+    MarkdownV2,
+    Html,
+}
+
+impl ParseMode {
+    fn api_name(&self) -> Option<&'static str> {
+        match self {
+            ParseMode::Plain => None,
+            ParseMode::MarkdownV2 => Some("MarkdownV2"),
+            ParseMode::Html => Some("HTML"),
+        }
+    }
+
+    /// Escapes text outside of any entity.
+    fn escape(&self, text: &str) -> String {
+        match self {
+            ParseMode::Plain => text.to_string(),
+            ParseMode::MarkdownV2 => escape_chars(text, "_*[]()~`>#+-=|{}.!\\"),
+            ParseMode::Html => escape_html(text),
+        }
+    }
+
+    /// Escapes text inside a `pre` block.
+    fn escape_pre(&self, text: &str) -> String {
+        match self {
+            ParseMode::Plain => text.to_string(),
+            ParseMode::MarkdownV2 => escape_chars(text, "`\\"),
+            ParseMode::Html => escape_html(text),
+        }
+    }
+
+    fn bold(&self, text: &str) -> String {
+        match self {
+            ParseMode::Plain => text.to_string(),
+            ParseMode::MarkdownV2 => format!("*{}*", self.escape(text)),
+            ParseMode::Html => format!("<b>{}</b>", self.escape(text)),
+        }
+    }
+
+    fn code(&self, text: &str) -> String {
+        match self {
+            ParseMode::Plain => text.to_string(),
+            ParseMode::MarkdownV2 => format!("`{}`", self.escape_pre(text)),
+            ParseMode::Html => format!("<code>{}</code>", self.escape(text)),
+        }
+    }
+
+    /// Opening and closing markup of a `pre` block.
+    fn pre_delimiters(&self) -> (&'static str, &'static str) {
+        match self {
+            ParseMode::Plain => ("", ""),
+            ParseMode::MarkdownV2 => ("```\n", "\n```"),
+            ParseMode::Html => ("<pre>", "</pre>"),
+        }
+    }
+}
+
+fn escape_chars(text: &str, special: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if special.contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Splits `text` into escaped chunks, the first at most `first_budget` and
+/// the others at most `budget` characters long once escaped. Chunks end at
+/// line breaks where possible and never cut an escape sequence in half.
+fn split_escaped(
+    text: &str,
+    first_budget: usize,
+    budget: usize,
+    escape: impl Fn(&str) -> String,
+) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0;
+    let limit = |chunks: &Vec<String>| {
+        if chunks.is_empty() {
+            first_budget
+        } else {
+            budget
+        }
+    };
+
+    for line in text.split_inclusive('\n') {
+        let escaped = escape(line);
+        let len = escaped.chars().count();
+        if current_len + len <= limit(&chunks) {
+            current.push_str(&escaped);
+            current_len += len;
+            continue;
+        }
+        if !current.is_empty() {
+            chunks.push(std::mem::take(&mut current));
+            current_len = 0;
+        }
+        if len <= limit(&chunks) {
+            current = escaped;
+            current_len = len;
+            continue;
+        }
+
+        // A single line over the limit is cut between characters
+        let mut buf = [0; 4];
+        for c in line.chars() {
+            let escaped = escape(c.encode_utf8(&mut buf));
+            let len = escaped.chars().count();
+            if current_len + len > limit(&chunks) {
+                chunks.push(std::mem::take(&mut current));
+                current_len = 0;
+            }
+            current.push_str(&escaped);
+            current_len += len;
+        }
+    }
+
+    if !current.is_empty() || chunks.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// The first `limit` characters of `text`, marked with an ellipsis when
+/// something was cut.
+fn clip(text: &str, limit: usize) -> String {
+    match text.char_indices().nth(limit) {
+        Some((idx, _)) => format!("{}…", &text[..idx]),
+        None => text.to_string(),
+    }
+}
+
+/// Message posted when the command started, edited while it runs in live
+/// mode and replied to otherwise.
+struct StartMessage {
+    message_id: i64,
+    updated_at: Instant,
+}
 
 pub struct TelegramNotifier {
+    api_url: String,
     bot_token: String,
     chat_id: String,
+    document_threshold: Option<usize>,
+    parse_mode: ParseMode,
+    message_thread_id: Option<i64>,
+    disable_notification: bool,
+    disable_notification_on_success: bool,
+    disable_web_page_preview: bool,
+    /// Interval between live status updates, `None` when disabled.
+    live_update_interval: Option<Duration>,
+    start_message: Mutex<Option<StartMessage>>,
     client: Client,
 }
 
 impl TelegramNotifier {
     pub fn new(bot_token: String, chat_id: String) -> Self {
         Self {
+            api_url: DEFAULT_API_URL.to_string(),
             bot_token,
             chat_id,
+            document_threshold: None,
+            parse_mode: ParseMode::Plain,
+            message_thread_id: None,
+            disable_notification: false,
+            disable_notification_on_success: false,
+            disable_web_page_preview: false,
+            live_update_interval: None,
+            start_message: Mutex::new(None),
             client: Client::new(),
         }
     }
 
+    pub fn from_config(config: &TelegramConfig) -> Result<Self, Box<dyn Error>> {
+        let parse_mode = match config.parse_mode.as_deref().unwrap_or_default() {
+            "" => ParseMode::Plain,
+            mode if mode.eq_ignore_ascii_case("markdownv2") => ParseMode::MarkdownV2,
+            mode if mode.eq_ignore_ascii_case("html") => ParseMode::Html,
+            other => return Err(format!("Unsupported Telegram parse mode '{}'", other).into()),
+        };
+
+        let mut notifier = Self::new(config.token.clone(), config.chat_id.clone());
+        notifier.parse_mode = parse_mode;
+        notifier.message_thread_id = config.message_thread_id;
+        notifier.disable_notification = config.disable_notification.unwrap_or(false);
+        notifier.disable_notification_on_success =
+            config.disable_notification_on_success.unwrap_or(false);
+        notifier.disable_web_page_preview = config.disable_web_page_preview.unwrap_or(false);
+        notifier.document_threshold = config.document_threshold.map(|t| t as usize);
+        if let Some(api_url) = config.api_url.as_deref().filter(|url| !url.is_empty()) {
+            notifier.api_url = api_url.trim_end_matches('/').to_string();
+        }
+        if config.live_updates.unwrap_or(false) {
+            let interval = config
+                .live_update_interval
+                .unwrap_or(DEFAULT_LIVE_UPDATE_INTERVAL);
+            notifier.live_update_interval = Some(Duration::from_secs(interval as u64));
+        }
+        Ok(notifier)
+    }
+
     pub fn create(
         params: HashMap<String, String>,
     ) -> Result<Box<dyn NotificationSender>, Box<dyn Error>> {
@@ -34,22 +306,181 @@ impl TelegramNotifier {
 
         Ok(Box::new(TelegramNotifier::new(bot_token, chat_id)))
     }
-}
 
-#[async_trait::async_trait]
-impl NotificationSender for TelegramNotifier {
-    async fn send(&self, message: &str) -> Result<(), Box<dyn Error>> {
-        let url = format!("https://api.telegram.org/bot{}/sendMessage", self.bot_token);
+    /// Message texts for `report`, split to fit Telegram's size limit.
+    fn format_report(&self, report: &RunReport) -> Vec<String> {
+        let mode = self.parse_mode;
+        if mode == ParseMode::Plain {
+            return split_escaped(&report.to_message(), MESSAGE_LIMIT, MESSAGE_LIMIT, |s| {
+                s.to_string()
+            });
+        }
+
+        let header = self.format_header(report, MESSAGE_ERROR_LIMIT);
+        let output = report.output.as_deref().unwrap_or_default().trim_end();
+        if output.is_empty() {
+            return vec![header];
+        }
+
+        let (open, close) = mode.pre_delimiters();
+        let wrapper = open.chars().count() + close.chars().count();
+        let header_len = header.chars().count();
+        split_escaped(
+            output,
+            MESSAGE_LIMIT.saturating_sub(header_len + wrapper),
+            MESSAGE_LIMIT - wrapper,
+            |s| mode.escape_pre(s),
+        )
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let chunk = format!("{}{}{}", open, chunk.trim_end_matches('\n'), close);
+            if i == 0 {
+                format!("{}{}", header, chunk)
+            } else {
+                chunk
+            }
+        })
+        .collect()
+    }
+
+    /// Summary lines of `report`, showing at most `error_limit` characters
+    /// of the error.
+    fn format_header(&self, report: &RunReport, error_limit: usize) -> String {
+        let mode = self.parse_mode;
+        let icon = if report.success { "✅" } else { "❌" };
+        let exit_code = report
+            .exit_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let mut header = format!(
+            "{}\n{} {}\n{} {}\n{} {}\n",
+            mode.bold(&format!(
+                "{} {} {}",
+                icon,
+                clip(&report.command, HEADER_FIELD_LIMIT),
+                report.status_text()
+            )),
+            mode.escape("Command:"),
+            mode.code(&clip(&report.command_line(), HEADER_FIELD_LIMIT)),
+            mode.escape("Host:"),
+            mode.code(&clip(&report.host, HEADER_FIELD_LIMIT)),
+            mode.escape("Duration:"),
+            mode.escape(&format!(
+                "{}, exit code {}",
+                format_duration(report.duration),
+                exit_code
+            )),
+        );
+        if report.error.is_some() {
+            header.push_str(&format!(
+                "{}\n",
+                mode.escape(report.error_head(error_limit))
+            ));
+        }
+        header
+    }
 
-        let body = json!({
+    /// Announcement of a run that just started.
+    fn format_start(&self, context: &RunContext) -> String {
+        let mode = self.parse_mode;
+        format!(
+            "⏳ {} {} {}",
+            mode.code(&context.command_line()),
+            mode.escape("started on host"),
+            mode.code(&context.host),
+        )
+    }
+
+    /// Text of the live status message for a command still running.
+    fn format_progress(&self, context: &RunContext, progress: &RunProgress) -> String {
+        let mode = self.parse_mode;
+        let mut text = format!(
+            "⏳ {} {} {} {}\n{} {}\n",
+            mode.escape("running"),
+            mode.code(&context.command_line()),
+            mode.escape("on host"),
+            mode.code(&context.host),
+            mode.escape("Elapsed:"),
+            mode.escape(&format_duration(progress.elapsed)),
+        );
+
+        let lines = progress.last_lines(LIVE_OUTPUT_LINES);
+        let skip = lines.chars().count().saturating_sub(LIVE_OUTPUT_LIMIT);
+        let lines: String = lines.chars().skip(skip).collect();
+        if !lines.trim().is_empty() {
+            let (open, close) = mode.pre_delimiters();
+            text.push_str(&format!("{}{}{}", open, mode.escape_pre(&lines), close));
+        }
+        text
+    }
+
+    fn format_message(&self, message: &str) -> Vec<String> {
+        split_escaped(message, MESSAGE_LIMIT, MESSAGE_LIMIT, |s| {
+            self.parse_mode.escape(s)
+        })
+    }
+
+    /// `sendMessage` payload, `success` being the outcome of the reported
+    /// run if any.
+    fn message_payload(&self, text: &str, success: Option<bool>) -> Value {
+        let mut body = json!({
             "chat_id": self.chat_id,
-            "text": message,
+            "text": text,
         });
+        if let Some(parse_mode) = self.parse_mode.api_name() {
+            body["parse_mode"] = json!(parse_mode);
+        }
+        if let Some(thread_id) = self.message_thread_id {
+            body["message_thread_id"] = json!(thread_id);
+        }
+        if self.disable_notification
+            || (self.disable_notification_on_success && success == Some(true))
+        {
+            body["disable_notification"] = json!(true);
+        }
+        if self.disable_web_page_preview {
+            body["disable_web_page_preview"] = json!(true);
+        }
+        body
+    }
 
-        let response = self.client.post(&url).json(&body).send().await?;
+    /// `editMessageText` payload replacing the text of `message_id`.
+    fn edit_payload(&self, message_id: i64, text: &str) -> Value {
+        let mut body = json!({
+            "chat_id": self.chat_id,
+            "message_id": message_id,
+            "text": text,
+        });
+        if let Some(parse_mode) = self.parse_mode.api_name() {
+            body["parse_mode"] = json!(parse_mode);
+        }
+        if self.disable_web_page_preview {
+            body["disable_web_page_preview"] = json!(true);
+        }
+        body
+    }
+
+    /// Makes `body` a reply to `message_id`, if any.
+    fn reply_to(mut body: Value, message_id: Option<i64>) -> Value {
+        if let Some(message_id) = message_id {
+            body["reply_parameters"] = json!({
+                "message_id": message_id,
+                "allow_sending_without_reply": true,
+            });
+        }
+        body
+    }
+
+    fn method_url(&self, method: &str) -> String {
+        format!("{}/bot{}/{}", self.api_url, self.bot_token, method)
+    }
 
+    /// The `result` of a successful API call.
+    async fn check_response(response: Response) -> Result<Value, Box<dyn Error>> {
         if response.status().is_success() {
-            Ok(())
+            let body: Value = response.json().await.unwrap_or_default();
+            Ok(body["result"].clone())
         } else {
             let status = response.status();
             let error_text = response.text().await?;
@@ -60,11 +491,194 @@ impl NotificationSender for TelegramNotifier {
             .into())
         }
     }
+
+    async fn call(&self, method: &str, body: &Value) -> Result<Value, Box<dyn Error>> {
+        let response = self
+            .client
+            .post(self.method_url(method))
+            .json(body)
+            .send()
+            .await?;
+        Self::check_response(response).await
+    }
+
+    async fn send_payloads(&self, payloads: Vec<Value>) -> Result<(), Box<dyn Error>> {
+        for body in payloads {
+            self.call("sendMessage", &body).await?;
+        }
+        Ok(())
+    }
+
+    async fn edit_message(&self, message_id: i64, text: &str) -> Result<(), Box<dyn Error>> {
+        self.call("editMessageText", &self.edit_payload(message_id, text))
+            .await
+            .map(|_| ())
+    }
+
+    fn exceeds_document_threshold(&self, report: &RunReport) -> bool {
+        let output_len = report.output.as_deref().unwrap_or_default().chars().count();
+        self.document_threshold
+            .is_some_and(|threshold| output_len > threshold)
+    }
+
+    /// Sends `report` as messages or a document, replying to `reply_to`.
+    async fn deliver_report(
+        &self,
+        report: &RunReport,
+        reply_to: Option<i64>,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.exceeds_document_threshold(report) {
+            return self.send_document(report, reply_to).await;
+        }
+
+        let payloads = self
+            .format_report(report)
+            .iter()
+            .map(|text| Self::reply_to(self.message_payload(text, Some(report.success)), reply_to))
+            .collect();
+        self.send_payloads(payloads).await
+    }
+
+    /// Uploads the output of `report` as a log file captioned with its summary.
+    async fn send_document(
+        &self,
+        report: &RunReport,
+        reply_to: Option<i64>,
+    ) -> Result<(), Box<dyn Error>> {
+        let caption = self.format_header(report, CAPTION_ERROR_LIMIT);
+        let payload = Self::reply_to(
+            self.message_payload(&caption, Some(report.success)),
+            reply_to,
+        );
+        let mut form = Form::new();
+        for (key, value) in payload.as_object().into_iter().flatten() {
+            let key = if key == "text" {
+                "caption"
+            } else {
+                key.as_str()
+            };
+            let value = match value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            form = form.text(key.to_string(), value);
+        }
+
+        let document = Part::bytes(report.output.clone().unwrap_or_default().into_bytes())
+            .file_name(report.log_file_name())
+            .mime_str("text/plain")?;
+        form = form.part("document", document);
+
+        let response = self
+            .client
+            .post(self.method_url("sendDocument"))
+            .multipart(form)
+            .send()
+            .await?;
+        Self::check_response(response).await.map(|_| ())
+    }
+}
+
+#[async_trait::async_trait]
+impl NotificationSender for TelegramNotifier {
+    async fn send(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        let payloads = self
+            .format_message(message)
+            .iter()
+            .map(|text| self.message_payload(text, None))
+            .collect();
+        self.send_payloads(payloads).await
+    }
+
+    async fn send_report(&self, report: &RunReport) -> Result<(), Box<dyn Error>> {
+        self.deliver_report(report, None).await
+    }
+
+    async fn on_start(&self, context: &RunContext) -> Result<(), Box<dyn Error>> {
+        let text = if self.live_update_interval.is_some() {
+            self.format_progress(context, &RunProgress::default())
+        } else if context.notify_on_start {
+            self.format_start(context)
+        } else {
+            return Ok(());
+        };
+
+        let result = self
+            .call("sendMessage", &self.message_payload(&text, None))
+            .await?;
+        let message_id = result["message_id"]
+            .as_i64()
+            .ok_or("Telegram did not return the id of the start message")?;
+        *self.start_message.lock().unwrap() = Some(StartMessage {
+            message_id,
+            updated_at: Instant::now(),
+        });
+        Ok(())
+    }
+
+    async fn on_progress(
+        &self,
+        context: &RunContext,
+        progress: &RunProgress,
+    ) -> Result<(), Box<dyn Error>> {
+        let Some(interval) = self.live_update_interval else {
+            return Ok(());
+        };
+        let message_id = match &*self.start_message.lock().unwrap() {
+            Some(start) if start.updated_at.elapsed() >= interval => start.message_id,
+            _ => return Ok(()),
+        };
+
+        let result = self
+            .edit_message(message_id, &self.format_progress(context, progress))
+            .await;
+        if let Some(start) = self.start_message.lock().unwrap().as_mut() {
+            start.updated_at = Instant::now();
+        }
+        result
+    }
+
+    async fn on_finish(
+        &self,
+        _context: &RunContext,
+        report: &RunReport,
+    ) -> Result<(), Box<dyn Error>> {
+        let start = self.start_message.lock().unwrap().take();
+        let Some(start) = start else {
+            return self.send_report(report).await;
+        };
+        if self.live_update_interval.is_none() {
+            return self.deliver_report(report, Some(start.message_id)).await;
+        }
+
+        if self.exceeds_document_threshold(report) {
+            let header = self.format_header(report, CAPTION_ERROR_LIMIT);
+            self.edit_message(start.message_id, &header).await?;
+            return self.send_document(report, Some(start.message_id)).await;
+        }
+
+        // The status message becomes the first part of the report, the
+        // rest is threaded below it
+        let mut texts = self.format_report(report).into_iter();
+        if let Some(first) = texts.next() {
+            self.edit_message(start.message_id, &first).await?;
+        }
+        let payloads = texts
+            .map(|text| {
+                Self::reply_to(
+                    self.message_payload(&text, Some(report.success)),
+                    Some(start.message_id),
+                )
+            })
+            .collect();
+        self.send_payloads(payloads).await
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::notifications::test_server::{MockResponse, MockServer};
     use once_cell::sync::Lazy;
     use serde::Deserialize;
     use std::fs;
@@ -135,4 +749,374 @@ mod tests {
             }
         });
     }
+
+    fn report(output: &str) -> RunReport {
+        let mut report = RunReport::new("make", vec!["test".to_string()]);
+        report.host = "build-1".to_string();
+        report.duration = std::time::Duration::from_secs(3);
+        report.exit_code = Some(0);
+        report.output = Some(output.to_string());
+        report
+    }
+
+    fn notifier(parse_mode: &str) -> TelegramNotifier {
+        TelegramNotifier::from_config(&TelegramConfig {
+            token: "token".to_string(),
+            chat_id: "42".to_string(),
+            parse_mode: Some(parse_mode.to_string()),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_escaping() {
+        assert_eq!(
+            ParseMode::MarkdownV2.escape("a_b*c [x](y) 1.5!"),
+            "a\\_b\\*c \\[x\\]\\(y\\) 1\\.5\\!"
+        );
+        assert_eq!(
+            ParseMode::MarkdownV2.escape_pre("`x` \\ _y_"),
+            "\\`x\\` \\\\ _y_"
+        );
+        assert_eq!(
+            ParseMode::Html.escape("<b>&</b>"),
+            "&lt;b&gt;&amp;&lt;/b&gt;"
+        );
+        assert_eq!(ParseMode::Plain.escape("<b>_</b>"), "<b>_</b>");
+    }
+
+    #[test]
+    fn test_format_report() {
+        let markdown = notifier("MarkdownV2").format_report(&report("ok.\n`done`\n"));
+        assert_eq!(
+            markdown,
+            vec![
+                "*✅ make succeeded*\nCommand: `make test`\nHost: `build-1`\nDuration: 3s, exit code 0\n```\nok.\n\\`done\\`\n```"
+            ]
+        );
+
+        let html = notifier("HTML").format_report(&report("a < b"));
+        assert_eq!(
+            html,
+            vec![
+                "<b>✅ make succeeded</b>\nCommand: <code>make test</code>\nHost: <code>build-1</code>\nDuration: 3s, exit code 0\n<pre>a &lt; b</pre>"
+            ]
+        );
+
+        assert!(TelegramNotifier::from_config(&TelegramConfig {
+            parse_mode: Some("Markdown".to_string()),
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_split_long_output() {
+        let line = format!("{}\n", "x.".repeat(50));
+        let output = line.repeat(200);
+
+        for mode in ["MarkdownV2", "HTML"] {
+            let messages = notifier(mode).format_report(&report(&output));
+            assert!(messages.len() > 1);
+            for message in &messages {
+                assert!(message.chars().count() <= MESSAGE_LIMIT);
+            }
+            // Every chunk is a complete pre block
+            let (open, close) = notifier(mode).parse_mode.pre_delimiters();
+            assert!(messages[1].starts_with(open) && messages[1].ends_with(close));
+        }
+
+        let plain = TelegramNotifier::new("token".to_string(), "42".to_string());
+        let messages = plain.format_message(&"y".repeat(MESSAGE_LIMIT * 2 + 1));
+        assert_eq!(
+            messages.iter().map(|m| m.len()).collect::<Vec<_>>(),
+            vec![MESSAGE_LIMIT, MESSAGE_LIMIT, 1]
+        );
+    }
+
+    #[test]
+    fn test_long_error() {
+        let mut failed = report("compiling...\n");
+        failed.success = false;
+        failed.exit_code = Some(1);
+        failed.error = Some("error: <x> failed.\n".repeat(500));
+
+        for mode in ["MarkdownV2", "HTML"] {
+            let messages = notifier(mode).format_report(&failed);
+            assert_eq!(messages.len(), 1);
+            assert!(messages[0].chars().count() <= MESSAGE_LIMIT);
+            assert!(messages[0].contains("compiling"));
+        }
+    }
+
+    #[test]
+    fn test_long_caption() {
+        let mut failed = report("");
+        failed.success = false;
+        failed.command = "/opt/tools/".repeat(50);
+        failed.args = vec!["--input=file.txt".to_string(); 200];
+        failed.host = "h".repeat(300);
+        failed.error = Some("e".repeat(5000));
+
+        let caption = notifier("").format_header(&failed, CAPTION_ERROR_LIMIT);
+        assert!(caption.chars().count() <= 1024);
+        assert!(caption.contains(&format!("Command: {}…\n", &failed.command[..100])));
+    }
+
+    #[test]
+    fn test_split_keeps_escapes_whole() {
+        let chunks = split_escaped(&".".repeat(10), 5, 5, |s| ParseMode::MarkdownV2.escape(s));
+        assert_eq!(
+            chunks,
+            vec!["\\.\\.", "\\.\\.", "\\.\\.", "\\.\\.", "\\.\\."]
+        );
+    }
+
+    #[test]
+    fn test_message_payload() {
+        let notifier = TelegramNotifier::from_config(&TelegramConfig {
+            token: "token".to_string(),
+            chat_id: "42".to_string(),
+            parse_mode: Some("HTML".to_string()),
+            message_thread_id: Some(7),
+            disable_notification: None,
+            disable_notification_on_success: Some(true),
+            disable_web_page_preview: Some(true),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let success = notifier.message_payload("hi", Some(true));
+        assert_eq!(
+            success,
+            json!({
+                "chat_id": "42",
+                "text": "hi",
+                "parse_mode": "HTML",
+                "message_thread_id": 7,
+                "disable_notification": true,
+                "disable_web_page_preview": true
+            })
+        );
+
+        let failure = notifier.message_payload("hi", Some(false));
+        assert!(failure.get("disable_notification").is_none());
+
+        let plain = TelegramNotifier::new("token".to_string(), "42".to_string());
+        assert_eq!(
+            plain.message_payload("hi", None),
+            json!({"chat_id": "42", "text": "hi"})
+        );
+    }
+
+    fn mock_notifier(server: &MockServer, document_threshold: Option<u32>) -> TelegramNotifier {
+        TelegramNotifier::from_config(&TelegramConfig {
+            token: "123:abc".to_string(),
+            chat_id: "42".to_string(),
+            message_thread_id: Some(7),
+            document_threshold,
+            api_url: Some(format!("{}/", server.url)),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_send_message_to_api_url() {
+        let server = MockServer::start(vec![MockResponse::json(
+            json!({"ok": true, "result": {"message_id": 1}}),
+        )])
+        .await;
+        let notifier = mock_notifier(&server, Some(1000));
+
+        notifier.send_report(&report("short output")).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/bot123:abc/sendMessage");
+        let body = requests[0].json();
+        assert_eq!(body["chat_id"], "42");
+        assert_eq!(body["message_thread_id"], 7);
+        assert!(body["text"].as_str().unwrap().contains("short output"));
+    }
+
+    #[tokio::test]
+    async fn test_send_document_above_threshold() {
+        let server = MockServer::start(vec![MockResponse::json(
+            json!({"ok": true, "result": {"message_id": 2}}),
+        )])
+        .await;
+        let notifier = mock_notifier(&server, Some(10));
+
+        let mut report = report("line one\nline two\nline three\n");
+        report.command = "/usr/bin/make".to_string();
+        notifier.send_report(&report).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/bot123:abc/sendDocument");
+        assert!(requests[0]
+            .header("content-type")
+            .unwrap()
+            .starts_with("multipart/form-data"));
+
+        let body = requests[0].body_text();
+        assert!(body.contains("name=\"chat_id\"\r\n\r\n42\r\n"));
+        assert!(body.contains("name=\"message_thread_id\"\r\n\r\n7\r\n"));
+        assert!(body.contains("name=\"caption\"\r\n\r\n✅ /usr/bin/make succeeded\n"));
+        assert!(body.contains(&format!(
+            "name=\"document\"; filename=\"{}\"",
+            report.log_file_name()
+        )));
+        assert!(report.log_file_name().starts_with("make-"));
+        assert!(body.contains("line one\nline two\nline three\n"));
+    }
+
+    #[tokio::test]
+    async fn test_api_error() {
+        let server = MockServer::start(vec![MockResponse::new(
+            400,
+            r#"{"ok":false,"description":"Bad Request: chat not found"}"#,
+        )])
+        .await;
+        let err = mock_notifier(&server, None)
+            .send("hello")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("chat not found"));
+    }
+
+    fn live_notifier(server: &MockServer) -> TelegramNotifier {
+        TelegramNotifier::from_config(&TelegramConfig {
+            token: "123:abc".to_string(),
+            chat_id: "42".to_string(),
+            parse_mode: Some("MarkdownV2".to_string()),
+            api_url: Some(server.url.clone()),
+            live_updates: Some(true),
+            live_update_interval: Some(0),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    fn context(report: &RunReport) -> RunContext {
+        RunContext::new("ci", report)
+    }
+
+    #[test]
+    fn test_format_progress() {
+        let context = context(&report(""));
+        let mut progress = RunProgress {
+            elapsed: std::time::Duration::from_secs(65),
+            ..Default::default()
+        };
+        assert_eq!(
+            notifier("MarkdownV2").format_progress(&context, &progress),
+            "⏳ running `make test` on host `build-1`\nElapsed: 1m 5s\n"
+        );
+        assert_eq!(
+            notifier("HTML").format_start(&context),
+            "⏳ <code>make test</code> started on host <code>build-1</code>"
+        );
+
+        progress.output_tail = (1..=12).map(|i| format!("step {}\n", i)).collect();
+        let text = notifier("HTML").format_progress(&context, &progress);
+        assert!(text.ends_with("<pre>step 3\nstep 4\nstep 5\nstep 6\nstep 7\nstep 8\nstep 9\nstep 10\nstep 11\nstep 12</pre>"));
+    }
+
+    #[tokio::test]
+    async fn test_live_status_message() {
+        let server = MockServer::start(vec![
+            MockResponse::json(json!({"ok": true, "result": {"message_id": 99}})),
+            MockResponse::json(json!({"ok": true, "result": true})),
+        ])
+        .await;
+        let notifier = live_notifier(&server);
+
+        let report = report("all done\n");
+        let context = context(&report);
+        notifier.on_start(&context).await.unwrap();
+        let progress = RunProgress {
+            elapsed: std::time::Duration::from_secs(2),
+            output_tail: "compiling\n".to_string(),
+        };
+        notifier.on_progress(&context, &progress).await.unwrap();
+        notifier.on_finish(&context, &report).await.unwrap();
+
+        let requests = server.requests();
+        let paths: Vec<_> = requests.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "/bot123:abc/sendMessage",
+                "/bot123:abc/editMessageText",
+                "/bot123:abc/editMessageText"
+            ]
+        );
+        assert!(requests[0].json()["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("⏳ running `make test`"));
+
+        let update = requests[1].json();
+        assert_eq!(update["message_id"], 99);
+        assert_eq!(update["parse_mode"], "MarkdownV2");
+        assert!(update["text"].as_str().unwrap().contains("Elapsed: 2s"));
+        assert!(update["text"].as_str().unwrap().contains("compiling"));
+
+        let result = requests[2].json();
+        assert_eq!(result["message_id"], 99);
+        assert!(result["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("*✅ make succeeded*"));
+
+        // The next run starts from a fresh message
+        notifier.on_finish(&context, &report).await.unwrap();
+        assert_eq!(
+            server.requests().last().unwrap().path,
+            "/bot123:abc/sendMessage"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reply_to_start_message() {
+        let server = MockServer::start(vec![MockResponse::json(
+            json!({"ok": true, "result": {"message_id": 5}}),
+        )])
+        .await;
+        let notifier = mock_notifier(&server, None);
+
+        let report = report("done");
+        let mut context = context(&report);
+        notifier.on_start(&context).await.unwrap();
+        notifier
+            .on_progress(&context, &RunProgress::default())
+            .await
+            .unwrap();
+        assert!(server.requests().is_empty());
+
+        context.notify_on_start = true;
+        notifier.on_start(&context).await.unwrap();
+        notifier.on_finish(&context, &report).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].json()["text"]
+            .as_str()
+            .unwrap()
+            .contains("started on host"));
+        let result = requests[1].json();
+        assert_eq!(result["reply_parameters"]["message_id"], 5);
+        assert!(result["text"].as_str().unwrap().contains("done"));
+
+        // Without a start message the report stands alone
+        notifier.on_finish(&context, &report).await.unwrap();
+        assert!(server.requests()[2]
+            .json()
+            .get("reply_parameters")
+            .is_none());
+    }
 }
@@ -0,0 +1,326 @@
+use crate::config::{NotificationConfigType, TerminalConfig};
+use crate::notifications::registry::{FieldSpec, FieldType, ProviderDescriptor};
+use crate::notifications::{config_mismatch, NotificationSender};
+use crate::report::{format_duration, RunReport};
+use log::{info, warn};
+use std::error::Error;
+use std::io::{IsTerminal, Write};
+use std::sync::{Arc, Mutex};
+
+pub const PROVIDER: ProviderDescriptor = ProviderDescriptor {
+    name: "terminal",
+    display_name: "Terminal",
+    fields: &[
+        FieldSpec::new("bell", "Bell", FieldType::Bool),
+        FieldSpec::new("osc", "OSC (auto/9/777/none)", FieldType::String),
+        FieldSpec::new("tmux", "tmux Alerts", FieldType::Bool),
+    ],
+    default_config: || NotificationConfigType::Terminal(TerminalConfig::default()),
+    build: |config| match config {
+        NotificationConfigType::Terminal(config) => {
+            Ok(Box::new(TerminalNotifier::from_config(config)?))
+        }
+        other => Err(config_mismatch(&PROVIDER, other)),
+    },
+};
+
+const BEL: &str = "\x07";
+
+/// Desktop notification escape sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Osc {
+    None,
+    /// `OSC 9 ; body`, iTerm2, kitty, WezTerm, Ghostty.
+    Nine,
+    /// `OSC 777 ; notify ; title ; body`, foot, urxvt, Ghostty.
+    SevenSevenSeven,
+}
+
+/// The parts of the environment deciding which sequences to write.
+#[derive(Debug, Clone, Default)]
+struct Environment {
+    term: String,
+    term_program: String,
+    /// Set by iTerm2 and kept across ssh and tmux.
+    lc_terminal: String,
+    tmux_pane: Option<String>,
+    screen: bool,
+}
+
+impl Environment {
+    fn current() -> Self {
+        let var = |name: &str| std::env::var(name).unwrap_or_default();
+        Self {
+            term: var("TERM"),
+            term_program: var("TERM_PROGRAM"),
+            lc_terminal: var("LC_TERMINAL"),
+            tmux_pane: std::env::var("TMUX")
+                .ok()
+                .map(|_| var("TMUX_PANE"))
+                .filter(|pane| !pane.is_empty()),
+            screen: std::env::var_os("STY").is_some(),
+        }
+    }
+
+    /// The notification sequence understood by the terminal, if known.
+    fn detect_osc(&self) -> Osc {
+        let term = self.term.as_str();
+        match self.term_program.as_str() {
+            "iTerm.app" | "WezTerm" | "ghostty" => return Osc::Nine,
+            _ => {}
+        }
+        if self.lc_terminal == "iTerm2" || term == "xterm-kitty" {
+            Osc::Nine
+        } else if term.starts_with("foot") || term.starts_with("rxvt-unicode") {
+            Osc::SevenSevenSeven
+        } else {
+            Osc::None
+        }
+    }
+}
+
+/// Where sequences are written.
+enum Sink {
+    /// The controlling terminal, or stderr when that is a terminal.
+    Tty,
+    #[cfg_attr(not(test), allow(dead_code))]
+    Buffer(Arc<Mutex<Vec<u8>>>),
+}
+
+pub struct TerminalNotifier {
+    bell: bool,
+    osc: Osc,
+    tmux: bool,
+    environment: Environment,
+    sink: Sink,
+    /// Program run for the tmux status line message.
+    tmux_program: String,
+}
+
+impl TerminalNotifier {
+    pub fn from_config(config: &TerminalConfig) -> Result<Self, Box<dyn Error>> {
+        let environment = Environment::current();
+        let osc = match config.osc.as_deref().unwrap_or_default() {
+            "" | "auto" => environment.detect_osc(),
+            "9" => Osc::Nine,
+            "777" => Osc::SevenSevenSeven,
+            "none" => Osc::None,
+            other => {
+                return Err(format!(
+                    "Unknown terminal OSC '{}', expected auto, 9, 777 or none",
+                    other
+                )
+                .into())
+            }
+        };
+
+        Ok(Self {
+            bell: config.bell.unwrap_or(true),
+            osc,
+            tmux: config.tmux.unwrap_or(true),
+            environment,
+            sink: Sink::Tty,
+            tmux_program: "tmux".to_string(),
+        })
+    }
+
+    /// Wraps `sequence` so a terminal multiplexer hands it to the outer
+    /// terminal.
+    fn passthrough(&self, sequence: &str) -> String {
+        if self.tmux && self.environment.tmux_pane.is_some() {
+            // Needs `set -g allow-passthrough on` with tmux 3.3 and later
+            format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+        } else if self.environment.screen {
+            format!("\x1bP{}\x1b\\", sequence)
+        } else {
+            sequence.to_string()
+        }
+    }
+
+    /// Bytes alerting about `body` titled `title`.
+    fn sequences(&self, title: &str, body: &str) -> String {
+        let mut out = String::new();
+        // Left bare so tmux and screen flag the window
+        if self.bell {
+            out.push_str(BEL);
+        }
+        let osc = match self.osc {
+            Osc::None => None,
+            Osc::Nine => Some(format!("\x1b]9;{}: {}{}", title, body, BEL)),
+            Osc::SevenSevenSeven => Some(format!(
+                "\x1b]777;notify;{};{}{}",
+                title.replace(';', ","),
+                body,
+                BEL
+            )),
+        };
+        if let Some(osc) = osc {
+            out.push_str(&self.passthrough(&osc));
+        }
+        out
+    }
+
+    fn write(&self, bytes: &[u8]) -> std::io::Result<()> {
+        match &self.sink {
+            Sink::Buffer(buffer) => {
+                buffer.lock().unwrap().extend_from_slice(bytes);
+                Ok(())
+            }
+            Sink::Tty => match std::fs::OpenOptions::new().write(true).open("/dev/tty") {
+                Ok(mut tty) => tty.write_all(bytes),
+                Err(_) if std::io::stderr().is_terminal() => {
+                    let mut stderr = std::io::stderr();
+                    stderr.write_all(bytes)?;
+                    stderr.flush()
+                }
+                Err(_) => {
+                    info!("No terminal to notify on, skipping");
+                    Ok(())
+                }
+            },
+        }
+    }
+
+    /// Shows `message` in the tmux status line of the pane notifyme runs in.
+    fn display_in_tmux(&self, message: &str) {
+        let Some(pane) = self.environment.tmux_pane.as_deref().filter(|_| self.tmux) else {
+            return;
+        };
+        let status = std::process::Command::new(&self.tmux_program)
+            // `#` starts a tmux format
+            .args(["display-message", "-t", pane, &message.replace('#', "##")])
+            .status();
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(status) => warn!("tmux display-message exited with {}", status),
+            Err(e) => warn!("Failed to run tmux: {}", e),
+        }
+    }
+
+    fn alert(&self, title: &str, body: &str) -> Result<(), Box<dyn Error>> {
+        let (title, body) = (sanitize(title), sanitize(body));
+        self.write(self.sequences(&title, &body).as_bytes())?;
+        self.display_in_tmux(&format!("{}: {}", title, body));
+        Ok(())
+    }
+}
+
+/// `text` on one line without control characters, which would end or
+/// corrupt an escape sequence.
+fn sanitize(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl NotificationSender for TerminalNotifier {
+    async fn send(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.alert("notifyme", message)
+    }
+
+    async fn send_report(&self, report: &RunReport) -> Result<(), Box<dyn Error>> {
+        let icon = if report.success { "✅" } else { "❌" };
+        let title = format!("{} {} {}", icon, report.command, report.status_text());
+        let mut body = format!(
+            "{} on {} after {}",
+            report.command_line(),
+            report.host,
+            format_duration(report.duration)
+        );
+        if let Some(code) = report.exit_code {
+            body.push_str(&format!(", exit code {}", code));
+        }
+        self.alert(&title, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capture(osc: Osc, environment: Environment) -> (TerminalNotifier, Arc<Mutex<Vec<u8>>>) {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let notifier = TerminalNotifier {
+            bell: true,
+            osc,
+            tmux: true,
+            environment,
+            sink: Sink::Buffer(buffer.clone()),
+            tmux_program: "true".to_string(),
+        };
+        (notifier, buffer)
+    }
+
+    fn written(buffer: &Arc<Mutex<Vec<u8>>>) -> String {
+        String::from_utf8(std::mem::take(&mut *buffer.lock().unwrap())).unwrap()
+    }
+
+    fn report() -> RunReport {
+        let mut report = RunReport::new("make", vec!["test".to_string()]);
+        report.host = "build-1".to_string();
+        report.duration = std::time::Duration::from_secs(3);
+        report.exit_code = Some(2);
+        report.success = false;
+        report
+    }
+
+    #[test]
+    fn test_detect_osc() {
+        let env = |term: &str, term_program: &str| Environment {
+            term: term.to_string(),
+            term_program: term_program.to_string(),
+            ..Default::default()
+        };
+        assert_eq!(env("xterm-256color", "iTerm.app").detect_osc(), Osc::Nine);
+        assert_eq!(env("xterm-kitty", "").detect_osc(), Osc::Nine);
+        assert_eq!(env("foot", "").detect_osc(), Osc::SevenSevenSeven);
+        assert_eq!(env("xterm-256color", "").detect_osc(), Osc::None);
+
+        let invalid = TerminalConfig {
+            osc: Some("99".to_string()),
+            ..Default::default()
+        };
+        assert!(TerminalNotifier::from_config(&invalid).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_report_sequences() {
+        let (notifier, buffer) = capture(Osc::Nine, Environment::default());
+        notifier.send_report(&report()).await.unwrap();
+        assert_eq!(
+            written(&buffer),
+            "\x07\x1b]9;❌ make failed: make test on build-1 after 3s, exit code 2\x07"
+        );
+
+        let (notifier, buffer) = capture(Osc::SevenSevenSeven, Environment::default());
+        notifier.send("line one\nline\x1b two").await.unwrap();
+        assert_eq!(
+            written(&buffer),
+            "\x07\x1b]777;notify;notifyme;line one line two\x07"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tmux_passthrough() {
+        let environment = Environment {
+            tmux_pane: Some("%3".to_string()),
+            ..Default::default()
+        };
+        let (notifier, buffer) = capture(Osc::Nine, environment.clone());
+        notifier.send("done").await.unwrap();
+        assert_eq!(
+            written(&buffer),
+            "\x07\x1bPtmux;\x1b\x1b]9;notifyme: done\x07\x1b\\"
+        );
+
+        let (mut notifier, buffer) = capture(Osc::Nine, environment);
+        notifier.tmux = false;
+        notifier.bell = false;
+        notifier.send("done").await.unwrap();
+        assert_eq!(written(&buffer), "\x1b]9;notifyme: done\x07");
+    }
+}
@@ -0,0 +1,353 @@
+use crate::config::{NotificationConfigType, WeComConfig};
+use crate::notifications::registry::{FieldSpec, FieldType, ProviderDescriptor};
+use crate::notifications::{
+    config_mismatch, head_within_bytes, tail_within_bytes, MentionWhen, NotificationSender,
+};
+use crate::report::{format_duration, RunReport};
+use log::{error, info};
+use reqwest::multipart::{Form, Part};
+use reqwest::{Client, RequestBuilder, Url};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::error::Error;
+
+pub const PROVIDER: ProviderDescriptor = ProviderDescriptor {
+    name: "wecom",
+    display_name: "WeCom",
+    fields: &[
+        FieldSpec::new("webhook_url", "Webhook URL", FieldType::String).required(),
+        FieldSpec::new("mentioned_user", "Mentioned Users", FieldType::List),
+        FieldSpec::new("mentioned_mobile", "Mentioned Mobiles", FieldType::List),
+        FieldSpec::new(
+            "mention_when",
+            "Mention When (always/success/failure)",
+            FieldType::String,
+        ),
+        FieldSpec::new(
+            "file_threshold",
+            "Upload Output Above (chars)",
+            FieldType::Integer,
+        ),
+    ],
+    default_config: || NotificationConfigType::WeCom(WeComConfig::default()),
+    build: |config| match config {
+        NotificationConfigType::WeCom(config) => Ok(Box::new(WeComNotifier::from_config(config)?)),
+        other => Err(config_mismatch(&PROVIDER, other)),
+    },
+};
+
+/// WeCom rejects markdown content longer than this many bytes.
+const MARKDOWN_LIMIT: usize = 4096;
+/// Characters of the error shown in a report, the rest of the limit is
+/// left to the header and the output.
+const ERROR_LIMIT: usize = 300;
+
+pub struct WeComNotifier {
+    webhook_url: String,
+    mentioned_list: Vec<String>,
+    mentioned_mobile_list: Vec<String>,
+    mention_when: MentionWhen,
+    file_threshold: Option<usize>,
+    client: Client,
+}
+
+impl WeComNotifier {
+    pub fn from_config(config: &WeComConfig) -> Result<Self, Box<dyn Error>> {
+        let when = config.mention_when.as_deref().unwrap_or_default();
+        let mention_when = MentionWhen::parse(when)
+            .ok_or_else(|| format!("Unknown WeCom mention condition '{}'", when))?;
+
+        Ok(Self {
+            webhook_url: config.webhook_url.clone(),
+            mentioned_list: config.mentioned_list.clone(),
+            mentioned_mobile_list: config.mentioned_mobile_list.clone(),
+            mention_when,
+            file_threshold: config.file_threshold.map(|t| t as usize),
+            client: Client::new(),
+        })
+    }
+
+    fn has_mentions(&self, success: Option<bool>) -> bool {
+        self.mention_when.applies(success)
+            && !(self.mentioned_list.is_empty() && self.mentioned_mobile_list.is_empty())
+    }
+
+    /// Text message, the only kind `mentioned_list` works with.
+    fn text_payload(&self, content: &str, success: Option<bool>) -> Value {
+        let mut text = json!({"content": content});
+        if self.has_mentions(success) {
+            text["mentioned_list"] = json!(self.mentioned_list);
+            text["mentioned_mobile_list"] = json!(self.mentioned_mobile_list);
+        }
+        json!({"msgtype": "text", "text": text})
+    }
+
+    fn format_report(&self, report: &RunReport) -> String {
+        let (color, icon) = if report.success {
+            ("info", "✅")
+        } else {
+            ("warning", "❌")
+        };
+        let exit_code = report
+            .exit_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let mut content = format!(
+            "<font color=\"{}\">{} {} {}</font>\n**Command:** `{}`\n**Host:** {}\n**Duration:** {}, exit code {}\n",
+            color,
+            icon,
+            report.command,
+            report.status_text(),
+            report.command_line(),
+            report.host,
+            format_duration(report.duration),
+            exit_code
+        );
+        if report.error.is_some() {
+            content.push_str(&format!("**Error:** {}\n", report.error_head(ERROR_LIMIT)));
+        }
+        // Only a very long command line gets here, keep the status line
+        content = head_within_bytes(&content, MARKDOWN_LIMIT).to_string();
+
+        let output = report.output.as_deref().unwrap_or_default().trim_end();
+        if !output.is_empty() {
+            let quoted = format!("> {}", output.replace('\n', "\n> "));
+            let budget = MARKDOWN_LIMIT.saturating_sub(content.len() + 1);
+            content.push('\n');
+            content.push_str(tail_within_bytes(&quoted, budget));
+        }
+        content
+    }
+
+    /// The upload endpoint next to the webhook's `send` endpoint.
+    fn upload_url(&self) -> Result<Url, Box<dyn Error>> {
+        let mut url = Url::parse(&self.webhook_url)?;
+        let path = url.path().trim_end_matches('/');
+        let path = match path.strip_suffix("/send") {
+            Some(base) => format!("{}/upload_media", base),
+            None => {
+                return Err(format!("Unexpected WeCom webhook URL '{}'", self.webhook_url).into())
+            }
+        };
+        url.set_path(&path);
+        url.query_pairs_mut().append_pair("type", "file");
+        Ok(url)
+    }
+
+    /// Uploads `content` as a file, returning its media id.
+    async fn upload_file(
+        &self,
+        file_name: String,
+        content: String,
+    ) -> Result<String, Box<dyn Error>> {
+        let file = Part::bytes(content.into_bytes())
+            .file_name(file_name)
+            .mime_str("text/plain")?;
+        let url = self.upload_url()?;
+        let request = self
+            .client
+            .post(url)
+            .multipart(Form::new().part("media", file));
+        let body = Self::execute(request).await?;
+        body.media_id
+            .ok_or_else(|| "WeCom did not return a media id for the uploaded file".into())
+    }
+
+    async fn post(&self, payload: Value) -> Result<(), Box<dyn Error>> {
+        Self::execute(self.client.post(&self.webhook_url).json(&payload))
+            .await
+            .map(|_| ())
+    }
+
+    async fn execute(request: RequestBuilder) -> Result<WeComResponse, Box<dyn Error>> {
+        let response = request.send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        let body: WeComResponse = serde_json::from_str(&text).map_err(|e| {
+            error!("Failed to parse WeCom response: {}, body: {}", e, text);
+            format!("Invalid WeCom response: {}, body: {}", e, text)
+        })?;
+
+        if status.is_success() && body.errcode == 0 {
+            info!("WeCom request succeeded");
+            Ok(body)
+        } else {
+            error!(
+                "WeCom request failed: status={}, errcode={}, errmsg={}",
+                status, body.errcode, body.errmsg
+            );
+            Err(format!(
+                "Failed to send WeCom message: status={}, errcode={}, errmsg={}",
+                status, body.errcode, body.errmsg
+            )
+            .into())
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct WeComResponse {
+    errcode: i64,
+    #[serde(default)]
+    errmsg: String,
+    #[serde(default)]
+    media_id: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl NotificationSender for WeComNotifier {
+    async fn send(&self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.post(self.text_payload(message, None)).await
+    }
+
+    /// Sends the report as markdown, followed by the full output as a file
+    /// when it is long and by a text message carrying the mentions, which
+    /// markdown messages cannot.
+    async fn send_report(&self, report: &RunReport) -> Result<(), Box<dyn Error>> {
+        self.post(json!({
+            "msgtype": "markdown",
+            "markdown": {"content": self.format_report(report)}
+        }))
+        .await?;
+
+        let output = report.output.as_deref().unwrap_or_default();
+        if self
+            .file_threshold
+            .is_some_and(|threshold| output.chars().count() > threshold)
+        {
+            let media_id = self
+                .upload_file(report.log_file_name(), output.to_string())
+                .await?;
+            self.post(json!({"msgtype": "file", "file": {"media_id": media_id}}))
+                .await?;
+        }
+
+        if self.has_mentions(Some(report.success)) {
+            let title = format!("{} {}", report.command, report.status_text());
+            self.post(self.text_payload(&title, Some(report.success)))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::test_server::{MockResponse, MockServer};
+
+    fn config(webhook_url: &str) -> WeComConfig {
+        WeComConfig {
+            webhook_url: webhook_url.to_string(),
+            mentioned_list: vec!["zhangsan".to_string(), "@all".to_string()],
+            mentioned_mobile_list: vec!["13800000000".to_string()],
+            mention_when: Some("failure".to_string()),
+            file_threshold: Some(10),
+        }
+    }
+
+    fn report(success: bool, output: &str) -> RunReport {
+        let mut report = RunReport::new("make", vec!["test".to_string()]);
+        report.host = "build-1".to_string();
+        report.duration = std::time::Duration::from_secs(3);
+        report.exit_code = Some(if success { 0 } else { 2 });
+        report.success = success;
+        report.output = Some(output.to_string());
+        report
+    }
+
+    #[test]
+    fn test_format_report() {
+        let notifier = WeComNotifier::from_config(&config("https://example.com/send")).unwrap();
+        assert_eq!(
+            notifier.format_report(&report(true, "one\ntwo\n")),
+            "<font color=\"info\">✅ make succeeded</font>\n**Command:** `make test`\n**Host:** build-1\n**Duration:** 3s, exit code 0\n\n> one\n> two"
+        );
+
+        let long = notifier.format_report(&report(false, &"输出\n".repeat(2000)));
+        assert!(long.len() <= MARKDOWN_LIMIT);
+        assert!(long.starts_with("<font color=\"warning\">❌ make failed</font>"));
+        assert!(long.ends_with("> 输出"));
+
+        let mut failed = report(false, "one\n");
+        failed.error = Some("错误".repeat(2000));
+        let long = notifier.format_report(&failed);
+        assert!(long.len() <= MARKDOWN_LIMIT);
+        assert!(long.starts_with("<font color=\"warning\">❌ make failed</font>"));
+        assert!(long.ends_with("> one"));
+    }
+
+    #[test]
+    fn test_text_payload_mentions() {
+        let notifier = WeComNotifier::from_config(&config("https://example.com/send")).unwrap();
+        assert_eq!(
+            notifier.text_payload("make failed", Some(false)),
+            json!({
+                "msgtype": "text",
+                "text": {
+                    "content": "make failed",
+                    "mentioned_list": ["zhangsan", "@all"],
+                    "mentioned_mobile_list": ["13800000000"]
+                }
+            })
+        );
+        assert_eq!(
+            notifier.text_payload("hi", None),
+            json!({"msgtype": "text", "text": {"content": "hi"}})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_report_uploads_long_output() {
+        let server = MockServer::start(vec![MockResponse::json(
+            json!({"errcode": 0, "errmsg": "ok", "type": "file", "media_id": "m-1"}),
+        )])
+        .await;
+        let notifier = WeComNotifier::from_config(&config(&format!(
+            "{}/cgi-bin/webhook/send?key=abc",
+            server.url
+        )))
+        .unwrap();
+
+        let report = report(false, "line one\nline two\n");
+        notifier.send_report(&report).await.unwrap();
+
+        let requests = server.requests();
+        let paths: Vec<_> = requests.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "/cgi-bin/webhook/send?key=abc",
+                "/cgi-bin/webhook/upload_media?key=abc&type=file",
+                "/cgi-bin/webhook/send?key=abc",
+                "/cgi-bin/webhook/send?key=abc",
+            ]
+        );
+        assert_eq!(requests[0].json()["msgtype"], "markdown");
+        let upload = requests[1].body_text();
+        assert!(upload.contains(&format!(
+            "name=\"media\"; filename=\"{}\"",
+            report.log_file_name()
+        )));
+        assert!(upload.contains("line one\nline two\n"));
+        assert_eq!(
+            requests[2].json(),
+            json!({"msgtype": "file", "file": {"media_id": "m-1"}})
+        );
+        assert_eq!(requests[3].json()["text"]["mentioned_list"][0], "zhangsan");
+    }
+
+    #[tokio::test]
+    async fn test_api_error() {
+        let server = MockServer::start(vec![MockResponse::json(
+            json!({"errcode": 93000, "errmsg": "invalid webhook url"}),
+        )])
+        .await;
+        let err = WeComNotifier::from_config(&config(&server.url))
+            .unwrap()
+            .send("hello")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid webhook url"));
+    }
+}
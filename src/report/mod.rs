@@ -0,0 +1,386 @@
+use chrono::{DateTime, Local};
+use std::time::Duration;
+
+/// Characters of output a `{output}` placeholder expands to.
+const TEMPLATE_OUTPUT_LIMIT: usize = 20000;
+
+/// Outcome of a monitored command, handed to every notification sender.
+#[derive(Debug, Clone)]
+pub struct RunReport {
+    pub command: String,
+    pub args: Vec<String>,
+    pub host: String,
+    pub started_at: DateTime<Local>,
+    pub duration: Duration,
+    pub exit_code: Option<i32>,
+    pub success: bool,
+    pub output: Option<String>,
+    pub error: Option<String>,
+}
+
+impl RunReport {
+    pub fn new(command: impl Into<String>, args: Vec<String>) -> Self {
+        Self {
+            command: command.into(),
+            args,
+            host: hostname(),
+            started_at: Local::now(),
+            duration: Duration::default(),
+            exit_code: None,
+            success: true,
+            output: None,
+            error: None,
+        }
+    }
+
+    /// The command line as it would be typed in a shell.
+    pub fn command_line(&self) -> String {
+        command_line(&self.command, &self.args)
+    }
+
+    /// Key shared by every run of the same command on the same host, so an
+    /// incident tool can resolve on success what a failure opened.
+    #[cfg(any(feature = "pagerduty", feature = "opsgenie"))]
+    pub fn incident_key(&self) -> String {
+        use sha2::{Digest, Sha256};
+
+        let digest = Sha256::digest(format!("{}\n{}", self.host, self.command_line()));
+        let hex: String = digest[..8]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        format!("notifyme-{}", hex)
+    }
+
+    /// `succeeded` or `failed`, for titles and summaries.
+    pub fn status_text(&self) -> &'static str {
+        if self.success {
+            "succeeded"
+        } else {
+            "failed"
+        }
+    }
+
+    /// The last `max_chars` characters of the output, for channels that
+    /// limit message size.
+    pub fn output_tail(&self, max_chars: usize) -> &str {
+        let output = self.output.as_deref().unwrap_or_default();
+        match output.char_indices().rev().nth(max_chars.saturating_sub(1)) {
+            Some((idx, _)) if max_chars > 0 => &output[idx..],
+            Some(_) => "",
+            None => output,
+        }
+    }
+
+    /// The first `max_chars` characters of the error. The error is all of
+    /// stderr for a failed command, and its start usually names the cause.
+    pub fn error_head(&self, max_chars: usize) -> &str {
+        let error = self.error.as_deref().unwrap_or_default();
+        match error.char_indices().nth(max_chars) {
+            Some((idx, _)) => &error[..idx],
+            None => error,
+        }
+    }
+
+    /// `<command>-<start time>.log`, for uploading the output as a file.
+    /// Anything unusual in the command name is replaced.
+    pub fn log_file_name(&self) -> String {
+        let command = self.command.rsplit('/').next().unwrap_or_default();
+        let command: String = command
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        format!(
+            "{}-{}.log",
+            command,
+            self.started_at.format("%Y%m%d-%H%M%S")
+        )
+    }
+
+    /// Values of the `{command}`, `{status}`, ... placeholders in user
+    /// supplied templates.
+    pub fn placeholders(&self) -> Vec<(&'static str, String)> {
+        let exit_code = self
+            .exit_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        vec![
+            ("{icon}", if self.success { "✅" } else { "❌" }.to_string()),
+            ("{command}", self.command.clone()),
+            ("{command_line}", self.command_line()),
+            ("{status}", self.status_text().to_string()),
+            ("{host}", self.host.clone()),
+            (
+                "{started_at}",
+                self.started_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+            ),
+            ("{duration}", format_duration(self.duration)),
+            ("{exit_code}", exit_code),
+            ("{error}", self.error.clone().unwrap_or_default()),
+            (
+                "{output}",
+                self.output_tail(TEMPLATE_OUTPUT_LIMIT).to_string(),
+            ),
+        ]
+    }
+
+    /// `template` with the placeholders replaced.
+    pub fn render(&self, template: &str) -> String {
+        fill_template(template, &self.placeholders(), str::to_string)
+    }
+
+    /// Plain text rendering used by senders that have no richer format.
+    pub fn to_message(&self) -> String {
+        let mut message = match &self.output {
+            Some(output) => format!("Command output:\n{}", output),
+            None => "Command executed but produced no output".to_string(),
+        };
+
+        if let Some(e) = &self.error {
+            message.push_str(&format!("\nCommand failed with error: {}", e));
+        }
+        message
+    }
+}
+
+/// What is known about a run from its start, handed to every lifecycle
+/// hook of a sender.
+#[derive(Debug, Clone)]
+pub struct RunContext {
+    /// Name of the config set the senders were built from.
+    pub config_set: String,
+    pub command: String,
+    pub args: Vec<String>,
+    pub host: String,
+    pub started_at: DateTime<Local>,
+    /// Whether senders should announce the start of the run.
+    pub notify_on_start: bool,
+}
+
+impl RunContext {
+    /// Context of the run `report` is being collected for.
+    pub fn new(config_set: impl Into<String>, report: &RunReport) -> Self {
+        Self {
+            config_set: config_set.into(),
+            command: report.command.clone(),
+            args: report.args.clone(),
+            host: report.host.clone(),
+            started_at: report.started_at,
+            notify_on_start: false,
+        }
+    }
+
+    pub fn command_line(&self) -> String {
+        command_line(&self.command, &self.args)
+    }
+
+    /// Plain text announcement used by senders that have no richer format.
+    pub fn start_message(&self) -> String {
+        format!("⏳ {} started on host {}", self.command_line(), self.host)
+    }
+}
+
+/// Snapshot of a command that is still running.
+#[derive(Debug, Clone, Default)]
+pub struct RunProgress {
+    pub elapsed: Duration,
+    /// Most recent output, bounded in size.
+    pub output_tail: String,
+}
+
+impl RunProgress {
+    /// The last `count` lines of the output seen so far.
+    pub fn last_lines(&self, count: usize) -> &str {
+        if count == 0 {
+            return "";
+        }
+        let output = self.output_tail.trim_end_matches('\n');
+        match output.match_indices('\n').rev().nth(count - 1) {
+            Some((idx, _)) => &output[idx + 1..],
+            None => output,
+        }
+    }
+}
+
+fn command_line(command: &str, args: &[String]) -> String {
+    if args.is_empty() {
+        command.to_string()
+    } else {
+        format!("{} {}", command, args.join(" "))
+    }
+}
+
+/// `template` with each placeholder of `values` replaced by its value passed
+/// through `encode`. The template is scanned once, so placeholders inside a
+/// substituted value are left alone.
+pub fn fill_template(
+    template: &str,
+    values: &[(&str, String)],
+    encode: impl Fn(&str) -> String,
+) -> String {
+    let mut text = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match values
+            .iter()
+            .find(|(placeholder, _)| rest.starts_with(placeholder))
+        {
+            Some((placeholder, value)) => {
+                text.push_str(&encode(value));
+                rest = &rest[placeholder.len()..];
+            }
+            None => {
+                text.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+/// Human readable duration such as `1h 2m 3s` or `850ms`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs == 0 {
+        return format!("{}ms", duration.as_millis());
+    }
+
+    let (hours, minutes, seconds) = (secs / 3600, secs % 3600 / 60, secs % 60);
+    if hours > 0 {
+        format!("{}h {}m {}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+pub fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    let ret = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if ret != 0 {
+        return "unknown".to_string();
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_message() {
+        let mut report = RunReport::new("ls", vec!["-l".to_string()]);
+        assert_eq!(report.command_line(), "ls -l");
+        assert_eq!(
+            report.to_message(),
+            "Command executed but produced no output"
+        );
+
+        report.output = Some("total 0".to_string());
+        report.error = Some("exit status 2".to_string());
+        assert_eq!(
+            report.to_message(),
+            "Command output:\ntotal 0\nCommand failed with error: exit status 2"
+        );
+    }
+
+    #[test]
+    fn test_render_once() {
+        let mut report = RunReport::new("make", vec![]);
+        report.host = "build-1".to_string();
+        report.success = true;
+        report.output = Some("{host} {unknown".to_string());
+        assert_eq!(
+            report.render("{output} on {host} {{status}}"),
+            "{host} {unknown on build-1 {succeeded}"
+        );
+        assert_eq!(
+            fill_template(
+                "{a}{b}",
+                &[("{a}", "{b}".to_string()), ("{b}", "x".to_string())],
+                |v| v.to_uppercase()
+            ),
+            "{B}X"
+        );
+    }
+
+    #[cfg(any(feature = "pagerduty", feature = "opsgenie"))]
+    #[test]
+    fn test_incident_key() {
+        let mut report = RunReport::new("backup.sh", vec!["--full".to_string()]);
+        report.host = "db-1".to_string();
+        let key = report.incident_key();
+        assert!(key.starts_with("notifyme-"));
+        assert_eq!(key.len(), "notifyme-".len() + 16);
+
+        let mut rerun = RunReport::new("backup.sh", vec!["--full".to_string()]);
+        rerun.host = "db-1".to_string();
+        assert_eq!(rerun.incident_key(), key);
+        rerun.host = "db-2".to_string();
+        assert_ne!(rerun.incident_key(), key);
+    }
+
+    #[test]
+    fn test_output_tail() {
+        let mut report = RunReport::new("ls", vec![]);
+        assert_eq!(report.output_tail(10), "");
+
+        report.output = Some("héllo wörld".to_string());
+        assert_eq!(report.output_tail(5), "wörld");
+        assert_eq!(report.output_tail(100), "héllo wörld");
+        assert_eq!(report.output_tail(0), "");
+    }
+
+    #[test]
+    fn test_error_head() {
+        let mut report = RunReport::new("ls", vec![]);
+        assert_eq!(report.error_head(10), "");
+
+        report.error = Some("héllo wörld".to_string());
+        assert_eq!(report.error_head(5), "héllo");
+        assert_eq!(report.error_head(100), "héllo wörld");
+        assert_eq!(report.error_head(0), "");
+    }
+
+    #[test]
+    fn test_start_message() {
+        let mut report = RunReport::new("make", vec!["test".to_string()]);
+        report.host = "build-1".to_string();
+        let context = RunContext::new("ci", &report);
+        assert_eq!(context.config_set, "ci");
+        assert_eq!(
+            context.start_message(),
+            "⏳ make test started on host build-1"
+        );
+    }
+
+    #[test]
+    fn test_progress_last_lines() {
+        let mut progress = RunProgress::default();
+        assert_eq!(progress.last_lines(3), "");
+
+        progress.output_tail = "one\ntwo\nthree\nfour\n".to_string();
+        assert_eq!(progress.last_lines(2), "three\nfour");
+        assert_eq!(progress.last_lines(10), "one\ntwo\nthree\nfour");
+        assert_eq!(progress.last_lines(0), "");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_millis(850)), "850ms");
+        assert_eq!(format_duration(Duration::from_secs(42)), "42s");
+        assert_eq!(format_duration(Duration::from_secs(125)), "2m 5s");
+        assert_eq!(format_duration(Duration::from_secs(3723)), "1h 2m 3s");
+    }
+}
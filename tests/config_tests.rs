@@ -1,15 +1,156 @@
 #[cfg(test)]
 mod tests {
-    use super::*;
-    use crate::config::*;
+    use notifyme::config::*;
+    use std::path::PathBuf;
+
+    fn temp_config_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "notifyme-config-tests-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
 
     #[test]
     fn test_read_config() {
-        // TODO: Implement test cases for reading configs
+        let dir = temp_config_dir("read");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("default.xml"),
+            r#"<config-set name="default"><notification-configs><telegram><token>t</token><chat_id>42</chat_id></telegram></notification-configs></config-set>"#,
+        )
+        .unwrap();
+
+        let manager = ConfigManager::with_config_dir(dir.clone());
+        let config_set = manager.read_config("default").unwrap();
+        assert_eq!(config_set.name, "default");
+        match &config_set.notification_configs.configs[..] {
+            [NotificationConfigType::Telegram(config)] => {
+                assert_eq!(config.token, "t");
+                assert_eq!(config.chat_id, "42");
+            }
+            other => panic!("unexpected configs: {:?}", other),
+        }
+
+        assert!(manager.read_config("missing").is_err());
+        std::fs::remove_dir_all(dir).unwrap();
     }
 
     #[test]
     fn test_write_config() {
-        // TODO: Implement test cases for writing configs
+        let dir = temp_config_dir("write");
+        let manager = ConfigManager::with_config_dir(dir.clone());
+
+        let mut config_set = ConfigSet::new("test".to_string());
+        config_set
+            .notification_configs
+            .configs
+            .push(NotificationConfigType::Lark(LarkConfig {
+                webhook_url: "https://example.com/hook".to_string(),
+                sign_key: "key".to_string(),
+                at: None,
+                message_type: Some("card".to_string()),
+                log_url: None,
+                mentions: vec![LarkMentionConfig {
+                    id_type: Some("all".to_string()),
+                    id: String::new(),
+                    when: Some("failure".to_string()),
+                }],
+            }));
+        manager.write_config(&config_set).unwrap();
+
+        assert_eq!(manager.list_configs().unwrap(), vec!["test".to_string()]);
+        let read_back = manager.read_config("test").unwrap();
+        match &read_back.notification_configs.configs[..] {
+            [NotificationConfigType::Lark(config)] => {
+                assert_eq!(config.webhook_url, "https://example.com/hook");
+                assert_eq!(config.sign_key, "key");
+                assert_eq!(config.message_type.as_deref(), Some("card"));
+                assert_eq!(config.log_url, None);
+                assert_eq!(config.mentions.len(), 1);
+                assert_eq!(config.mentions[0].id_type.as_deref(), Some("all"));
+                assert_eq!(config.mentions[0].when.as_deref(), Some("failure"));
+            }
+            other => panic!("unexpected configs: {:?}", other),
+        }
+
+        manager.delete_config("test").unwrap();
+        assert!(manager.list_configs().unwrap().is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_plugin_config() {
+        let dir = temp_config_dir("plugin");
+        let manager = ConfigManager::with_config_dir(dir.clone());
+
+        let mut config_set = ConfigSet::new("plugin".to_string());
+        config_set
+            .notification_configs
+            .configs
+            .push(NotificationConfigType::Plugin(PluginConfig {
+                name: "chat".to_string(),
+                path: None,
+                timeout: Some(10),
+                params: vec![
+                    PluginParam {
+                        key: "room".to_string(),
+                        value: "ops".to_string(),
+                    },
+                    PluginParam {
+                        key: "token".to_string(),
+                        value: "secret".to_string(),
+                    },
+                ],
+            }));
+        manager.write_config(&config_set).unwrap();
+
+        let content = std::fs::read_to_string(dir.join("plugin.xml")).unwrap();
+        assert!(content.contains(r#"<plugin name="chat" timeout="10">"#));
+        assert!(content.contains(r#"<param key="room" value="ops"/>"#));
+
+        let read_back = manager.read_config("plugin").unwrap();
+        match &read_back.notification_configs.configs[..] {
+            [NotificationConfigType::Plugin(config)] => {
+                assert_eq!(config.name, "chat");
+                assert_eq!(config.path, None);
+                assert_eq!(config.timeout, Some(10));
+                assert_eq!(config.params.len(), 2);
+                assert_eq!(config.params[1].key, "token");
+                assert_eq!(config.params[1].value, "secret");
+            }
+            other => panic!("unexpected configs: {:?}", other),
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_email_recipients() {
+        let dir = temp_config_dir("email");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("mail.xml"),
+            r#"<config-set name="mail"><notification-configs><email><to>ops@example.org</to><to>dev@example.org</to><bcc>audit@example.org</bcc><from>notifyme@example.org</from><transport>sendmail</transport></email></notification-configs></config-set>"#,
+        )
+        .unwrap();
+
+        let manager = ConfigManager::with_config_dir(dir.clone());
+        let config_set = manager.read_config("mail").unwrap();
+        manager.write_config(&config_set).unwrap();
+
+        let read_back = manager.read_config("mail").unwrap();
+        match &read_back.notification_configs.configs[..] {
+            [NotificationConfigType::Email(config)] => {
+                assert_eq!(config.to, vec!["ops@example.org", "dev@example.org"]);
+                assert!(config.cc.is_empty());
+                assert_eq!(config.bcc, vec!["audit@example.org"]);
+                assert_eq!(config.transport.as_deref(), Some("sendmail"));
+                assert_eq!(config.smtp.auth, None);
+            }
+            other => panic!("unexpected configs: {:?}", other),
+        }
+        std::fs::remove_dir_all(dir).unwrap();
     }
 }